@@ -0,0 +1,33 @@
+//! serves a directory or `.pak` archive to `Network` loaders, for testing them locally
+//!
+//! usage: `yage-asset-server <dir> [addr]`. the address defaults to `127.0.0.1:8080`, so only
+//! this machine can reach it. pass `0.0.0.0:8080` to serve other devices on the network too
+
+use std::process::ExitCode;
+use yage::net::server::Server;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(root), addr, None) = (args.next(), args.next(), args.next()) else {
+        eprintln!("usage: yage-asset-server <dir> [addr]");
+        return ExitCode::FAILURE;
+    };
+    let addr = addr.unwrap_or_else(|| "127.0.0.1:8080".to_owned());
+
+    let server = match Server::bind(&addr, &root) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("yage-asset-server: serving {root} on {addr}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match server.local_addr() {
+        Ok(bound) => println!("serving {root} on {bound}"),
+        Err(_) => println!("serving {root} on {addr}"),
+    }
+    if let Err(error) = server.run() {
+        eprintln!("yage-asset-server: {error}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,53 @@
+//! packs directories into a `.pak` archive that `FileSystem` can mount as a search path
+//!
+//! usage: `yage-pack [--compress lz4|deflate] <output.pak> <dir>...`. later directories win when
+//! two have the same file. with `--compress`, files are stored compressed unless that doesn't
+//! make them any smaller
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::process::ExitCode;
+use yage::fs::pak::Builder;
+use yage_core::asset::compress::Codec;
+
+const USAGE: &str = "usage: yage-pack [--compress lz4|deflate] <output.pak> <dir>...";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut codec = None;
+    if args.peek().is_some_and(|arg| arg == "--compress") {
+        args.next();
+        codec = match args.next().as_deref().and_then(Codec::from_extension) {
+            Some(codec) => Some(codec),
+            None => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        };
+    }
+    let (Some(output), dirs) = (args.next(), args.collect::<Vec<_>>()) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    if dirs.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut builder = Builder::new();
+    builder.compress(codec);
+    for dir in &dirs {
+        if let Err(error) = builder.add_dir(dir) {
+            eprintln!("yage-pack: reading {dir}: {error}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let written = File::create(&output).and_then(|file| builder.write_to(BufWriter::new(file)));
+    if let Err(error) = written {
+        eprintln!("yage-pack: writing {output}: {error}");
+        return ExitCode::FAILURE;
+    }
+    println!("packed {} files into {output}", builder.len());
+    ExitCode::SUCCESS
+}
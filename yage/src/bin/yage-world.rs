@@ -0,0 +1,42 @@
+//! converts levels between the text form they are edited in and the binary form they ship in
+//!
+//! usage: `yage-world <input> <output>`. either form is read, the output's extension picks what
+//! is written: binary for `.wld`, text for anything else
+
+use std::process::ExitCode;
+use yage_core::asset::world::World;
+
+const USAGE: &str = "usage: yage-world <input> <output>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [input, output] = &args[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let world = match std::fs::read(input) {
+        Ok(bytes) => World::decode(&bytes),
+        Err(error) => {
+            eprintln!("yage-world: reading {input}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let world = match world {
+        Ok(world) => world,
+        Err(error) => {
+            eprintln!("yage-world: {input}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let contents = match output.ends_with(".wld") {
+        true => world.to_binary(),
+        false => world.to_text().into_bytes(),
+    };
+    if let Err(error) = std::fs::write(output, contents) {
+        eprintln!("yage-world: writing {output}: {error}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
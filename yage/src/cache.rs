@@ -1,8 +1,13 @@
+use crate::events::{AssetEvent, AssetEvents, EventHub};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::hash::{Hash, Hasher};
 use std::num::NonZero;
-use std::sync::Mutex;
-use std::sync::atomic::{AtomicU32, Ordering};
-use yage_core::asset::Cache;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use yage_core::asset::{Asset, AssetKind, Bytes, Cache, ContentHash};
+use yage_core::machine_cog::OnlyCalledByThisCrate;
+use yage_core::states::new::SearchPaths;
 
 /// this allows us to unconditionally implement `Hash`, `Eq`, and `PartialEq`
 /// this takes account for nothing on the data; as long as other.id == self.id (and by association, other.id != self.id), these are considered equal
@@ -33,39 +38,332 @@ impl<T> Hash for IdHashed<T> {
     }
 }
 
-pub struct AssetCache {}
+/// `lookup` hands out a shared copy of an entry's asset, so removing or reloading the entry
+/// never pulls it out from under whoever is reading it. the old version goes away with the
+/// last copy.
+///
+/// every name gets its own entry, but a name loaded with the same bytes (and kind) as another
+/// live entry shares that entry's asset instead of keeping a second copy. unhashed assets are
+/// never shared. reloading a name only
+/// replaces the entries for that name
+pub struct AssetCache {
+    entries: Mutex<HashMap<NonZero<usize>, Slot>>,
+    by_content: Mutex<ContentIndex>,
+    next_index: AtomicUsize,
+    events: EventHub,
+}
+
+struct Slot {
+    asset: Arc<Asset<Bytes>>,
+    /// the file this came from, for reloading
+    name: Option<Arc<str>>,
+    changed: bool,
+}
+
+impl Slot {
+    fn new(asset: Arc<Asset<Bytes>>, name: Option<Arc<str>>) -> Self {
+        Self {
+            asset,
+            name,
+            changed: false,
+        }
+    }
+
+    fn duplicate(&self) -> Self {
+        Self {
+            asset: self.asset.clone(),
+            name: self.name.clone(),
+            changed: self.changed,
+        }
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name)
+    }
+}
+
+impl AssetCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            by_content: Mutex::new(HashMap::new()),
+            next_index: AtomicUsize::new(1),
+            events: EventHub::default(),
+        }
+    }
+
+    /// hands out an index nothing else in this cache is using
+    pub fn reserve(&self) -> NonZero<usize> {
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        NonZero::new(index).expect("asset index overflowed")
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// every event from now on. loads, reloads and evictions come from the cache itself,
+    /// failures from the loaders filling it
+    pub fn subscribe(&self) -> AssetEvents {
+        self.events.subscribe()
+    }
+
+    /// sends `event` to every subscriber. for loaders to report failures, the cache sends the rest
+    pub fn emit(&self, event: AssetEvent) {
+        self.events.emit(event);
+    }
+
+    /// like `Cache::insert`, but remembers which file the asset came from so `reload` can find it
+    pub fn insert_named(&self, index: &NonZero<usize>, name: &str, value: Asset<Bytes>) -> bool {
+        let name: Arc<str> = Arc::from(name);
+        let asset = Arc::new(value);
+        match self.entries.lock().unwrap().entry(*index) {
+            Entry::Occupied(_) => return false,
+            Entry::Vacant(slot) => {
+                slot.insert(Slot::new(asset.clone(), Some(name.clone())));
+                remember(&mut self.by_content.lock().unwrap(), &asset);
+            }
+        }
+        self.events.emit(AssetEvent::Loaded {
+            index: *index,
+            name,
+        });
+        true
+    }
+
+    /// a new entry for `name`. if a live entry already has `value`'s bytes, the new one shares
+    /// its asset rather than keeping another copy
+    pub fn insert_content(&self, name: &str, value: Asset<Bytes>) -> NonZero<usize> {
+        let name: Arc<str> = Arc::from(name);
+        let mut entries = self.entries.lock().unwrap();
+        let mut by_content = self.by_content.lock().unwrap();
+        let shared = content_key(&value)
+            .and_then(|key| by_content.get(&key))
+            .and_then(Weak::upgrade);
+        let asset = shared.unwrap_or_else(|| {
+            let asset = Arc::new(value);
+            remember(&mut by_content, &asset);
+            asset
+        });
+        let index = self.reserve();
+        entries.insert(index, Slot::new(asset, Some(name.clone())));
+        drop((entries, by_content));
+        self.events.emit(AssetEvent::Loaded { index, name });
+        index
+    }
+
+    /// the asset of a live entry holding these bytes, if there is one
+    pub fn find_content(&self, hash: ContentHash, kind: AssetKind) -> Option<Arc<Asset<Bytes>>> {
+        let by_content = self.by_content.lock().unwrap();
+        by_content.get(&(hash, kind)).and_then(Weak::upgrade)
+    }
+
+    /// whether any entry came from `name`
+    pub fn contains_name(&self, name: &str) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries.values().any(|slot| slot.has_name(name))
+    }
+
+    /// swaps in a fresh `asset` for every entry loaded from `name` and flags them as changed.
+    /// entries for other names keep their asset, even if it was shared. returns how many
+    /// entries were replaced
+    pub fn reload(&self, name: &str, asset: Asset<Bytes>) -> usize {
+        let asset = Arc::new(asset);
+        let mut entries = self.entries.lock().unwrap();
+        let mut replaced = Vec::new();
+        let mut old = Vec::new();
+        for (index, slot) in entries.iter_mut() {
+            if slot.has_name(name) {
+                old.push(std::mem::replace(&mut slot.asset, asset.clone()));
+                slot.changed = true;
+                replaced.push(*index);
+            }
+        }
+        drop(entries);
+        let mut by_content = self.by_content.lock().unwrap();
+        if !replaced.is_empty() {
+            remember(&mut by_content, &asset);
+        }
+        let old_keys: Vec<_> = old.iter().filter_map(|old| content_key(old)).collect();
+        // whatever nobody else was reading is freed here, outside the entries lock
+        drop(old);
+        for key in old_keys {
+            forget_if_dead(&mut by_content, &key);
+        }
+        drop(by_content);
+
+        let name: Arc<str> = Arc::from(name);
+        for &index in &replaced {
+            self.events.emit(AssetEvent::Reloaded {
+                index,
+                name: name.clone(),
+            });
+        }
+        replaced.len()
+    }
+
+    /// forgets which entries changed. call it once a frame, before reloading
+    pub fn clear_changed(&self) {
+        for slot in self.entries.lock().unwrap().values_mut() {
+            slot.changed = false;
+        }
+    }
+}
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<SearchPaths> for AssetCache {
+    fn from(_: SearchPaths) -> Self {
+        Self::new()
+    }
+}
+
+impl From<Vec<&'static str>> for AssetCache {
+    fn from(_: Vec<&'static str>) -> Self {
+        Self::new()
+    }
+}
 
 impl Cache<NonZero<usize>> for AssetCache {
-    fn clone_entry(
-        &self,
-        index: &NonZero<usize>,
-        token: yage_core::machine_cog::OnlyCalledByThisCrate,
-    ) -> NonZero<usize> {
-        todo!()
+    fn clone_entry(&self, index: &NonZero<usize>, _: OnlyCalledByThisCrate) -> NonZero<usize> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get(index)
+            .map(Slot::duplicate)
+            .expect("cloned an asset that is not in the cache");
+        let new_index = self.reserve();
+        entries.insert(new_index, entry);
+        new_index
     }
 
-    fn insert(
+    fn try_clone_entry(
         &self,
         index: &NonZero<usize>,
-        value: <NonZero<usize> as yage_core::asset::Key>::Value,
-    ) -> bool {
-        todo!()
+        _: OnlyCalledByThisCrate,
+    ) -> Option<NonZero<usize>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(index).map(Slot::duplicate)?;
+        let new_index = self.reserve();
+        entries.insert(new_index, entry);
+        Some(new_index)
     }
 
-    fn lookup(
-        &self,
-        index: &NonZero<usize>,
-    ) -> Option<&<NonZero<usize> as yage_core::asset::Key>::Value> {
-        todo!()
+    fn insert(&self, index: &NonZero<usize>, value: Asset<Bytes>) -> bool {
+        match self.entries.lock().unwrap().entry(*index) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(slot) => {
+                slot.insert(Slot::new(Arc::new(value), None));
+                true
+            }
+        }
     }
 
-    fn remove(
-        &self,
-        index: &NonZero<usize>,
-    ) -> Option<(
-        NonZero<usize>,
-        <NonZero<usize> as yage_core::asset::Key>::Value,
-    )> {
-        todo!()
+    fn changed(&self, index: &NonZero<usize>) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries.get(index).is_some_and(|slot| slot.changed)
+    }
+
+    fn lookup(&self, index: &NonZero<usize>) -> Option<Arc<Asset<Bytes>>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(index).map(|slot| slot.asset.clone())
+    }
+
+    fn remove(&self, index: &NonZero<usize>) -> Option<(NonZero<usize>, Asset<Bytes>)> {
+        let slot = self.entries.lock().unwrap().remove(index)?;
+        let key = content_key(&slot.asset);
+        let asset = Arc::unwrap_or_clone(slot.asset);
+        if let Some(key) = key {
+            forget_if_dead(&mut self.by_content.lock().unwrap(), &key);
+        }
+        self.events.emit(AssetEvent::Evicted {
+            index: *index,
+            name: slot.name,
+        });
+        Some((*index, asset))
+    }
+}
+
+type ContentIndex = HashMap<(ContentHash, AssetKind), Weak<Asset<Bytes>>>;
+
+/// what `asset` is found by in `by_content`. assets that were never hashed aren't shared
+fn content_key(asset: &Asset<Bytes>) -> Option<(ContentHash, AssetKind)> {
+    Some((asset.hash()?, asset.kind))
+}
+
+/// makes `asset` the one later loads of the same bytes share, unless a live one already is
+fn remember(by_content: &mut ContentIndex, asset: &Arc<Asset<Bytes>>) {
+    let Some(key) = content_key(asset) else {
+        return;
+    };
+    let weak = Arc::downgrade(asset);
+    match by_content.entry(key) {
+        Entry::Occupied(mut entry) if entry.get().strong_count() == 0 => {
+            entry.insert(weak);
+        }
+        Entry::Occupied(_) => {}
+        Entry::Vacant(entry) => {
+            entry.insert(weak);
+        }
+    }
+}
+
+/// drops `key` once no entry holds its asset any more
+fn forget_if_dead(by_content: &mut ContentIndex, key: &(ContentHash, AssetKind)) {
+    if by_content
+        .get(key)
+        .is_some_and(|weak| weak.strong_count() == 0)
+    {
+        by_content.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yage_core::asset::{AccessError, OwnedHandle};
+
+    #[test]
+    fn typed_handles() {
+        let cache = Arc::new(AssetCache::new());
+        let index = cache.reserve();
+        let asset = Asset::new(AssetKind::JsonBody, Bytes::new()).with_value(7u32);
+        cache.insert(&index, asset);
+
+        let handle = OwnedHandle::new(index, cache.clone());
+        let expected = std::any::type_name::<String>();
+        assert_eq!(
+            handle.get::<String>(),
+            Err(AccessError::TypeMismatch { expected })
+        );
+        let handle = match handle.typed::<String>() {
+            Ok(_) => panic!("typed as the wrong type"),
+            Err(handle) => handle,
+        };
+        let typed = handle.typed::<u32>().ok().unwrap();
+        assert_eq!(*typed.get().unwrap(), 7);
+
+        let raw = cache.reserve();
+        cache.insert(&raw, Asset::new(AssetKind::RawData, Bytes::new()));
+        let raw = OwnedHandle::new(raw, cache.clone());
+        assert_eq!(raw.get::<u32>(), Err(AccessError::NotDecoded));
+    }
+
+    #[test]
+    fn lookups_outlive_the_entry() {
+        let cache = AssetCache::new();
+        let index = cache.reserve();
+        let bytes = Bytes::from(&b"still here"[..]);
+        cache.insert(&index, Asset::new(AssetKind::RawData, bytes));
+        let asset = cache.lookup(&index).unwrap();
+        cache.remove(&index).unwrap();
+        assert!(cache.lookup(&index).is_none());
+        assert_eq!(&**asset.data(), b"still here");
     }
 }
@@ -0,0 +1,196 @@
+//! what happens to assets over their lifetime, for tooling and UI to follow along. every
+//! `AssetCache::subscribe` gets its own copy of every event from then on
+
+use crate::fs::manifest::CorruptAsset;
+use std::collections::VecDeque;
+use std::io;
+use std::num::NonZero;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+/// how many events a subscriber that stopped reading holds on to. older ones are dropped first
+const QUEUE_LIMIT: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub enum AssetEvent {
+    /// `name` was loaded into the entry at `index`
+    Loaded {
+        index: NonZero<usize>,
+        name: Arc<str>,
+    },
+    /// the entry at `index` was swapped for a new version of `name`
+    Reloaded {
+        index: NonZero<usize>,
+        name: Arc<str>,
+    },
+    /// the entry at `index` left the cache. `name` is the file it came from, if any
+    Evicted {
+        index: NonZero<usize>,
+        name: Option<Arc<str>>,
+    },
+    Failed {
+        name: Arc<str>,
+        error: Arc<io::Error>,
+    },
+}
+
+impl AssetEvent {
+    /// a `Failed` event with a copy of `error`, the original goes back to whoever loaded
+    pub fn failed(name: &str, error: &io::Error) -> Self {
+        let copy = match CorruptAsset::find(error) {
+            Some(corrupt) => io::Error::from(corrupt.clone()),
+            None => io::Error::new(error.kind(), error.to_string()),
+        };
+        Self::Failed {
+            name: Arc::from(name),
+            error: Arc::new(copy),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<AssetEvent>,
+    missed: u64,
+    waker: Option<Waker>,
+    /// the cache is gone, nothing more is coming
+    closed: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct EventHub {
+    subscribers: Mutex<Vec<Weak<Mutex<Queue>>>>,
+}
+
+impl EventHub {
+    pub(crate) fn subscribe(&self) -> AssetEvents {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&queue));
+        AssetEvents { queue }
+    }
+
+    pub(crate) fn emit(&self, event: AssetEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // dropped subscribers go the next time anything happens
+        subscribers.retain(|queue| {
+            let Some(queue) = queue.upgrade() else {
+                return false;
+            };
+            let mut queue = queue.lock().unwrap();
+            if queue.events.len() == QUEUE_LIMIT {
+                queue.events.pop_front();
+                queue.missed += 1;
+            }
+            queue.events.push_back(event.clone());
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+            true
+        });
+    }
+}
+
+impl Drop for EventHub {
+    fn drop(&mut self) {
+        for queue in self.subscribers.get_mut().unwrap().drain(..) {
+            if let Some(queue) = queue.upgrade() {
+                let mut queue = queue.lock().unwrap();
+                queue.closed = true;
+                if let Some(waker) = queue.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+/// one subscriber's events. `recv` waits for the next one, `try_next` and `drain` never block
+pub struct AssetEvents {
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl AssetEvents {
+    pub fn try_next(&mut self) -> Option<AssetEvent> {
+        self.queue.lock().unwrap().events.pop_front()
+    }
+
+    /// everything that happened since the last call, say once a frame
+    pub fn drain(&mut self) -> impl Iterator<Item = AssetEvent> + use<> {
+        std::mem::take(&mut self.queue.lock().unwrap().events).into_iter()
+    }
+
+    /// `None` once the cache is gone and every event has been read
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<AssetEvent>> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv(self)
+    }
+
+    /// how many events were dropped because this subscriber fell too far behind
+    pub fn missed(&self) -> u64 {
+        self.queue.lock().unwrap().missed
+    }
+}
+
+pub struct Recv<'a>(&'a mut AssetEvents);
+
+impl Future for Recv<'_> {
+    type Output = Option<AssetEvent>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::AssetCache;
+    use yage_core::asset::{Asset, AssetKind, Bytes, OwnedHandle};
+
+    #[test]
+    fn weak_handles_and_events() {
+        let cache = Arc::new(AssetCache::new());
+        let mut events = cache.subscribe();
+        let bytes = Bytes::from(&b"bytes"[..]);
+        let index = cache.insert_content("a.txt", Asset::new(AssetKind::RawData, bytes.clone()));
+        cache.reload("a.txt", Asset::new(AssetKind::RawData, bytes));
+
+        let owned = OwnedHandle::new(index, cache.clone());
+        let weak = owned.downgrade();
+        let upgraded = weak.upgrade().unwrap();
+        assert_ne!(upgraded.index(), index);
+        drop(upgraded);
+        assert!(weak.is_alive());
+        drop(owned);
+        assert!(!weak.is_alive());
+        assert!(weak.upgrade().is_none());
+
+        let seen: Vec<_> = events.drain().collect();
+        assert!(matches!(&seen[0], AssetEvent::Loaded { name, .. } if &**name == "a.txt"));
+        assert!(matches!(seen[1], AssetEvent::Reloaded { index: i, .. } if i == index));
+        assert!(matches!(seen[2], AssetEvent::Evicted { index: i, .. } if i != index));
+        assert!(matches!(seen[3], AssetEvent::Evicted { index: i, .. } if i == index));
+        assert_eq!(seen.len(), 4);
+
+        drop(cache);
+        assert!(events.try_next().is_none());
+        let waker = Waker::noop();
+        let poll = events.poll_next(&mut Context::from_waker(waker));
+        assert!(matches!(poll, Poll::Ready(None)));
+    }
+}
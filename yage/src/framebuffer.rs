@@ -0,0 +1,307 @@
+//! a `Renderer` that draws into a plain `Vec` in memory, for games that don't need a gpu and
+//! for tests that want to look at what was drawn
+
+use std::fmt;
+use yage_core::renderer::{MakeRenderer, Renderer};
+
+/// `width * height` pixels of any `Copy` type, row by row from the top left. everything drawn
+/// is clipped to the framebuffer, so drawing partly or fully off screen is fine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer<P> {
+    width: u32,
+    height: u32,
+    pixels: Vec<P>,
+    /// what `rect` fills with, since `Renderer::rect` doesn't take a pixel
+    fill: P,
+    /// how far from their centre `arc`s are drawn, since `Renderer::arc` doesn't take a radius
+    arc_radius: u32,
+    /// whether anything was drawn since the last `sync`
+    dirty: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferError {
+    /// `width * height` pixels don't fit in memory
+    TooLarge { width: u32, height: u32 },
+    /// a buffer passed to `overwrite_with` has fewer pixels than its size says
+    BufferTooSmall { expected: usize, len: usize },
+}
+
+impl<P: Copy + Default> Framebuffer<P> {
+    /// every pixel starts out as `P::default()`
+    pub fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or(FramebufferError::TooLarge { width, height })?;
+        let mut pixels = Vec::new();
+        pixels
+            .try_reserve_exact(len)
+            .map_err(|_| FramebufferError::TooLarge { width, height })?;
+        pixels.resize(len, P::default());
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            fill: P::default(),
+            arc_radius: 0,
+            dirty: false,
+        })
+    }
+}
+
+impl<P: Copy> Framebuffer<P> {
+    pub fn get(&self, x: u32, y: u32) -> Option<P> {
+        self.index(x as i64, y as i64)
+            .map(|index| self.pixels[index])
+    }
+
+    pub fn clear(&mut self, pix: P) {
+        self.pixels.fill(pix);
+        self.dirty = true;
+    }
+
+    pub fn fill(&self) -> P {
+        self.fill
+    }
+
+    pub fn set_fill(&mut self, pix: P) {
+        self.fill = pix;
+    }
+
+    pub fn arc_radius(&self) -> u32 {
+        self.arc_radius
+    }
+
+    pub fn set_arc_radius(&mut self, radius: u32) {
+        self.arc_radius = radius;
+    }
+
+    /// whether anything was drawn since the last `sync`
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn into_pixels(self) -> Vec<P> {
+        self.pixels
+    }
+
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        let inside = (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y);
+        inside.then(|| y as usize * self.width as usize + x as usize)
+    }
+
+    /// signed so that shapes may hang off any edge
+    fn put(&mut self, x: i64, y: i64, pix: P) {
+        if let Some(index) = self.index(x, y) {
+            self.pixels[index] = pix;
+        }
+    }
+
+    /// midpoint circle, only the octants set in `parts`. octant `k` runs from `45 * k` to
+    /// `45 * (k + 1)` degrees, counter-clockwise from the right like on paper, so bit 0 is the
+    /// part just above the rightmost point and `0b0000_0011` the top right quarter
+    fn octants(&mut self, cx: i64, cy: i64, radius: i64, parts: u8, pix: P) {
+        let (mut a, mut b) = (radius, 0);
+        let mut error = 1 - radius;
+        while a >= b {
+            let points = [
+                (a, -b),
+                (b, -a),
+                (-b, -a),
+                (-a, -b),
+                (-a, b),
+                (-b, a),
+                (b, a),
+                (a, b),
+            ];
+            for (octant, (dx, dy)) in points.into_iter().enumerate() {
+                if parts & (1 << octant) != 0 {
+                    self.put(cx + dx, cy + dy, pix);
+                }
+            }
+            b += 1;
+            if error < 0 {
+                error += 2 * b + 1;
+            } else {
+                a -= 1;
+                error += 2 * (b - a) + 1;
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+impl<P: Copy> Renderer for Framebuffer<P> {
+    type Pixel = P;
+    type Error = FramebufferError;
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn data(&self) -> &[P] {
+        &self.pixels
+    }
+
+    fn data_mut(&mut self) -> &mut [P] {
+        self.dirty = true;
+        &mut self.pixels
+    }
+
+    /// there is nothing to present to, so this only reports whether anything was drawn since
+    /// the last call
+    fn sync(&mut self) -> Result<bool, FramebufferError> {
+        Ok(std::mem::take(&mut self.dirty))
+    }
+
+    fn pixel(&mut self, x: u32, y: u32, pix: P) -> Result<(), FramebufferError> {
+        self.put(x as i64, y as i64, pix);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// the octants in `parts` of a circle of `arc_radius` around `(x, y)`, see `set_arc_radius`
+    fn arc(&mut self, x: u32, y: u32, parts: u8, pix: P) -> Result<(), FramebufferError> {
+        self.octants(x as i64, y as i64, self.arc_radius as i64, parts, pix);
+        Ok(())
+    }
+
+    /// the outline of a circle around `(x, y)`. a negative radius draws nothing
+    fn circle(&mut self, x: u32, y: u32, radius: i32, pix: P) -> Result<(), FramebufferError> {
+        if radius >= 0 {
+            self.octants(x as i64, y as i64, radius as i64, u8::MAX, pix);
+        }
+        Ok(())
+    }
+
+    /// fills with the framebuffer's `fill`, see `set_fill`
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), FramebufferError> {
+        let right = x.saturating_add(width).min(self.width);
+        let bottom = y.saturating_add(height).min(self.height);
+        if x >= right {
+            return Ok(());
+        }
+        for row in y..bottom {
+            let start = row as usize * self.width as usize;
+            self.pixels[start + x as usize..start + right as usize].fill(self.fill);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn overwrite_with(
+        &mut self,
+        x: u32,
+        y: u32,
+        buffer_width: u32,
+        buffer_height: u32,
+        buffer: &[P],
+    ) -> Result<(), FramebufferError> {
+        let expected = buffer_width as usize * buffer_height as usize;
+        if buffer.len() < expected {
+            return Err(FramebufferError::BufferTooSmall {
+                expected,
+                len: buffer.len(),
+            });
+        }
+        let columns = buffer_width.min(self.width.saturating_sub(x)) as usize;
+        let rows = buffer_height.min(self.height.saturating_sub(y)) as usize;
+        if columns == 0 {
+            return Ok(());
+        }
+        for row in 0..rows {
+            let from = row * buffer_width as usize;
+            let to = (y as usize + row) * self.width as usize + x as usize;
+            self.pixels[to..to + columns].copy_from_slice(&buffer[from..from + columns]);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+impl<P: Copy + Default> MakeRenderer for Framebuffer<P> {
+    fn new(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        Framebuffer::new(width, height)
+    }
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { width, height } => {
+                write!(f, "a {width}x{height} framebuffer doesn't fit in memory")
+            }
+            Self::BufferTooSmall { expected, len } => {
+                write!(f, "expected a buffer of {expected} pixels, got {len}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramebufferError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(fb: &Framebuffer<u8>) -> Vec<String> {
+        fb.data()
+            .chunks(fb.width() as usize)
+            .map(|row| {
+                row.iter()
+                    .map(|&p| if p == 0 { '.' } else { '#' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clips_to_bounds() {
+        let mut fb = Framebuffer::<u8>::new(6, 5).unwrap();
+        assert!(!fb.sync().unwrap());
+        fb.set_fill(1);
+        fb.rect(4, 3, 10, 10).unwrap();
+        fb.pixel(100, 0, 1).unwrap();
+        fb.overwrite_with(0, 4, 3, 2, &[1, 0, 1, 1, 1, 1]).unwrap();
+        assert!(fb.sync().unwrap());
+        assert!(!fb.sync().unwrap());
+        assert_eq!(
+            rows(&fb),
+            ["......", "......", "......", "....##", "#.#.##"]
+        );
+        assert_eq!(
+            fb.overwrite_with(0, 0, 2, 2, &[1]),
+            Err(FramebufferError::BufferTooSmall {
+                expected: 4,
+                len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn circles_and_arcs() {
+        let mut fb = Framebuffer::<u8>::new(7, 7).unwrap();
+        fb.circle(3, 3, 2, 1).unwrap();
+        assert_eq!(
+            rows(&fb),
+            [
+                ".......", "..###..", ".#...#.", ".#...#.", ".#...#.", "..###..", "......."
+            ]
+        );
+
+        // the top right quarter, hanging off the top of the screen
+        let mut fb = Framebuffer::<u8>::new(7, 7).unwrap();
+        fb.set_arc_radius(4);
+        fb.arc(2, 2, 0b0000_0011, 1).unwrap();
+        assert_eq!(
+            rows(&fb),
+            [
+                ".....#.", "......#", "......#", ".......", ".......", ".......", "......."
+            ]
+        );
+    }
+}
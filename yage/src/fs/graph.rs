@@ -0,0 +1,125 @@
+//! which loaded asset needs which. loads use it to pull dependencies in first, hot reloads use it
+//! to rebuild everything that sits on top of a changed file
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    dependencies: HashMap<String, Vec<String>>,
+    dependents: HashMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// records what `name` depends on, replacing whatever it depended on before
+    pub fn set(&mut self, name: &str, dependencies: Vec<String>) {
+        for old in self.dependencies.remove(name).unwrap_or_default() {
+            if let Some(dependents) = self.dependents.get_mut(&old) {
+                dependents.remove(name);
+            }
+        }
+        for dependency in &dependencies {
+            self.dependents
+                .entry(dependency.clone())
+                .or_default()
+                .insert(name.to_owned());
+        }
+        if !dependencies.is_empty() {
+            self.dependencies.insert(name.to_owned(), dependencies);
+        }
+    }
+
+    pub fn dependencies(&self, name: &str) -> &[String] {
+        self.dependencies.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// the assets that depend on `name` directly
+    pub fn dependents(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.dependents
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// `changed` and everything that depends on it, however indirectly. each name comes after
+    /// everything it depends on, so reloading in this order never decodes against stale data
+    pub fn reload_order(&self, changed: &[String]) -> Vec<String> {
+        let mut affected = HashSet::new();
+        let mut pending: Vec<&str> = changed.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            if affected.insert(name) {
+                pending.extend(self.dependents(name));
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut roots: Vec<&str> = affected.iter().copied().collect();
+        roots.sort_unstable();
+        for root in roots {
+            self.visit(root, &affected, &mut visited, &mut order);
+        }
+        order
+    }
+
+    /// depth first over dependencies, pushing each name once all of its dependencies are in
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        affected: &HashSet<&str>,
+        visited: &mut HashSet<&'a str>,
+        order: &mut Vec<String>,
+    ) {
+        if !visited.insert(name) {
+            return;
+        }
+        for dependency in self.dependencies(name) {
+            if affected.contains(dependency.as_str()) {
+                self.visit(dependency, affected, visited, order);
+            }
+        }
+        order.push(name.to_owned());
+    }
+}
+
+/// the error for the loads in `chain` leading back around to `name`. the whole chain is kept,
+/// from the asset that was asked for down to the one that closes the loop
+pub fn cycle_error(chain: &[String], name: &str) -> io::Error {
+    let mut path: Vec<&str> = chain.iter().map(String::as_str).collect();
+    path.push(name);
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("dependency cycle: {}", path.join(" -> ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_order() {
+        let mut graph = DependencyGraph::new();
+        graph.set("level.obj", vec!["level.mtl".into()]);
+        graph.set("level.mtl", vec!["wood.png".into(), "stone.png".into()]);
+        graph.set("props.mtl", vec!["wood.png".into()]);
+
+        let order = graph.reload_order(&["wood.png".into()]);
+        assert_eq!(order, ["wood.png", "level.mtl", "level.obj", "props.mtl"]);
+        assert_eq!(graph.reload_order(&["stone.png".into()]).len(), 3);
+
+        graph.set("level.mtl", vec!["stone.png".into()]);
+        assert_eq!(
+            graph.dependents("wood.png").collect::<Vec<_>>(),
+            ["props.mtl"]
+        );
+
+        let error = cycle_error(&["a".into(), "b".into()], "a");
+        assert_eq!(error.to_string(), "dependency cycle: a -> b -> a");
+    }
+}
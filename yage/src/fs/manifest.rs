@@ -0,0 +1,161 @@
+//! the expected content hash of every shipped file. the format is what `sha256sum` prints, one
+//! `<hex>  <name>` line per file, so `sha256sum $(find . -type f) > manifest.sha256` makes one
+
+use super::vfs;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use yage_core::asset::ContentHash;
+
+/// where `FileSystem` looks for a manifest at startup
+pub const MANIFEST_NAME: &str = "manifest.sha256";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    hashes: BTreeMap<String, ContentHash>,
+}
+
+/// a file whose bytes don't match the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptAsset {
+    pub name: String,
+    pub expected: ContentHash,
+    pub actual: ContentHash,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut manifest = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{MANIFEST_NAME} line {}: {message}", number + 1),
+                )
+            };
+            let (hash, name) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid("expected `<hash>  <name>`"))?;
+            let hash = hash.parse().map_err(|error| invalid(&format!("{error}")))?;
+            // sha256sum marks binary mode reads with a `*` in front of the name
+            let name = name.trim_start_matches(' ').trim_start_matches('*');
+            let name = vfs::normalize(name).map_err(|error| invalid(&error.to_string()))?;
+            manifest.hashes.insert(name, hash);
+        }
+        Ok(manifest)
+    }
+
+    pub fn insert(&mut self, name: &str, hash: ContentHash) -> io::Result<&mut Self> {
+        self.hashes.insert(vfs::normalize(name)?, hash);
+        Ok(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<ContentHash> {
+        self.hashes.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, ContentHash)> {
+        self.hashes
+            .iter()
+            .map(|(name, hash)| (name.as_str(), *hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// checks `name`'s bytes hashed to what the manifest expects. files it doesn't list pass
+    pub fn verify(&self, name: &str, actual: ContentHash) -> Result<(), CorruptAsset> {
+        match self.get(name) {
+            Some(expected) if expected != actual => Err(CorruptAsset {
+                name: name.to_owned(),
+                expected,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, hash) in &self.hashes {
+            writeln!(f, "{hash}  {name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl CorruptAsset {
+    /// the corruption behind `error`, if that is what it is. loads report it as `InvalidData`
+    pub fn find(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for CorruptAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is corrupt: the manifest expects sha256 {}, its bytes hash to {}",
+            self.name, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for CorruptAsset {}
+
+impl From<CorruptAsset> for io::Error {
+    fn from(error: CorruptAsset) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::AssetCache;
+    use std::sync::Arc;
+    use yage_core::asset::{Asset, AssetKind, Bytes, Cache};
+
+    #[test]
+    fn verify_and_share() {
+        let good = ContentHash::of(b"hello");
+        let text = format!("{good}  ./text/hello.txt\n{good} *text/copy.txt\n");
+        let manifest = Manifest::parse(&text).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(Manifest::parse(&manifest.to_string()).unwrap(), manifest);
+
+        assert!(manifest.verify("text/hello.txt", good).is_ok());
+        assert!(manifest.verify("unlisted.txt", good).is_ok());
+        let error: io::Error = manifest
+            .verify("text/hello.txt", ContentHash::of(b"jello"))
+            .unwrap_err()
+            .into();
+        assert_eq!(CorruptAsset::find(&error).unwrap().expected, good);
+        assert!(Manifest::parse("abc  name").is_err());
+
+        let cache = AssetCache::new();
+        let bytes = Bytes::from(&b"hello"[..]);
+        let a = cache.insert_content("a.txt", Asset::new(AssetKind::RawData, bytes.clone()));
+        let b = cache.insert_content("b.txt", Asset::new(AssetKind::RawData, bytes.clone()));
+        let c = cache.insert_content("c.json", Asset::new(AssetKind::JsonBody, bytes));
+        assert_ne!(a, b);
+        let shared = |x, y| Arc::ptr_eq(&cache.lookup(x).unwrap(), &cache.lookup(y).unwrap());
+        assert!(shared(&a, &b));
+        assert!(!shared(&a, &c));
+        assert_eq!(cache.len(), 3);
+        assert!(cache.contains_name("b.txt"));
+    }
+}
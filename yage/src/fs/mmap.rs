@@ -0,0 +1,159 @@
+//! read-only memory maps, for assets big enough that copying them onto the heap is a waste. the
+//! kernel pages them in as they are touched and can drop them again when memory runs low
+//!
+//! mapping is `unsafe`: the file must not be written to or truncated while it is mapped.
+//! `MAP_PRIVATE` only keeps our own writes to ourselves, writes from anywhere else still show
+//! through, so bytes already handed out could change under whoever is reading them. touching a
+//! page past a truncated end kills the process with SIGBUS. editors that save by renaming a new
+//! file over the old one leave the mapped file alone
+
+use std::ffi::{c_int, c_long, c_void};
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::ptr;
+use yage_core::asset::{ByteSource, Bytes};
+
+const PROT_READ: c_int = 1;
+const MAP_PRIVATE: c_int = 2;
+const SC_PAGESIZE: c_int = 30;
+
+unsafe extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    fn sysconf(name: c_int) -> c_long;
+}
+
+pub struct Mmap {
+    /// the start of the mapping, which is page aligned. null for an empty map
+    ptr: *mut c_void,
+    /// the whole mapping, including the bytes before `offset`
+    mapped: usize,
+    /// where the bytes that were asked for start in the mapping
+    offset: usize,
+}
+
+// this process never writes through the mapping. whoever called `map` vouched for the rest
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// maps `len` bytes of `file` from `offset` on. the offset doesn't need to be page aligned,
+    /// but the range has to be inside the file
+    ///
+    /// # Safety
+    /// nothing, in this process or any other, may write to or truncate `file` until the map and
+    /// every `Bytes` made from it are dropped
+    pub unsafe fn map(file: &File, offset: u64, len: usize) -> io::Result<Self> {
+        let size = file.metadata()?.len();
+        if offset.checked_add(len as u64).is_none_or(|end| end > size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't map {len} bytes at {offset}, the file is {size} bytes"),
+            ));
+        }
+        if len == 0 {
+            return Ok(Self {
+                ptr: ptr::null_mut(),
+                mapped: 0,
+                offset: 0,
+            });
+        }
+        let page = unsafe { sysconf(SC_PAGESIZE) }.max(1) as u64;
+        let aligned = offset - offset % page;
+        let skip = (offset - aligned) as usize;
+        let mapped = skip + len;
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                mapped,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                aligned as i64,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr,
+            mapped,
+            offset: skip,
+        })
+    }
+
+    /// maps all of `file`
+    ///
+    /// # Safety
+    /// same as `map`
+    pub unsafe fn map_file(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        unsafe { Self::map(file, 0, len) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mapped - self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// hands the map over to the cache's byte type
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from_source(self)
+    }
+}
+
+impl ByteSource for Mmap {
+    fn bytes(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr.cast::<u8>().add(self.offset), self.len()) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { munmap(self.ptr, self.mapped) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unaligned_regions() {
+        let path = std::env::temp_dir().join(format!("yage-mmap-{}", std::process::id()));
+        let contents: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+        let file = File::open(&path).unwrap();
+
+        // SAFETY: nothing else knows about this file
+        unsafe {
+            let whole = Mmap::map_file(&file).unwrap().into_bytes();
+            assert!(whole.is_external());
+            assert_eq!(&*whole, &contents[..]);
+            let region = Mmap::map(&file, 4097, 10_000).unwrap();
+            assert_eq!(region.bytes(), &contents[4097..14_097]);
+            assert!(Mmap::map(&file, 4097, 0).unwrap().is_empty());
+            assert!(Mmap::map(&file, 19_000, 2000).is_err());
+        }
+
+        drop(file);
+        std::fs::remove_file(path).unwrap();
+    }
+}
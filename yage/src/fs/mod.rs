@@ -0,0 +1,715 @@
+use crate::cache::AssetCache;
+use crate::events::AssetEvent;
+use graph::DependencyGraph;
+use manifest::{CorruptAsset, MANIFEST_NAME, Manifest};
+use reader::{Reader, Request};
+use std::collections::HashMap;
+use std::io;
+use std::num::NonZero;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use stream::StreamSource;
+use vfs::Vfs;
+use watch::Watcher;
+use yage_core::asset::compress::Codec;
+use yage_core::asset::strings::{Locale, StringTableDecoder};
+use yage_core::asset::{
+    Asset, AssetDecoder, AssetKind, BorrowedHandle, Bytes, Cache, CowHandle, DecoderRegistry,
+    Fetch, Handle, Loader, OwnedHandle,
+};
+use yage_core::states::new::SearchPaths;
+
+pub mod graph;
+pub mod manifest;
+pub mod mmap;
+pub mod pak;
+pub(crate) mod reader;
+pub mod stream;
+#[cfg(feature = "io-uring")]
+mod uring;
+pub mod vfs;
+pub mod watch;
+
+pub struct FileSystem {
+    search_paths: Vec<&'static str>,
+    vfs: Arc<Vfs>,
+    cache: Arc<AssetCache>,
+    decoders: DecoderRegistry,
+    manifest: Option<Manifest>,
+    watcher: Mutex<Option<Watcher>>,
+    reader: Reader,
+    /// files at least this big are memory mapped instead of read
+    map_from: Option<u64>,
+    /// the entry the file system keeps for each file it has loaded
+    loaded: Mutex<HashMap<String, NonZero<usize>>>,
+    /// reads that have not come back yet, so a file two loads need is only read once
+    in_flight: Mutex<HashMap<String, Request>>,
+    graph: Mutex<DependencyGraph>,
+    /// what string tables are decoded in
+    locale: Arc<Mutex<Locale>>,
+}
+
+/// a changed file that could not be reloaded. the cache keeps the old version
+#[derive(Debug)]
+pub struct ReloadError {
+    pub name: String,
+    pub error: io::Error,
+}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reloading `{}`: {}", self.name, self.error)
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl FileSystem {
+    pub fn search_paths(&self) -> &[&'static str] {
+        &self.search_paths
+    }
+
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    /// for mounting more layers, like a mod directory. `None` while reads are still in flight
+    pub fn vfs_mut(&mut self) -> Option<&mut Vfs> {
+        Arc::get_mut(&mut self.vfs)
+    }
+
+    pub fn cache(&self) -> &Arc<AssetCache> {
+        &self.cache
+    }
+
+    pub fn decoders(&self) -> &DecoderRegistry {
+        &self.decoders
+    }
+
+    pub fn decoders_mut(&mut self) -> &mut DecoderRegistry {
+        &mut self.decoders
+    }
+
+    pub fn register_decoder<D: AssetDecoder>(
+        &mut self,
+        kind: AssetKind,
+        extensions: &[&str],
+        decoder: D,
+    ) {
+        self.decoders.register(kind, extensions, decoder);
+    }
+
+    /// the manifest loads are checked against, if the search paths had one
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// replaces the manifest loads are checked against. assets that are already loaded are not
+    /// checked again
+    pub fn set_manifest(&mut self, manifest: Option<Manifest>) {
+        self.manifest = manifest;
+    }
+
+    /// whether reads go through io_uring rather than the thread pool
+    pub fn uses_io_uring(&self) -> bool {
+        self.reader.uses_io_uring()
+    }
+
+    /// files at least this big are memory mapped rather than read, `None` (the default) reads
+    /// everything
+    pub fn map_threshold(&self) -> Option<u64> {
+        self.map_from
+    }
+
+    /// applies to loads and reloads from now on. mapped files skip the content hash unless the
+    /// manifest lists them, so they aren't shared with other entries by content
+    ///
+    /// # Safety
+    /// with a threshold set, no file that big, and no archive holding an uncompressed file that
+    /// big, may be written to or truncated while anything loaded from it is alive. hot reloading
+    /// those files is only sound if they are replaced by renaming a new file over them. see
+    /// `Mmap::map`
+    pub unsafe fn set_map_threshold(&mut self, map_from: Option<u64>) {
+        self.map_from = map_from;
+    }
+
+    /// puts a `StreamSource` for `name` in the cache rather than its bytes, for files too big to
+    /// keep in memory. nothing is read until the source is opened. streamed entries are never
+    /// shared with loads and never reloaded, every call makes an entry of its own
+    pub fn stream(&self, name: &str) -> io::Result<Handle<StreamSource, AssetCache>> {
+        let name = vfs::normalize(name)?;
+        let size = self.vfs.size(&name)?;
+        let kind = self.decoders.kind_of(&name);
+        let source = StreamSource::new(self.vfs.clone(), name, size);
+        let index = self.cache.reserve();
+        self.cache
+            .insert(&index, Asset::new(kind, Bytes::new()).with_value(source));
+        Ok(OwnedHandle::new(index, self.cache.clone())
+            .typed()
+            .ok()
+            .expect("the entry was made with a `StreamSource`"))
+    }
+
+    /// reads `name` from the first search path that has it, archives and directories alike.
+    /// this blocks, `Loader::load` is the non-blocking way in
+    pub fn read(&self, name: &str) -> io::Result<Arc<[u8]>> {
+        self.vfs.read(name)
+    }
+
+    /// checks `bytes` against the manifest, then runs whatever decoder is registered for
+    /// `name` over them. a mismatch fails with a `CorruptAsset`. mapped files are only hashed
+    /// when the manifest lists them, hashing would page them in whole
+    fn decode(&self, name: &str, bytes: Bytes) -> io::Result<Asset<Bytes>> {
+        let kind = self.decoders.kind_of(name);
+        let listed = self.manifest.as_ref().and_then(|manifest| manifest.get(name));
+        let mut asset = if bytes.is_external() && listed.is_none() {
+            Asset::unhashed(kind, bytes)
+        } else {
+            Asset::new(kind, bytes)
+        };
+        if let (Some(manifest), Some(hash)) = (&self.manifest, asset.hash()) {
+            manifest.verify(name, hash)?;
+        }
+        self.decoders
+            .decode_with(name, &mut asset, self)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(asset)
+    }
+
+    pub fn dependencies(&self, name: &str) -> Vec<String> {
+        self.graph.lock().unwrap().dependencies(name).to_vec()
+    }
+
+    /// forgets the entry the file system keeps for `name`, and drops it from the cache unless
+    /// another name shares it. copies made with `into_owned` stay. this needs `&mut self` since
+    /// borrowed handles point straight at that entry. `false` if `name` wasn't loaded
+    pub fn unload(&mut self, name: &str) -> bool {
+        let Ok(name) = vfs::normalize(name) else {
+            return false;
+        };
+        let loaded = self.loaded.get_mut().unwrap();
+        let Some(index) = loaded.remove(&name) else {
+            return false;
+        };
+        if !loaded.values().any(|&other| other == index) {
+            self.cache.remove(&index);
+        }
+        true
+    }
+
+    /// starts reading `name`, or joins a read of it that is already going
+    fn request(&self, name: &str) -> Request {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        in_flight
+            .entry(name.to_owned())
+            .or_insert_with(|| self.reader.read(self.vfs.clone(), name, self.map_from))
+            .clone()
+    }
+
+    /// decodes `bytes` and puts the result in the cache, unless a load that finished first
+    /// already did
+    fn store(&self, name: &str, bytes: Bytes) -> io::Result<NonZero<usize>> {
+        let asset = self.decode(name, bytes)?;
+        let mut loaded = self.loaded.lock().unwrap();
+        self.in_flight.lock().unwrap().remove(name);
+        if let Some(&index) = loaded.get(name) {
+            return Ok(index);
+        }
+        // a different name with the same bytes shares its data, not its entry
+        let index = self.cache.insert_content(name, asset);
+        loaded.insert(name.to_owned(), index);
+        Ok(index)
+    }
+
+    /// starts watching every directory search path for changes. archives are never watched
+    pub fn watch(&self) -> io::Result<()> {
+        let mut watcher = Watcher::new()?;
+        for mount in self.vfs.mounts() {
+            if let Some(dir) = mount.layer().dir() {
+                watcher.add_root(dir, mount.at())?;
+            }
+        }
+        *self.watcher.lock().unwrap() = Some(watcher);
+        Ok(())
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale.lock().unwrap().clone()
+    }
+
+    /// switches string tables to `locale`. every loaded table is reloaded in it, like a changed
+    /// file would be, so handles to them see the new text and report `changed()`
+    pub fn set_locale(&self, locale: Locale) -> Vec<ReloadError> {
+        *self.locale.lock().unwrap() = locale;
+        let tables: Vec<String> = self
+            .loaded
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|name| self.decoders.kind_of(name) == AssetKind::StringTable)
+            .cloned()
+            .collect();
+        self.reload(&tables)
+    }
+
+    /// reloads every cached asset whose file changed since the last call, and everything that
+    /// depends on one, so handles to them see the new version and report `changed()` until the
+    /// next call. meant to be called once a frame; does nothing unless `watch` was called first
+    pub fn reload_changed(&self) -> io::Result<Vec<ReloadError>> {
+        let mut changed = match &mut *self.watcher.lock().unwrap() {
+            Some(watcher) => watcher.changed()?,
+            None => return Ok(Vec::new()),
+        };
+        // `level.json.lz4` changing means `level.json` did
+        let uncompressed: Vec<_> = changed
+            .iter()
+            .filter_map(|name| Some(Codec::of_name(name)?.1.to_owned()))
+            .collect();
+        changed.extend(uncompressed);
+
+        self.cache.clear_changed();
+        Ok(self.reload(&changed))
+    }
+
+    /// rereads and decodes `changed` and everything that depends on them, dependencies first
+    fn reload(&self, changed: &[String]) -> Vec<ReloadError> {
+        let order = self.graph.lock().unwrap().reload_order(changed);
+        let mut errors = Vec::new();
+        for name in order {
+            if !self.cache.contains_name(&name) {
+                continue;
+            }
+            // SAFETY: `map_from` is only set by `set_map_threshold`, whose caller vouched for
+            // the files
+            let reloaded = unsafe { self.vfs.read_or_map(&name, self.map_from) }.and_then(|bytes| {
+                    let kind = self.decoders.kind_of(&name);
+                    let dependencies = self.decoders.dependencies(&name, kind, &bytes);
+                    self.graph.lock().unwrap().set(&name, dependencies);
+                    self.decode(&name, bytes)
+                });
+            match reloaded {
+                Ok(asset) => {
+                    self.cache.reload(&name, asset);
+                }
+                Err(error) => {
+                    self.cache.emit(AssetEvent::failed(&name, &error));
+                    errors.push(ReloadError { name, error });
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// lets decoders pull in the files an asset refers to. dependencies are already in the cache by
+/// the time their dependents decode, anything else is read from the search paths
+impl Fetch for FileSystem {
+    fn fetch(&self, name: &str) -> Option<Bytes> {
+        let name = vfs::normalize(name).ok()?;
+        let index = self.loaded.lock().unwrap().get(&name).copied();
+        if let Some(asset) = index.and_then(|index| self.cache.lookup(&index)) {
+            return Some(asset.data().clone());
+        }
+        // SAFETY: as in `reload_changed`
+        unsafe { self.vfs.read_or_map(&name, self.map_from) }.ok()
+    }
+}
+
+/// resolves and reads a file on the reader, loads everything its decoder says it depends on
+/// (all at once, and theirs in turn), then decodes it on whoever polls.
+/// the handle borrows the entry the file system keeps for that file, later loads of the same
+/// name finish straight away. `into_owned` gives an entry of your own
+pub struct FsFut<'a> {
+    this: &'a FileSystem,
+    name: String,
+    /// the loads that led to this one, for spotting cycles
+    chain: Vec<String>,
+    stage: Stage<'a>,
+}
+
+enum Stage<'a> {
+    Start,
+    Reading(Request),
+    Dependencies {
+        bytes: Bytes,
+        names: Vec<String>,
+        /// `None` once that dependency has loaded
+        loads: Vec<Option<Pin<Box<FsFut<'a>>>>>,
+    },
+    Done,
+}
+
+impl<'a> FsFut<'a> {
+    fn new(this: &'a FileSystem, name: String, chain: Vec<String>) -> Self {
+        Self {
+            this,
+            name,
+            chain,
+            stage: Stage::Start,
+        }
+    }
+
+    /// drives the load, and tells the cache's subscribers if it failed
+    fn poll_load(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<NonZero<usize>>> {
+        let poll = self.poll_stages(cx);
+        if let Poll::Ready(Err(error)) = &poll {
+            self.this.cache.emit(AssetEvent::failed(&self.name, error));
+        }
+        poll
+    }
+
+    fn poll_stages(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<NonZero<usize>>> {
+        let this = self.this;
+        loop {
+            match &mut self.stage {
+                Stage::Start => {
+                    // so `a/./b` and `a/b` share an entry, and reloads find it by the same name
+                    self.name = vfs::normalize(&self.name)?;
+                    if self.chain.contains(&self.name) {
+                        return Poll::Ready(Err(graph::cycle_error(&self.chain, &self.name)));
+                    }
+                    if let Some(&index) = this.loaded.lock().unwrap().get(&self.name) {
+                        return Poll::Ready(Ok(index));
+                    }
+                    self.stage = Stage::Reading(this.request(&self.name));
+                }
+                Stage::Reading(request) => {
+                    let bytes = match Pin::new(request).poll(cx) {
+                        Poll::Ready(Ok(bytes)) => bytes,
+                        Poll::Ready(Err(error)) => {
+                            this.in_flight.lock().unwrap().remove(&self.name);
+                            return Poll::Ready(Err(error));
+                        }
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let kind = this.decoders.kind_of(&self.name);
+                    let names = this.decoders.dependencies(&self.name, kind, &bytes);
+                    let mut chain = self.chain.clone();
+                    chain.push(self.name.clone());
+                    let loads = names
+                        .iter()
+                        .map(|name| Some(Box::pin(FsFut::new(this, name.clone(), chain.clone()))))
+                        .collect();
+                    self.stage = Stage::Dependencies {
+                        bytes,
+                        names,
+                        loads,
+                    };
+                }
+                Stage::Dependencies {
+                    bytes,
+                    names,
+                    loads,
+                } => {
+                    for (name, slot) in names.iter().zip(loads.iter_mut()) {
+                        let Some(load) = slot else {
+                            continue;
+                        };
+                        match load.as_mut().poll_load(cx) {
+                            Poll::Ready(Ok(_)) => *slot = None,
+                            Poll::Ready(Err(error)) => {
+                                this.in_flight.lock().unwrap().remove(&self.name);
+                                if CorruptAsset::find(&error).is_some() {
+                                    return Poll::Ready(Err(error));
+                                }
+                                let message = format!("`{}` needs `{name}`: {error}", self.name);
+                                return Poll::Ready(Err(io::Error::new(error.kind(), message)));
+                            }
+                            Poll::Pending => {}
+                        }
+                    }
+                    if loads.iter().any(Option::is_some) {
+                        return Poll::Pending;
+                    }
+                    let bytes = bytes.clone();
+                    let names = std::mem::take(names);
+                    self.stage = Stage::Done;
+                    this.graph.lock().unwrap().set(&self.name, names);
+                    return Poll::Ready(this.store(&self.name, bytes));
+                }
+                Stage::Done => panic!("`FsFut` polled after it finished"),
+            }
+        }
+    }
+}
+
+impl<'a> Future for FsFut<'a> {
+    type Output = io::Result<CowHandle<'a, AssetCache>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.get_mut();
+        let this = fut.this;
+        fut.poll_load(cx)
+            .map_ok(|index| CowHandle::Borrowed(BorrowedHandle::new(index, &this.cache)))
+    }
+}
+
+impl Loader<SearchPaths, &str, AssetCache> for FileSystem {
+    type Error = io::Error;
+    type InitFuture = std::future::Ready<io::Result<Self>>;
+    type LoadFuture<'a> = FsFut<'a>;
+
+    fn init(init: SearchPaths, cache: Arc<AssetCache>) -> Self::InitFuture
+    where
+        Self: Sized,
+    {
+        std::future::ready(Vfs::from_search_paths(&init.paths).and_then(|vfs| {
+            let manifest = match vfs.exists(MANIFEST_NAME) {
+                true => {
+                    let bytes = vfs.read(MANIFEST_NAME)?;
+                    let text = std::str::from_utf8(&bytes)
+                        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+                    Some(Manifest::parse(text)?)
+                }
+                false => None,
+            };
+            let locale = Arc::new(Mutex::new(Locale::default()));
+            let mut decoders = DecoderRegistry::new()
+                .with_json()
+                .with_meshes()
+                .with_images()
+                .with_fonts()
+                .with_worlds()
+                .with_preload();
+            let current = locale.clone();
+            decoders.register(
+                AssetKind::StringTable,
+                &["strings"],
+                StringTableDecoder::new(move || current.lock().unwrap().clone()),
+            );
+            Ok(Self {
+                search_paths: init.paths,
+                vfs: Arc::new(vfs),
+                cache,
+                decoders,
+                manifest,
+                watcher: Mutex::new(None),
+                reader: Reader::new(),
+                map_from: None,
+                loaded: Mutex::new(HashMap::new()),
+                in_flight: Mutex::new(HashMap::new()),
+                graph: Mutex::new(DependencyGraph::new()),
+                locale,
+            })
+        }))
+    }
+
+    fn load(&self, name: &str) -> Self::LoadFuture<'_> {
+        FsFut::new(self, name.to_owned(), Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, block_on, waker};
+    use yage_core::asset::json::Value;
+    use yage_core::asset::{ContentHash, DecodeContext};
+
+    /// a file system over a fresh directory holding `files`
+    fn file_system(test: &str, files: &[(&str, &str)]) -> (FileSystem, TempDir) {
+        let dir = TempDir::new(&format!("fs-{test}"));
+        for (name, text) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        let path: &'static str = Box::leak(dir.path().to_str().unwrap().into());
+        let paths = vec![path];
+        let cache = Arc::new(AssetCache::new());
+        let fs = block_on(FileSystem::init(SearchPaths { paths }, cache)).unwrap();
+        (fs, dir)
+    }
+
+    #[test]
+    fn hot_reload() {
+        let (fs, dir) = file_system("reload", &[("data/speed.json", "1")]);
+        fs.watch().unwrap();
+        let handle = block_on(fs.load("data/speed.json")).unwrap();
+        let old = Arc::downgrade(&handle.asset().unwrap());
+        assert!(!handle.changed());
+        assert!(fs.reload_changed().unwrap().is_empty());
+        assert!(!handle.changed());
+
+        std::fs::write(dir.join("data/speed.json"), "2").unwrap();
+        assert!(fs.reload_changed().unwrap().is_empty());
+        assert!(handle.changed());
+        let speed = handle.get::<Value>().unwrap();
+        assert_eq!(speed.as_i64(), Some(2));
+        // nothing held on to the old version, so the reload freed it
+        assert!(old.upgrade().is_none());
+
+        // the flag only lasts until the next call
+        assert!(fs.reload_changed().unwrap().is_empty());
+        assert!(!handle.changed());
+
+        std::fs::write(dir.join("data/speed.json"), "[").unwrap();
+        let errors = fs.reload_changed().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].name, "data/speed.json");
+        assert!(!handle.changed());
+        let speed = handle.get::<Value>().unwrap();
+        assert_eq!(speed.as_i64(), Some(2));
+    }
+
+    #[test]
+    fn same_bytes_reload_separately() {
+        let files = [("one.txt", "same"), ("two.txt", "same")];
+        let (fs, dir) = file_system("shared", &files);
+        fs.watch().unwrap();
+        let one = block_on(fs.load("one.txt")).unwrap();
+        let two = block_on(fs.load("two.txt")).unwrap();
+        assert_ne!(one.index(), two.index());
+        assert!(Arc::ptr_eq(&one.asset().unwrap(), &two.asset().unwrap()));
+
+        std::fs::write(dir.join("one.txt"), "different").unwrap();
+        assert!(fs.reload_changed().unwrap().is_empty());
+        assert!(one.changed());
+        assert!(!two.changed());
+        assert_eq!(&**one.asset().unwrap().data(), b"different");
+        assert_eq!(&**two.asset().unwrap().data(), b"same");
+    }
+
+    #[test]
+    fn mapped_files_skip_the_hash() {
+        let big = "b".repeat(8192);
+        let files = [
+            ("small.txt", "s"),
+            ("big.txt", big.as_str()),
+            ("listed.txt", big.as_str()),
+            ("corrupt.txt", big.as_str()),
+        ];
+        let (mut fs, _dir) = file_system("mapped", &files);
+        let mut manifest = Manifest::new();
+        manifest.insert("listed.txt", ContentHash::of(big.as_bytes())).unwrap();
+        manifest.insert("corrupt.txt", ContentHash::of(b"b")).unwrap();
+        fs.set_manifest(Some(manifest));
+        // SAFETY: nothing writes to these files while they are loaded
+        unsafe { fs.set_map_threshold(Some(4096)) };
+
+        let small = block_on(fs.load("small.txt")).unwrap().asset().unwrap();
+        assert!(!small.data().is_external());
+        assert!(small.hash().is_some());
+        let big = block_on(fs.load("big.txt")).unwrap().asset().unwrap();
+        assert!(big.data().is_external());
+        assert!(big.hash().is_none());
+        // the manifest needs the hash to check it
+        let listed = block_on(fs.load("listed.txt")).unwrap().asset().unwrap();
+        assert!(listed.data().is_external());
+        assert!(listed.hash().is_some());
+        let Err(error) = block_on(fs.load("corrupt.txt")) else {
+            panic!("loaded a file that doesn't match the manifest");
+        };
+        assert!(CorruptAsset::find(&error).is_some());
+    }
+
+    #[test]
+    fn loads_through_either_reader() {
+        let big = "0123456789".repeat(50_000);
+        let files = [
+            ("a.txt", "a"),
+            ("empty.txt", ""),
+            ("deep/big.txt", big.as_str()),
+        ];
+        assert!(!Reader::threaded().uses_io_uring());
+
+        for (test, reader) in [
+            ("load", Reader::new()),
+            ("load-threaded", Reader::threaded()),
+        ] {
+            let (mut fs, dir) = file_system(test, &files);
+            fs.reader = reader;
+            // started together, so the reads are in flight at the same time
+            let mut loads: Vec<_> = files
+                .iter()
+                .map(|(name, _)| Box::pin(fs.load(name)))
+                .collect();
+            let mut handles: Vec<_> = files.iter().map(|_| None).collect();
+            let waker = waker();
+            let mut cx = Context::from_waker(&waker);
+            while handles.iter().any(Option::is_none) {
+                for (load, handle) in loads.iter_mut().zip(&mut handles) {
+                    if handle.is_none()
+                        && let Poll::Ready(loaded) = load.as_mut().poll(&mut cx)
+                    {
+                        *handle = Some(loaded.unwrap());
+                    }
+                }
+                if handles.iter().any(Option::is_none) {
+                    std::thread::park();
+                }
+            }
+            for ((_, text), handle) in files.iter().zip(&handles) {
+                let asset = handle.as_ref().unwrap().asset().unwrap();
+                assert_eq!(&**asset.data(), text.as_bytes());
+            }
+
+            let Err(error) = block_on(fs.load("deep/missing.txt")) else {
+                panic!("loaded a file that isn't there");
+            };
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+            assert!(
+                error.to_string().contains(dir.path().to_str().unwrap()),
+                "{error}"
+            );
+        }
+    }
+
+    /// every line names another file, relative to this one, that has to load first
+    struct Links;
+
+    impl AssetDecoder for Links {
+        type Output = Vec<String>;
+        type Error = io::Error;
+
+        fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> io::Result<Vec<String>> {
+            let text = std::str::from_utf8(bytes).unwrap_or_default();
+            for line in text.lines() {
+                // dependencies are loaded before their dependents decode
+                cx.fetch_relative(line)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, line))?;
+            }
+            Ok(self.dependencies(cx, bytes))
+        }
+
+        fn dependencies(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+            let text = std::str::from_utf8(bytes).unwrap_or_default();
+            text.lines().map(|line| cx.relative(line)).collect()
+        }
+    }
+
+    #[test]
+    fn nested_dependencies_and_cycles() {
+        let files = [
+            ("a.links", "b.links\nsub/c.links"),
+            ("b.links", "sub/c.links"),
+            ("sub/c.links", "d.links"),
+            ("sub/d.links", ""),
+            ("x.links", "y.links"),
+            ("y.links", "z.links"),
+            ("z.links", "x.links"),
+        ];
+        let (mut fs, _dir) = file_system("graph", &files);
+        fs.register_decoder(AssetKind::Other, &["links"], Links);
+
+        let a = block_on(fs.load("a.links")).unwrap();
+        assert_eq!(*a.get::<Vec<String>>().unwrap(), ["b.links", "sub/c.links"]);
+        assert_eq!(fs.dependencies("sub/c.links"), ["sub/d.links"]);
+        assert_eq!(fs.loaded.lock().unwrap().len(), 4);
+        drop(a);
+
+        let Err(error) = block_on(fs.load("y.links")) else {
+            panic!("loaded a cycle");
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let cycle = "dependency cycle: y.links -> z.links -> x.links -> y.links";
+        assert!(error.to_string().ends_with(cycle), "{error}");
+        assert!(!fs.loaded.lock().unwrap().contains_key("x.links"));
+        assert!(fs.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,493 @@
+//! `.pak` archives: many asset files packed into one, with an index at the end
+//!
+//! layout, all integers little endian:
+//!
+//! ```text
+//! header   magic "YPAK", version: u32, index offset: u64, index length: u64, index crc32: u32
+//! data     each file's stored bytes, back to back
+//! index    entry count: u32, then per entry:
+//!          path length: u16, path (utf-8, `/` separated), offset: u64,
+//!          stored size: u64, size: u64, compression: u8 (0 none, 1 zlib, 2 lz4),
+//!          crc32 of the original bytes: u32
+//! ```
+
+use super::mmap::Mmap;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use yage_core::asset::Bytes;
+use yage_core::asset::checksum::crc32;
+use yage_core::asset::compress::{self, Codec};
+
+pub const MAGIC: [u8; 4] = *b"YPAK";
+pub const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// a zlib stream
+    Deflate,
+    /// the size as a u64, then an lz4 block, like a loose `.lz4` file
+    Lz4,
+}
+
+impl Compression {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::None),
+            1 => Some(Self::Deflate),
+            2 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Deflate => 1,
+            Self::Lz4 => 2,
+        }
+    }
+
+    pub fn codec(self) -> Option<Codec> {
+        match self {
+            Self::None => None,
+            Self::Deflate => Some(Codec::Deflate),
+            Self::Lz4 => Some(Codec::Lz4),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub offset: u64,
+    /// bytes taken up in the archive
+    pub stored_size: u64,
+    /// bytes once decompressed
+    pub size: u64,
+    pub compression: Compression,
+    pub crc32: u32,
+}
+
+trait Source: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Source for T {}
+
+/// an opened archive. only the index is kept in memory, files are read on demand
+pub struct Archive {
+    entries: BTreeMap<String, Entry>,
+    source: Mutex<Box<dyn Source>>,
+    /// where the archive was opened from, if it was a file. only those can be mapped
+    file: Option<PathBuf>,
+    /// the whole archive, mapped the first time a file in it is
+    mapped: Mutex<Option<Bytes>>,
+    /// the most a compressed file may decompress to
+    limit: usize,
+}
+
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Archive")
+            .field("entries", &self.entries)
+            .finish_non_exhaustive()
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("bad pak archive: {what}"),
+    )
+}
+
+/// a tiny cursor over the index, every read fails cleanly at the end
+struct IndexReader<'a>(&'a [u8]);
+
+impl IndexReader<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let (bytes, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .ok_or_else(|| invalid("index ended early"))?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(invalid("index ended early"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut archive = Self::from_reader(BufReader::new(File::open(path.as_ref())?))?;
+        archive.file = Some(path.as_ref().to_owned());
+        Ok(archive)
+    }
+
+    pub fn from_reader<R: Read + Seek + Send + 'static>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; HEADER_LEN as usize];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+        let mut header = IndexReader(&header);
+        if header.take::<4>()? != MAGIC {
+            return Err(invalid("not a pak file"));
+        }
+        let version = u32::from_le_bytes(header.take()?);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {version}")));
+        }
+        let index_offset = u64::from_le_bytes(header.take()?);
+        let index_len = u64::from_le_bytes(header.take()?);
+        let index_crc = u32::from_le_bytes(header.take()?);
+
+        let archive_len = reader.seek(SeekFrom::End(0))?;
+        if index_offset.checked_add(index_len) != Some(archive_len) {
+            return Err(invalid("index does not end the archive"));
+        }
+        let mut index = vec![0u8; index_len as usize];
+        reader.seek(SeekFrom::Start(index_offset))?;
+        reader.read_exact(&mut index)?;
+        if crc32(&index) != index_crc {
+            return Err(invalid("index checksum mismatch"));
+        }
+
+        let mut index = IndexReader(&index);
+        let count = u32::from_le_bytes(index.take()?);
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let path_len = u16::from_le_bytes(index.take()?) as usize;
+            let path = std::str::from_utf8(index.bytes(path_len)?)
+                .map_err(|_| invalid("path is not utf-8"))?
+                .to_owned();
+            let entry = Entry {
+                offset: u64::from_le_bytes(index.take()?),
+                stored_size: u64::from_le_bytes(index.take()?),
+                size: u64::from_le_bytes(index.take()?),
+                compression: Compression::from_byte(index.take::<1>()?[0])
+                    .ok_or_else(|| invalid("unknown compression"))?,
+                crc32: u32::from_le_bytes(index.take()?),
+            };
+            let in_bounds = entry
+                .offset
+                .checked_add(entry.stored_size)
+                .is_some_and(|end| entry.offset >= HEADER_LEN && end <= index_offset);
+            if !in_bounds {
+                return Err(invalid(&format!("`{path}` points outside the data")));
+            }
+            entries.insert(path, entry);
+        }
+
+        Ok(Self {
+            entries,
+            source: Mutex::new(Box::new(reader)),
+            file: None,
+            mapped: Mutex::new(None),
+            limit: compress::DEFAULT_LIMIT,
+        })
+    }
+
+    /// the most a compressed file may decompress to, files that claim more fail to read
+    pub fn decompress_limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_decompress_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn entry(&self, path: &str) -> Option<&Entry> {
+        self.entries.get(path)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// reads, decompresses and checks one file. `None` if the archive doesn't have it
+    pub fn read(&self, path: &str) -> Option<io::Result<Arc<[u8]>>> {
+        let entry = *self.entries.get(path)?;
+        Some(self.read_entry(path, &entry))
+    }
+
+    /// like `read`, but a file stored without compression in an archive opened from disk comes
+    /// back as a view into a memory map of the whole archive rather than a copy. anything else
+    /// is read as usual
+    ///
+    /// # Safety
+    /// the archive file must not be written to or truncated while anything mapped from it is
+    /// alive, see `Mmap::map`
+    pub unsafe fn map(&self, path: &str) -> Option<io::Result<Bytes>> {
+        let entry = *self.entries.get(path)?;
+        Some(match (&self.file, entry.compression) {
+            (Some(file), Compression::None) => unsafe { self.map_entry(file, path, &entry) },
+            _ => self.read_entry(path, &entry).map(Bytes::from),
+        })
+    }
+
+    /// # Safety
+    /// same as `map`
+    unsafe fn map_entry(&self, file: &Path, path: &str, entry: &Entry) -> io::Result<Bytes> {
+        let whole = {
+            let mut mapped = self.mapped.lock().unwrap_or_else(|e| e.into_inner());
+            match &*mapped {
+                Some(whole) => whole.clone(),
+                None => mapped
+                    .insert(unsafe { Mmap::map_file(&File::open(file)?)? }.into_bytes())
+                    .clone(),
+            }
+        };
+        let start = entry.offset as usize;
+        let end = start + entry.stored_size as usize;
+        // the index was checked against the archive when it was opened, not against the file
+        // as it is now
+        if end > whole.len() {
+            return Err(invalid(&format!("`{path}` is past the end of the archive")));
+        }
+        let bytes = whole.slice(start..end);
+        if crc32(&bytes) != entry.crc32 {
+            return Err(invalid(&format!("`{path}` checksum mismatch")));
+        }
+        Ok(bytes)
+    }
+
+    /// reads one file a bit at a time. files stored without compression in an archive opened
+    /// from disk are read straight out of it, without a checksum since nothing sees the whole
+    /// file at once; anything else is read in full first
+    pub fn reader(&self, path: &str) -> Option<io::Result<Box<dyn Read + Send>>> {
+        let entry = *self.entries.get(path)?;
+        let open = |file: &Path| -> io::Result<Box<dyn Read + Send>> {
+            let mut file = File::open(file)?;
+            file.seek(SeekFrom::Start(entry.offset))?;
+            Ok(Box::new(file.take(entry.stored_size)))
+        };
+        Some(match (&self.file, entry.compression) {
+            (Some(file), Compression::None) => open(file),
+            _ => self
+                .read_entry(path, &entry)
+                .map(|bytes| Box::new(io::Cursor::new(bytes)) as Box<dyn Read + Send>),
+        })
+    }
+
+    fn read_entry(&self, path: &str, entry: &Entry) -> io::Result<Arc<[u8]>> {
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        {
+            let mut source = self.source.lock().unwrap_or_else(|e| e.into_inner());
+            source.seek(SeekFrom::Start(entry.offset))?;
+            source.read_exact(&mut stored)?;
+        }
+        let bytes = match entry.compression.codec() {
+            None => stored,
+            Some(_) if entry.size > self.limit as u64 => {
+                return Err(invalid(&format!(
+                    "`{path}` decompresses to {} bytes, over the {} byte limit",
+                    entry.size, self.limit
+                )));
+            }
+            Some(codec) => compress::decompress(codec, &stored, entry.size as usize)
+                .map_err(|error| invalid(&format!("`{path}`: {error}")))?,
+        };
+        if bytes.len() as u64 != entry.size || crc32(&bytes) != entry.crc32 {
+            return Err(invalid(&format!("`{path}` checksum mismatch")));
+        }
+        Ok(Arc::from(bytes))
+    }
+}
+
+/// collects files and writes them out as one archive
+#[derive(Debug, Default)]
+pub struct Builder {
+    files: BTreeMap<String, Vec<u8>>,
+    compression: Option<Codec>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// compresses files with `codec` from now on. a file that doesn't get smaller is stored
+    /// as it is
+    pub fn compress(&mut self, codec: Option<Codec>) -> &mut Self {
+        self.compression = codec;
+        self
+    }
+
+    /// adds a file under `path`, replacing anything already there
+    pub fn add(&mut self, path: impl Into<String>, bytes: Vec<u8>) -> &mut Self {
+        self.files.insert(path.into(), bytes);
+        self
+    }
+
+    /// adds every file under `root`, named by its `/` separated path relative to `root`
+    pub fn add_dir(&mut self, root: impl AsRef<Path>) -> io::Result<&mut Self> {
+        let root = root.as_ref();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let name = path
+                    .strip_prefix(root)
+                    .expect("read_dir yields children of root")
+                    .components()
+                    .map(|part| {
+                        part.as_os_str().to_str().ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("{} is not valid utf-8", path.display()),
+                            )
+                        })
+                    })
+                    .collect::<io::Result<Vec<_>>>()?
+                    .join("/");
+                self.add(name, std::fs::read(&path)?);
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut index = Vec::new();
+        index.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+
+        let stored: Vec<(Compression, Cow<'_, [u8]>)> = self
+            .files
+            .values()
+            .map(|bytes| {
+                let Some(codec) = self.compression else {
+                    return (Compression::None, Cow::Borrowed(&bytes[..]));
+                };
+                let compressed = compress::compress(codec, bytes);
+                if compressed.len() >= bytes.len() {
+                    return (Compression::None, Cow::Borrowed(&bytes[..]));
+                }
+                let compression = match codec {
+                    Codec::Lz4 => Compression::Lz4,
+                    Codec::Deflate => Compression::Deflate,
+                };
+                (compression, Cow::Owned(compressed))
+            })
+            .collect();
+
+        let mut offset = HEADER_LEN;
+        for ((path, bytes), (compression, data)) in self.files.iter().zip(&stored) {
+            let path_len = u16::try_from(path.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("`{path}` is too long"))
+            })?;
+            index.extend_from_slice(&path_len.to_le_bytes());
+            index.extend_from_slice(path.as_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            index.push(compression.to_byte());
+            index.extend_from_slice(&crc32(bytes).to_le_bytes());
+            offset += data.len() as u64;
+        }
+
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&(index.len() as u64).to_le_bytes())?;
+        out.write_all(&crc32(&index).to_le_bytes())?;
+        for (_, data) in &stored {
+            out.write_all(data)?;
+        }
+        out.write_all(&index)?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let mut packed = Vec::new();
+        Builder::new()
+            .add("maps/one.json", b"{\"a\": 1}".to_vec())
+            .add("empty", Vec::new())
+            .write_to(&mut packed)
+            .unwrap();
+
+        let archive = Archive::from_reader(Cursor::new(packed.clone())).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(
+            &*archive.read("maps/one.json").unwrap().unwrap(),
+            b"{\"a\": 1}"
+        );
+        assert_eq!(&*archive.read("empty").unwrap().unwrap(), b"");
+        assert!(archive.read("maps/two.json").is_none());
+
+        // flip a byte of file data, the index still loads but the file doesn't
+        packed[HEADER_LEN as usize] ^= 1;
+        let archive = Archive::from_reader(Cursor::new(packed)).unwrap();
+        assert!(archive.read("maps/one.json").unwrap().is_err());
+    }
+
+    #[test]
+    fn compressed_entries() {
+        let text = b"compress me, compress me, compress me, compress me".repeat(20);
+        for codec in Codec::ALL {
+            let mut packed = Vec::new();
+            Builder::new()
+                .compress(Some(codec))
+                .add("text.txt", text.clone())
+                .add("short", b"abc".to_vec())
+                .write_to(&mut packed)
+                .unwrap();
+            assert!(packed.len() < text.len() / 4);
+
+            let mut archive = Archive::from_reader(Cursor::new(packed)).unwrap();
+            assert_ne!(
+                archive.entry("text.txt").unwrap().compression,
+                Compression::None
+            );
+            assert_eq!(
+                archive.entry("short").unwrap().compression,
+                Compression::None
+            );
+            assert_eq!(&*archive.read("text.txt").unwrap().unwrap(), &text[..]);
+            archive.set_decompress_limit(100);
+            assert!(archive.read("text.txt").unwrap().is_err());
+            assert_eq!(&*archive.read("short").unwrap().unwrap(), b"abc");
+        }
+    }
+}
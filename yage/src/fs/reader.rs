@@ -0,0 +1,193 @@
+//! reads files off the main loop. a small thread pool by default, io_uring with the
+//! `io-uring` feature when the kernel allows it
+
+use super::vfs::Vfs;
+use std::io;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use yage_core::asset::Bytes;
+
+type ReadResult = io::Result<Bytes>;
+
+/// where a read's result ends up, shared between the backend and every `Request` waiting on it
+#[derive(Default)]
+pub(crate) struct Slot {
+    state: Mutex<SlotState>,
+}
+
+#[derive(Default)]
+struct SlotState {
+    result: Option<Result<Bytes, Arc<io::Error>>>,
+    wakers: Vec<Waker>,
+}
+
+impl Slot {
+    pub(crate) fn complete(&self, result: ReadResult) {
+        let mut state = self.state.lock().unwrap();
+        state.result = Some(result.map_err(Arc::new));
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// a read that has been handed to a backend. clones wait on the same read
+#[derive(Clone)]
+pub(crate) struct Request {
+    slot: Arc<Slot>,
+}
+
+impl Request {
+    /// a request and the slot whoever does the work completes it through
+    pub(crate) fn new() -> (Self, Arc<Slot>) {
+        let slot = Arc::new(Slot::default());
+        (Self { slot: slot.clone() }, slot)
+    }
+}
+
+impl Future for Request {
+    type Output = ReadResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ReadResult> {
+        let mut state = self.slot.state.lock().unwrap();
+        match &state.result {
+            Some(Ok(bytes)) => Poll::Ready(Ok(bytes.clone())),
+            Some(Err(error)) => Poll::Ready(Err(io::Error::new(error.kind(), error.clone()))),
+            None => {
+                if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// everything a backend needs to find and read one file
+pub(super) struct Job {
+    pub(super) vfs: Arc<Vfs>,
+    pub(super) name: String,
+    /// files at least this big are mapped instead of read. from `FileSystem::set_map_threshold`,
+    /// whose caller vouched for them
+    pub(super) map_from: Option<u64>,
+    pub(super) slot: Arc<Slot>,
+}
+
+impl Job {
+    /// the blocking path, used by the thread pool and for anything io_uring can't do
+    pub(super) fn run(self) {
+        // SAFETY: see `map_from`
+        let result = unsafe { self.vfs.read_or_map(&self.name, self.map_from) };
+        self.slot.complete(result);
+    }
+}
+
+type Work = Box<dyn FnOnce() + Send>;
+
+/// a fixed number of threads taking work off one queue. dropping it lets them finish what is
+/// queued and waits for them
+pub(crate) struct ThreadPool {
+    jobs: Mutex<Option<Sender<Work>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// threads are called `name-0`, `name-1`, ...
+    pub(crate) fn new(name: &str, threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Work>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|i| {
+                let receiver = receiver.clone();
+                std::thread::Builder::new()
+                    .name(format!("{name}-{i}"))
+                    .spawn(move || {
+                        loop {
+                            // the lock is only held while waiting, not while working
+                            let work = receiver.lock().unwrap().recv();
+                            match work {
+                                Ok(work) => work(),
+                                Err(_) => break,
+                            }
+                        }
+                    })
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        Self {
+            jobs: Mutex::new(Some(sender)),
+            workers,
+        }
+    }
+
+    pub(crate) fn submit(&self, work: impl FnOnce() + Send + 'static) {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.as_ref()
+            .expect("the pool is only shut down on drop")
+            .send(Box::new(work))
+            .expect("worker threads exited early");
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // closing the channel lets every worker fall out of its loop
+        self.jobs.lock().unwrap().take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+enum Backend {
+    Threads(ThreadPool),
+    #[cfg(feature = "io-uring")]
+    Uring(super::uring::Driver),
+}
+
+pub(super) struct Reader {
+    backend: Backend,
+}
+
+impl Reader {
+    /// io_uring if it is compiled in and the kernel lets us set up a ring, threads otherwise
+    pub(super) fn new() -> Self {
+        #[cfg(feature = "io-uring")]
+        if let Ok(driver) = super::uring::Driver::new() {
+            return Self {
+                backend: Backend::Uring(driver),
+            };
+        }
+        Self::threaded()
+    }
+
+    pub(super) fn threaded() -> Self {
+        let threads = std::thread::available_parallelism().map_or(2, |n| n.get().min(4));
+        Self {
+            backend: Backend::Threads(ThreadPool::new("yage-fs", threads)),
+        }
+    }
+
+    pub(super) fn uses_io_uring(&self) -> bool {
+        !matches!(self.backend, Backend::Threads(_))
+    }
+
+    pub(super) fn read(&self, vfs: Arc<Vfs>, name: &str, map_from: Option<u64>) -> Request {
+        let (request, slot) = Request::new();
+        let job = Job {
+            vfs,
+            name: name.to_owned(),
+            map_from,
+            slot,
+        };
+        match &self.backend {
+            Backend::Threads(pool) => pool.submit(|| job.run()),
+            #[cfg(feature = "io-uring")]
+            Backend::Uring(driver) => driver.submit(job),
+        }
+        request
+    }
+}
@@ -0,0 +1,287 @@
+//! reads an asset a chunk at a time on a thread of its own, for files too big to keep in memory
+//! (music, video, huge levels). the thread reads at most `depth` chunks ahead of whoever is
+//! consuming them, then waits for them to catch up, so memory use stays bounded however big
+//! the file is
+
+use super::vfs::Vfs;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use yage_core::asset::Bytes;
+
+pub const CHUNK_SIZE: usize = 256 << 10;
+/// how many chunks the reading thread gets ahead by
+pub const DEPTH: usize = 4;
+
+struct Shared {
+    state: Mutex<State>,
+    /// the reading thread waits on this for room
+    room: Condvar,
+    /// blocking consumers wait on this for chunks
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    chunks: VecDeque<Bytes>,
+    /// what stopped the reading thread, handed out once the chunks before it are
+    error: Option<io::Error>,
+    /// the reading thread is done, one way or another
+    finished: bool,
+    waker: Option<Waker>,
+    /// the consumer is gone, the reading thread should stop
+    cancelled: bool,
+}
+
+impl State {
+    /// the next thing for the consumer, or `None` if there is nothing yet
+    fn take(&mut self) -> Option<Option<io::Result<Bytes>>> {
+        if let Some(chunk) = self.chunks.pop_front() {
+            return Some(Some(Ok(chunk)));
+        }
+        if let Some(error) = self.error.take() {
+            return Some(Some(Err(error)));
+        }
+        self.finished.then_some(None)
+    }
+}
+
+/// one pass over an asset, start to end. `poll_chunk` and `next_chunk` hand out chunks as they
+/// were read, `Read` copies out of them
+pub struct AssetStream {
+    shared: Arc<Shared>,
+    size: u64,
+    /// what is left of the chunk `Read` is partway through
+    current: Bytes,
+}
+
+impl AssetStream {
+    /// starts a thread reading `reader` in `chunk_size` pieces, at most `depth` of them ahead.
+    /// `size` is what `size` reports, it isn't checked against what is actually read
+    pub fn new(
+        reader: impl Read + Send + 'static,
+        size: u64,
+        chunk_size: usize,
+        depth: usize,
+    ) -> io::Result<Self> {
+        assert!(chunk_size > 0 && depth > 0, "streams need room for a chunk");
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            room: Condvar::new(),
+            ready: Condvar::new(),
+        });
+        let producer = shared.clone();
+        std::thread::Builder::new()
+            .name("yage-fs-stream".into())
+            .spawn(move || produce(&producer, reader, chunk_size, depth))?;
+        Ok(Self {
+            shared,
+            size,
+            current: Bytes::new(),
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// `None` once the whole file has been handed out
+    pub fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+        let mut state = self.lock();
+        match state.take() {
+            Some(next) => {
+                self.shared.room.notify_one();
+                Poll::Ready(next)
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// like `poll_chunk`, but blocks until the next chunk has been read
+    pub fn next_chunk(&mut self) -> Option<io::Result<Bytes>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(next) = state.take() {
+                self.shared.room.notify_one();
+                return next;
+            }
+            state = self.shared.ready.wait(state).unwrap();
+        }
+    }
+
+    pub fn chunk(&mut self) -> NextChunk<'_> {
+        NextChunk(self)
+    }
+}
+
+pub struct NextChunk<'a>(&'a mut AssetStream);
+
+impl Future for NextChunk<'_> {
+    type Output = Option<io::Result<Bytes>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_chunk(cx)
+    }
+}
+
+impl Read for AssetStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.next_chunk() {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.len());
+        buf[..len].copy_from_slice(&self.current[..len]);
+        self.current = self.current.slice(len..);
+        Ok(len)
+    }
+}
+
+impl Drop for AssetStream {
+    fn drop(&mut self) {
+        // the thread notices next time it waits for room, or once its current read returns
+        self.lock().cancelled = true;
+        self.shared.room.notify_all();
+    }
+}
+
+impl fmt::Debug for AssetStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetStream")
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+fn produce(shared: &Shared, mut reader: impl Read, chunk_size: usize, depth: usize) {
+    loop {
+        {
+            let mut state = shared.state.lock().unwrap();
+            while state.chunks.len() >= depth && !state.cancelled {
+                state = shared.room.wait(state).unwrap();
+            }
+            if state.cancelled {
+                return;
+            }
+        }
+
+        let mut chunk = vec![0; chunk_size];
+        let mut filled = 0;
+        let result = loop {
+            if filled == chunk_size {
+                break Ok(());
+            }
+            match reader.read(&mut chunk[filled..]) {
+                Ok(0) => break Ok(()),
+                Ok(n) => filled += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        chunk.truncate(filled);
+
+        let mut state = shared.state.lock().unwrap();
+        if filled > 0 {
+            state.chunks.push_back(Bytes::from(chunk));
+        }
+        // a short chunk means the reader ran dry
+        let done = filled < chunk_size || result.is_err();
+        state.error = result.err();
+        state.finished = done;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        shared.ready.notify_all();
+        if done {
+            return;
+        }
+    }
+}
+
+/// what `FileSystem::stream` leaves in the cache instead of the bytes: a way to stream them.
+/// every `open` is a new pass from the start, through whatever layer has the file by then
+#[derive(Clone)]
+pub struct StreamSource {
+    vfs: Arc<Vfs>,
+    name: String,
+    size: u64,
+}
+
+impl StreamSource {
+    pub(crate) fn new(vfs: Arc<Vfs>, name: String, size: u64) -> Self {
+        Self { vfs, name, size }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the size when the asset was loaded
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn open(&self) -> io::Result<AssetStream> {
+        self.open_with(CHUNK_SIZE, DEPTH)
+    }
+
+    pub fn open_with(&self, chunk_size: usize, depth: usize) -> io::Result<AssetStream> {
+        let (reader, size) = self.vfs.reader(&self.name)?;
+        AssetStream::new(reader, size, chunk_size, depth)
+    }
+}
+
+impl fmt::Debug for StreamSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSource")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// counts how far the reading thread got
+    struct Counting(io::Cursor<Vec<u8>>, Arc<Mutex<usize>>);
+
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.read(buf)?;
+            *self.1.lock().unwrap() += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn backpressure() {
+        let contents: Vec<u8> = (0..100u8).collect();
+        let read = Arc::new(Mutex::new(0));
+        let reader = Counting(io::Cursor::new(contents.clone()), read.clone());
+        let mut stream = AssetStream::new(reader, 100, 10, 2).unwrap();
+
+        assert_eq!(&*stream.next_chunk().unwrap().unwrap(), &contents[..10]);
+        // one chunk taken, two waiting, and the one being read when room freed up
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(*read.lock().unwrap() <= 40);
+
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &contents[10..]);
+        assert!(stream.next_chunk().is_none());
+    }
+}
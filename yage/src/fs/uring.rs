@@ -0,0 +1,474 @@
+//! a tiny io_uring driver: one thread owns the ring and keeps many file reads in flight at once.
+//! archives and path resolution still go through the blocking path, only the actual reads of
+//! loose files are queued on the ring
+
+use super::reader::Job;
+use super::vfs::Located;
+use std::collections::HashMap;
+use std::ffi::{c_int, c_long, c_uint, c_void};
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use yage_core::asset::Bytes;
+
+const SYS_IO_URING_SETUP: c_long = 425;
+const SYS_IO_URING_ENTER: c_long = 426;
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x800_0000;
+const IORING_OFF_SQES: i64 = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: c_uint = 1;
+const IORING_OP_READ: u8 = 22;
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const MAP_SHARED: c_int = 1;
+const MAP_POPULATE: c_int = 0x8000;
+const EINTR: i32 = 4;
+
+/// how many reads can be in flight at once
+const ENTRIES: u32 = 64;
+
+unsafe extern "C" {
+    fn syscall(number: c_long, ...) -> c_long;
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqOffsets,
+    cq_off: CqOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// an mmapped region of the ring, unmapped on drop
+struct Map {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Map {
+    fn new(fd: c_int, len: usize, offset: i64) -> io::Result<Self> {
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// # Safety
+    /// `offset` has to be inside the mapping and suitably aligned for `T`
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Map {
+    fn drop(&mut self) {
+        unsafe { munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+struct Ring {
+    // the maps have to go before the fd. the rings are only held on to so they get unmapped
+    _sq: Map,
+    _cq: Map,
+    sqes: Map,
+    fd: OwnedFd,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_array: *mut u32,
+    cq_mask: u32,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cqes: *const Cqe,
+    /// sqes pushed since the last `enter`
+    unsubmitted: u32,
+}
+
+// SAFETY: the pointers all point into mappings the ring owns, and only one thread uses it at a time
+unsafe impl Send for Ring {}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe { syscall(SYS_IO_URING_SETUP, entries as c_uint, &raw mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: io_uring_setup just returned this fd to us
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as c_int) };
+        let raw = fd.as_raw_fd();
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * std::mem::size_of::<Sqe>();
+        let sq = Map::new(raw, sq_len, IORING_OFF_SQ_RING)?;
+        let cq = Map::new(raw, cq_len, IORING_OFF_CQ_RING)?;
+        let sqes = Map::new(raw, sqes_len, IORING_OFF_SQES)?;
+
+        // SAFETY: the kernel hands out these offsets into the mappings above
+        unsafe {
+            Ok(Self {
+                sq_mask: *sq.at::<u32>(params.sq_off.ring_mask),
+                sq_entries: *sq.at::<u32>(params.sq_off.ring_entries),
+                sq_head: sq.at(params.sq_off.head),
+                sq_tail: sq.at(params.sq_off.tail),
+                sq_array: sq.at(params.sq_off.array),
+                cq_mask: *cq.at::<u32>(params.cq_off.ring_mask),
+                cq_head: cq.at(params.cq_off.head),
+                cq_tail: cq.at(params.cq_off.tail),
+                cqes: cq.at(params.cq_off.cqes),
+                unsubmitted: 0,
+                _sq: sq,
+                _cq: cq,
+                sqes,
+                fd,
+            })
+        }
+    }
+
+    /// queues a read of `len` bytes from `fd` at `offset` into `buf`, `false` if the ring is full
+    ///
+    /// # Safety
+    /// `buf` has to stay valid for `len` bytes until the completion for `user_data` comes back
+    unsafe fn push_read(
+        &mut self,
+        fd: c_int,
+        offset: u64,
+        buf: *mut u8,
+        len: u32,
+        user_data: u64,
+    ) -> bool {
+        unsafe {
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            let head = (*self.sq_head).load(Ordering::Acquire);
+            if tail.wrapping_sub(head) == self.sq_entries {
+                return false;
+            }
+            let index = tail & self.sq_mask;
+            self.sqes.at::<Sqe>(0).add(index as usize).write(Sqe {
+                opcode: IORING_OP_READ,
+                fd,
+                off: offset,
+                addr: buf as u64,
+                len,
+                user_data,
+                ..Sqe::default()
+            });
+            self.sq_array.add(index as usize).write(index);
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        self.unsubmitted += 1;
+        true
+    }
+
+    /// submits everything queued and waits until at least `wait_for` reads have finished
+    fn enter(&mut self, wait_for: u32) -> io::Result<()> {
+        loop {
+            let flags = if wait_for > 0 {
+                IORING_ENTER_GETEVENTS
+            } else {
+                0
+            };
+            let submitted = unsafe {
+                syscall(
+                    SYS_IO_URING_ENTER,
+                    self.fd.as_raw_fd(),
+                    self.unsubmitted as c_uint,
+                    wait_for as c_uint,
+                    flags,
+                    std::ptr::null::<c_void>(),
+                    0usize,
+                )
+            };
+            if submitted >= 0 {
+                self.unsubmitted -= submitted as u32;
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(EINTR) {
+                return Err(error);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Cqe> {
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+            let cqe = *self.cqes.add((head & self.cq_mask) as usize);
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+            Some(cqe)
+        }
+    }
+}
+
+struct InFlight {
+    file: File,
+    buf: Vec<u8>,
+    filled: usize,
+    job: Job,
+}
+
+impl InFlight {
+    fn push(&mut self, ring: &mut Ring, id: u64) -> bool {
+        let remaining = (self.buf.len() - self.filled).min(u32::MAX as usize) as u32;
+        // SAFETY: the buffer lives in `in_flight` until this read's completion is handled
+        unsafe {
+            ring.push_read(
+                self.file.as_raw_fd(),
+                self.filled as u64,
+                self.buf.as_mut_ptr().add(self.filled),
+                remaining,
+                id,
+            )
+        }
+    }
+
+    fn finish(self, result: io::Result<()>) {
+        let Self {
+            mut buf,
+            filled,
+            job,
+            ..
+        } = self;
+        buf.truncate(filled);
+        job.slot.complete(result.map(|()| Bytes::from(buf)));
+    }
+}
+
+fn drive(mut ring: Ring, jobs: Receiver<Job>) {
+    let mut in_flight: HashMap<u64, InFlight> = HashMap::new();
+    let mut next_id = 0u64;
+    let mut closed = false;
+
+    loop {
+        // take on new work while there is room, only blocking when there is nothing else to do
+        while !closed && (in_flight.len() as u32) < ring.sq_entries {
+            let job = if in_flight.is_empty() {
+                jobs.recv().map_err(|_| TryRecvError::Disconnected)
+            } else {
+                jobs.try_recv()
+            };
+            let job = match job {
+                Ok(job) => job,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    closed = true;
+                    break;
+                }
+            };
+
+            let path = match job.vfs.locate(&job.name) {
+                Ok(Located::File(path)) => path,
+                // archives, memory layers and errors are quick enough to do right here
+                Ok(_) | Err(_) => {
+                    job.run();
+                    continue;
+                }
+            };
+            let opened = File::open(&path).and_then(|file| {
+                let len = file.metadata()?.len() as usize;
+                Ok((file, len))
+            });
+            let (file, len) = match opened {
+                // mapping is quick, there is nothing for the ring to do
+                Ok((_, len)) if job.map_from.is_some_and(|map_from| len as u64 >= map_from) => {
+                    job.run();
+                    continue;
+                }
+                Ok(opened) if opened.1 > 0 => opened,
+                Ok(_) => {
+                    job.slot.complete(Ok(Bytes::new()));
+                    continue;
+                }
+                Err(error) => {
+                    job.slot.complete(Err(error));
+                    continue;
+                }
+            };
+            let mut read = InFlight {
+                file,
+                buf: vec![0; len],
+                filled: 0,
+                job,
+            };
+            let id = next_id;
+            next_id += 1;
+            let pushed = read.push(&mut ring, id);
+            debug_assert!(pushed, "in_flight never outgrows the ring");
+            in_flight.insert(id, read);
+        }
+
+        if in_flight.is_empty() {
+            if closed {
+                return;
+            }
+            continue;
+        }
+
+        if ring.enter(1).is_err() {
+            // the kernel may still write into buffers it was handed, so those are leaked rather
+            // than freed. everything left is read the blocking way, here, from now on
+            for (_, read) in in_flight.drain() {
+                std::mem::forget(read.buf);
+                read.job.run();
+            }
+            drop(ring);
+            for job in jobs {
+                job.run();
+            }
+            return;
+        }
+
+        while let Some(cqe) = ring.pop() {
+            let Some(mut read) = in_flight.remove(&cqe.user_data) else {
+                continue;
+            };
+            match cqe.res {
+                res if res < 0 => read.finish(Err(io::Error::from_raw_os_error(-res))),
+                // the file got shorter since we looked at its size
+                0 => read.finish(Ok(())),
+                res => {
+                    read.filled += res as usize;
+                    if read.filled == read.buf.len() {
+                        read.finish(Ok(()));
+                    } else {
+                        read.push(&mut ring, cqe.user_data);
+                        in_flight.insert(cqe.user_data, read);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(super) struct Driver {
+    jobs: Mutex<Option<Sender<Job>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Driver {
+    pub(super) fn new() -> io::Result<Self> {
+        let ring = Ring::new(ENTRIES)?;
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("yage-fs-uring".into())
+            .spawn(move || drive(ring, receiver))?;
+        Ok(Self {
+            jobs: Mutex::new(Some(sender)),
+            thread: Some(thread),
+        })
+    }
+
+    pub(super) fn submit(&self, job: Job) {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.as_ref()
+            .expect("the driver is only shut down on drop")
+            .send(job)
+            .expect("the io_uring thread exited early");
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        self.jobs.lock().unwrap().take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! a layered virtual file system. layers are mounted at a point in the tree and stack on top of
+//! each other: later mounts shadow earlier ones, and a whiteout in an upper layer hides a file
+//! in the layers below it without touching them. this is what lets a mod directory override the
+//! base game
+
+use super::mmap::Mmap;
+use super::pak::Archive;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use yage_core::asset::Bytes;
+use yage_core::asset::compress::{self, Codec};
+
+/// prefix of the marker files a `UserDir` uses to record whiteouts
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// turns `path` into `a/b/c` form. `.` and empty parts are dropped and `..` is resolved, but
+/// never past the root
+pub fn normalize(path: &str) -> io::Result<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("`{path}` escapes the root"),
+                    ));
+                }
+            }
+            _ if part.starts_with(WHITEOUT_PREFIX) || part.contains('\\') => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{path}` is not a valid asset path"),
+                ));
+            }
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+/// an in-memory layer, mostly for tests. `None` marks a whiteout
+#[derive(Debug, Default)]
+pub struct MemoryLayer {
+    files: Mutex<BTreeMap<String, Option<Arc<[u8]>>>>,
+}
+
+impl MemoryLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(self, path: &str, bytes: impl Into<Arc<[u8]>>) -> Self {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), Some(bytes.into()));
+        self
+    }
+
+    pub fn with_whiteout(self, path: &str) -> Self {
+        self.files.lock().unwrap().insert(path.to_owned(), None);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum Layer {
+    /// a read-only directory, like the base game's assets
+    Dir(PathBuf),
+    /// a directory that takes writes. whiteouts are `.wh.<name>` files next to where the hidden
+    /// file would be
+    UserDir(PathBuf),
+    /// a read-only `.pak` archive, and where it was opened from
+    Pak(PathBuf, Archive),
+    Memory(MemoryLayer),
+}
+
+/// what a layer has at some path
+#[derive(Clone)]
+pub(crate) enum Located<'a> {
+    File(PathBuf),
+    /// the archive, and the path inside it
+    Pak(&'a Archive, String),
+    Memory(Arc<[u8]>),
+    /// a compressed copy of the file that was asked for, and the name it has with the extension
+    Compressed(Box<Located<'a>>, Codec, String),
+}
+
+impl Located<'_> {
+    /// compressed files are decompressed to find out, they don't record their size
+    fn size(&self, limit: usize) -> io::Result<u64> {
+        match self {
+            Self::File(file) => Ok(file.metadata()?.len()),
+            Self::Pak(archive, inner) => Ok(archive.entry(inner).expect("located here").size),
+            Self::Memory(bytes) => Ok(bytes.len() as u64),
+            Self::Compressed(..) => Ok(self.clone().read(limit)?.len() as u64),
+        }
+    }
+
+    fn read(self, limit: usize) -> io::Result<Arc<[u8]>> {
+        match self {
+            Self::File(file) => std::fs::read(file).map(Arc::from),
+            Self::Pak(archive, inner) => archive.read(&inner).expect("located in this archive"),
+            Self::Memory(bytes) => Ok(bytes),
+            Self::Compressed(inner, codec, name) => {
+                let bytes = inner.read(limit)?;
+                compress::decompress(codec, &bytes, limit)
+                    .map(Arc::from)
+                    .map_err(|error| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("`{name}`: {error}"))
+                    })
+            }
+        }
+    }
+
+    /// compressed files can't be mapped, they are read instead
+    ///
+    /// # Safety
+    /// the file, or the archive it is in, must not be written to or truncated while the bytes
+    /// are alive, see `Mmap::map`
+    unsafe fn map(self, limit: usize) -> io::Result<Bytes> {
+        match self {
+            Self::File(file) => Ok(unsafe { Mmap::map_file(&File::open(file)?)? }.into_bytes()),
+            Self::Pak(archive, inner) => {
+                unsafe { archive.map(&inner) }.expect("located in this archive")
+            }
+            Self::Memory(bytes) => Ok(Bytes::from(bytes)),
+            compressed @ Self::Compressed(..) => compressed.read(limit).map(Bytes::from),
+        }
+    }
+}
+
+enum Lookup<'a> {
+    Found(Located<'a>),
+    Whiteout,
+    Missing,
+}
+
+impl Layer {
+    /// a read-only directory, or an archive if `path` is a `.pak` file
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if path.extension().is_some_and(|ext| ext == "pak") && path.is_file() {
+            let archive = Archive::open(&path)?;
+            return Ok(Self::Pak(path, archive));
+        }
+        Ok(Self::Dir(path))
+    }
+
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Self::UserDir(_) | Self::Memory(_))
+    }
+
+    /// the directory on disk behind this layer, if it is one
+    pub fn dir(&self) -> Option<&Path> {
+        match self {
+            Self::Dir(dir) | Self::UserDir(dir) => Some(dir),
+            Self::Pak(..) | Self::Memory(_) => None,
+        }
+    }
+
+    fn describe(&self, path: &str) -> String {
+        match self {
+            Self::Dir(dir) | Self::UserDir(dir) => dir.join(path).display().to_string(),
+            Self::Pak(archive, _) => format!("{}:{path}", archive.display()),
+            Self::Memory(_) => format!("memory:{path}"),
+        }
+    }
+
+    fn whiteout_marker(dir: &Path, path: &str) -> PathBuf {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        dir.join(parent).join(format!("{WHITEOUT_PREFIX}{name}"))
+    }
+
+    /// `path` itself, or failing that a compressed copy of it, like `path.lz4`
+    fn lookup(&self, path: &str) -> Lookup<'_> {
+        match self.lookup_exact(path) {
+            Lookup::Missing => {}
+            found => return found,
+        }
+        for codec in Codec::ALL {
+            let name = format!("{path}.{}", codec.extension());
+            if let Lookup::Found(located) = self.lookup_exact(&name) {
+                return Lookup::Found(Located::Compressed(Box::new(located), codec, name));
+            }
+        }
+        Lookup::Missing
+    }
+
+    fn lookup_exact(&self, path: &str) -> Lookup<'_> {
+        match self {
+            Self::Dir(dir) | Self::UserDir(dir) => {
+                let file = dir.join(path);
+                if file.is_file() {
+                    Lookup::Found(Located::File(file))
+                } else if matches!(self, Self::UserDir(_))
+                    && Self::whiteout_marker(dir, path).exists()
+                {
+                    Lookup::Whiteout
+                } else {
+                    Lookup::Missing
+                }
+            }
+            Self::Pak(_, archive) if archive.contains(path) => {
+                Lookup::Found(Located::Pak(archive, path.to_owned()))
+            }
+            Self::Pak(..) => Lookup::Missing,
+            Self::Memory(memory) => match memory.files.lock().unwrap().get(path) {
+                Some(Some(bytes)) => Lookup::Found(Located::Memory(bytes.clone())),
+                Some(None) => Lookup::Whiteout,
+                None => Lookup::Missing,
+            },
+        }
+    }
+
+    fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Self::UserDir(dir) => {
+                let file = dir.join(path);
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(file, bytes)?;
+                match std::fs::remove_file(Self::whiteout_marker(dir, path)) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+                    _ => Ok(()),
+                }
+            }
+            Self::Memory(memory) => {
+                let mut files = memory.files.lock().unwrap();
+                files.insert(path.to_owned(), Some(Arc::from(bytes)));
+                Ok(())
+            }
+            Self::Dir(_) | Self::Pak(..) => Err(read_only()),
+        }
+    }
+
+    /// deletes `path` from this layer, leaving a whiteout behind if `hide` is set
+    fn remove(&self, path: &str, hide: bool) -> io::Result<()> {
+        match self {
+            Self::UserDir(dir) => {
+                match std::fs::remove_file(dir.join(path)) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                }
+                if hide {
+                    let marker = Self::whiteout_marker(dir, path);
+                    if let Some(parent) = marker.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(marker, [])?;
+                }
+                Ok(())
+            }
+            Self::Memory(memory) => {
+                let mut files = memory.files.lock().unwrap();
+                match hide {
+                    true => files.insert(path.to_owned(), None),
+                    false => files.remove(path),
+                };
+                Ok(())
+            }
+            Self::Dir(_) | Self::Pak(..) => Err(read_only()),
+        }
+    }
+}
+
+fn read_only() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "no writable layer is mounted there",
+    )
+}
+
+#[derive(Debug)]
+pub struct MountPoint {
+    /// normalized, empty for the root
+    at: String,
+    layer: Layer,
+}
+
+impl MountPoint {
+    pub fn at(&self) -> &str {
+        &self.at
+    }
+
+    pub fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    /// `path` relative to this mount point, if it is under it
+    fn relative<'p>(&self, path: &'p str) -> Option<&'p str> {
+        if self.at.is_empty() {
+            return Some(path);
+        }
+        path.strip_prefix(self.at.as_str())?.strip_prefix('/')
+    }
+}
+
+#[derive(Debug)]
+pub struct Vfs {
+    /// bottom layer first
+    mounts: Vec<MountPoint>,
+    /// the most a compressed file may decompress to
+    decompress_limit: usize,
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Self {
+            mounts: Vec::new(),
+            decompress_limit: compress::DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decompress_limit(&self) -> usize {
+        self.decompress_limit
+    }
+
+    /// the most any one compressed file may decompress to, loose or in an archive. files that
+    /// would grow past it fail to load rather than eat all the memory there is
+    pub fn set_decompress_limit(&mut self, limit: usize) {
+        self.decompress_limit = limit;
+        for mount in &mut self.mounts {
+            if let Layer::Pak(_, archive) = &mut mount.layer {
+                archive.set_decompress_limit(limit);
+            }
+        }
+    }
+
+    /// a read-only layer per search path, the first path taking precedence like it always has
+    pub fn from_search_paths(paths: &[&'static str]) -> io::Result<Self> {
+        let mut vfs = Self::new();
+        for path in paths.iter().rev() {
+            vfs.mount("", Layer::open(path)?)?;
+        }
+        Ok(vfs)
+    }
+
+    /// puts `layer` on top of everything mounted so far, at `at` in the tree
+    pub fn mount(&mut self, at: &str, mut layer: Layer) -> io::Result<&mut Self> {
+        let at = normalize(at)?;
+        if let Layer::Pak(_, archive) = &mut layer {
+            archive.set_decompress_limit(self.decompress_limit);
+        }
+        self.mounts.push(MountPoint { at, layer });
+        Ok(self)
+    }
+
+    /// takes the topmost layer mounted at `at` off again
+    pub fn unmount(&mut self, at: &str) -> io::Result<Layer> {
+        let at = normalize(at)?;
+        let position = self
+            .mounts
+            .iter()
+            .rposition(|mount| mount.at == at)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("nothing is mounted at `{at}`"),
+                )
+            })?;
+        Ok(self.mounts.remove(position).layer)
+    }
+
+    /// every mount point, bottom layer first
+    pub fn mounts(&self) -> &[MountPoint] {
+        &self.mounts
+    }
+
+    /// finds `path` in the topmost layer that has it or hides it. the not found error lists
+    /// every place it looked
+    pub(crate) fn locate(&self, path: &str) -> io::Result<Located<'_>> {
+        let path = normalize(path)?;
+        let mut tried = Vec::new();
+        for mount in self.mounts.iter().rev() {
+            let Some(relative) = mount.relative(&path) else {
+                continue;
+            };
+            match mount.layer.lookup(relative) {
+                Lookup::Found(located) => return Ok(located),
+                Lookup::Whiteout => {
+                    tried.push(format!("{} (whiteout)", mount.layer.describe(relative)));
+                    break;
+                }
+                Lookup::Missing => tried.push(mount.layer.describe(relative)),
+            }
+        }
+        let message = match tried.is_empty() {
+            true => format!("`{path}` not found, nothing is mounted there"),
+            false => format!("`{path}` not found, tried {}", tried.join(", ")),
+        };
+        Err(io::Error::new(io::ErrorKind::NotFound, message))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.locate(path).is_ok()
+    }
+
+    /// reads `path` from the topmost layer that has it. this blocks
+    pub fn read(&self, path: &str) -> io::Result<Arc<[u8]>> {
+        self.locate(path)?.read(self.decompress_limit)
+    }
+
+    /// like `read`, but loose files and files stored uncompressed in archives are memory mapped
+    /// rather than copied
+    ///
+    /// # Safety
+    /// the file `path` comes from, or the archive it is in, must not be written to or truncated
+    /// while the bytes are alive, see `Mmap::map`
+    pub unsafe fn map(&self, path: &str) -> io::Result<Bytes> {
+        unsafe { self.locate(path)?.map(self.decompress_limit) }
+    }
+
+    pub fn size(&self, path: &str) -> io::Result<u64> {
+        self.locate(path)?.size(self.decompress_limit)
+    }
+
+    /// maps `path` if it is at least `map_from` bytes, reads it otherwise. compressed files are
+    /// always read
+    ///
+    /// # Safety
+    /// same as `map` whenever `path` is at least `map_from` bytes. with `None` nothing is mapped
+    /// and any call is fine
+    pub unsafe fn read_or_map(&self, path: &str, map_from: Option<u64>) -> io::Result<Bytes> {
+        let located = self.locate(path)?;
+        let limit = self.decompress_limit;
+        match map_from {
+            Some(map_from)
+                if !matches!(located, Located::Compressed(..))
+                    && located.size(limit)? >= map_from =>
+            unsafe { located.map(limit) },
+            _ => located.read(limit).map(Bytes::from),
+        }
+    }
+
+    /// opens `path` for reading a bit at a time, along with its size. this blocks until the
+    /// file is open, and compressed files are decompressed up front
+    pub fn reader(&self, path: &str) -> io::Result<(Box<dyn Read + Send>, u64)> {
+        let located = self.locate(path)?;
+        let limit = self.decompress_limit;
+        if let Located::Compressed(..) = located {
+            let bytes = located.read(limit)?;
+            let size = bytes.len() as u64;
+            return Ok((Box::new(io::Cursor::new(bytes)), size));
+        }
+        let size = located.size(limit)?;
+        let reader: Box<dyn Read + Send> = match located {
+            Located::File(file) => Box::new(File::open(file)?),
+            Located::Pak(archive, inner) => archive.reader(&inner).expect("located here")?,
+            Located::Memory(bytes) => Box::new(io::Cursor::new(bytes)),
+            Located::Compressed(..) => unreachable!("read in full above"),
+        };
+        Ok((reader, size))
+    }
+
+    /// the topmost writable layer `path` falls under, and `path` relative to it
+    fn writable<'p>(&self, path: &'p str) -> io::Result<(usize, &'p str)> {
+        self.mounts
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, mount)| mount.layer.is_writable())
+            .find_map(|(i, mount)| Some((i, mount.relative(path)?)))
+            .ok_or_else(read_only)
+    }
+
+    /// writes `path` into the topmost writable layer, shadowing whatever is below it
+    pub fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = normalize(path)?;
+        let (layer, relative) = self.writable(&path)?;
+        self.mounts[layer].layer.write(relative, bytes)
+    }
+
+    /// removes `path` from the topmost writable layer. if a lower layer still has it, a
+    /// whiteout is left so it stays hidden
+    pub fn remove(&self, path: &str) -> io::Result<()> {
+        let path = normalize(path)?;
+        let (layer, relative) = self.writable(&path)?;
+        let hide = self.mounts[..layer].iter().any(|mount| {
+            mount
+                .relative(&path)
+                .is_some_and(|inner| matches!(mount.layer.lookup(inner), Lookup::Found(_)))
+        });
+        self.mounts[layer].layer.remove(relative, hide)
+    }
+}
+
+impl fmt::Display for MountPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{} <- {}", self.at, self.layer.describe(""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_and_whiteouts() {
+        let mut vfs = Vfs::new();
+        vfs.mount(
+            "",
+            Layer::Memory(
+                MemoryLayer::new()
+                    .with_file("a.json", *b"base")
+                    .with_file("b.json", *b"base"),
+            ),
+        )
+        .unwrap()
+        .mount(
+            "",
+            Layer::Memory(
+                MemoryLayer::new()
+                    .with_file("a.json", *b"mod")
+                    .with_whiteout("b.json"),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(&*vfs.read("./a.json").unwrap(), b"mod");
+        assert!(!vfs.exists("b.json"));
+
+        vfs.write("c/d.json", b"new").unwrap();
+        assert_eq!(&*vfs.read("c/../c/d.json").unwrap(), b"new");
+        vfs.remove("a.json").unwrap();
+        assert!(!vfs.exists("a.json"), "the base copy stays hidden");
+
+        vfs.unmount("").unwrap();
+        assert_eq!(&*vfs.read("a.json").unwrap(), b"base");
+        assert_eq!(
+            vfs.read("../a.json").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn compressed_files() {
+        let text = b"level data, level data, level data, level data".repeat(10);
+        let mut vfs = Vfs::new();
+        vfs.mount(
+            "",
+            Layer::Memory(
+                MemoryLayer::new()
+                    .with_file("level.json.lz4", compress::compress(Codec::Lz4, &text))
+                    .with_file(
+                        "intro.txt.deflate",
+                        compress::compress(Codec::Deflate, &text),
+                    ),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(&*vfs.read("level.json").unwrap(), &text[..]);
+        assert_eq!(vfs.size("intro.txt").unwrap(), text.len() as u64);
+        let (mut reader, size) = vfs.reader("intro.txt").unwrap();
+        let mut streamed = Vec::new();
+        reader.read_to_end(&mut streamed).unwrap();
+        assert_eq!((streamed, size), (text.clone(), text.len() as u64));
+        // asked for by its own name, a compressed file is left alone
+        assert!(vfs.read("level.json.lz4").unwrap().len() < text.len());
+
+        vfs.set_decompress_limit(100);
+        let error = vfs.read("level.json").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn mount_points() {
+        let mut vfs = Vfs::new();
+        vfs.mount(
+            "mods/cool",
+            Layer::Memory(MemoryLayer::new().with_file("x", *b"x")),
+        )
+        .unwrap();
+        assert!(vfs.exists("mods/cool/x"));
+        assert!(!vfs.exists("x"));
+        assert!(!vfs.exists("mods/coolx"));
+    }
+}
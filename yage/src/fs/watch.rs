@@ -0,0 +1,128 @@
+//! inotify watches over the directory search paths, for hot reloading during development
+
+use std::collections::HashMap;
+use std::ffi::{CString, c_char, c_int};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const IN_NONBLOCK: c_int = 0o4000;
+const IN_CLOEXEC: c_int = 0o2000000;
+
+const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+const IN_MOVED_TO: u32 = 0x0000_0080;
+const IN_CREATE: u32 = 0x0000_0100;
+const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+const IN_ISDIR: u32 = 0x4000_0000;
+
+/// `struct inotify_event` without the trailing name
+const EVENT_LEN: usize = 16;
+
+unsafe extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+}
+
+/// one watched directory, and where it sits relative to its search path
+struct Watched {
+    dir: PathBuf,
+    prefix: String,
+}
+
+pub struct Watcher {
+    inotify: File,
+    watches: HashMap<c_int, Watched>,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: inotify_init1 just gave us this fd and nothing else owns it
+        let inotify = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        Ok(Self {
+            inotify,
+            watches: HashMap::new(),
+        })
+    }
+
+    /// watches `root` and every directory under it. files are reported relative to `root`,
+    /// under `mount_point` if it isn't empty
+    pub fn add_root(&mut self, root: impl AsRef<Path>, mount_point: &str) -> io::Result<()> {
+        let prefix = match mount_point {
+            "" => String::new(),
+            at => format!("{at}/"),
+        };
+        self.add_dir(root.as_ref().to_path_buf(), prefix)
+    }
+
+    fn add_dir(&mut self, dir: PathBuf, prefix: String) -> io::Result<()> {
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let mask = IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE;
+        let wd = unsafe { inotify_add_watch(self.inotify.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                    continue;
+                };
+                self.add_dir(entry.path(), format!("{prefix}{name}/"))?;
+            }
+        }
+        self.watches.insert(wd, Watched { dir, prefix });
+        Ok(())
+    }
+
+    /// every file written since the last call, as `/` separated names relative to its search
+    /// path. never blocks
+    pub fn changed(&mut self) -> io::Result<Vec<String>> {
+        let mut changed = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let len = match self.inotify.read(&mut buf) {
+                Ok(len) => len,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+
+            let mut events = &buf[..len];
+            while events.len() >= EVENT_LEN {
+                let wd = c_int::from_ne_bytes(events[0..4].try_into().unwrap());
+                let mask = u32::from_ne_bytes(events[4..8].try_into().unwrap());
+                let name_len = u32::from_ne_bytes(events[12..16].try_into().unwrap()) as usize;
+                let name = &events[EVENT_LEN..EVENT_LEN + name_len];
+                events = &events[EVENT_LEN + name_len..];
+
+                if mask & IN_Q_OVERFLOW != 0 {
+                    return Err(io::Error::other("inotify queue overflowed"));
+                }
+                // the name is padded with nuls out to an alignment boundary
+                let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+                let (Some(watched), Ok(name)) = (self.watches.get(&wd), std::str::from_utf8(name))
+                else {
+                    continue;
+                };
+                let full = format!("{}{name}", watched.prefix);
+
+                if mask & IN_ISDIR != 0 {
+                    // new directories need their own watch, and may already have files in them
+                    let dir = watched.dir.join(name);
+                    self.add_dir(dir, format!("{full}/"))?;
+                } else if mask & (IN_CLOSE_WRITE | IN_MOVED_TO) != 0 && !changed.contains(&full) {
+                    changed.push(full);
+                }
+            }
+        }
+        Ok(changed)
+    }
+}
@@ -1,16 +1,22 @@
 
 
 pub mod fs;
+pub mod framebuffer;
 pub mod cache;
+pub mod events;
 pub mod net;
+pub mod preload;
+pub mod screenshot;
+#[cfg(test)]
+mod testing;
 
 pub type New = yage_core::prelude::New<
   fs::FileSystem, 
   net::Network, 
   std::io::Error, 
   cache::AssetCache,
-  std::future::Ready<fs::FileSystem>,
-  std::future::Ready<net::Network>
+  std::future::Ready<std::io::Result<fs::FileSystem>>,
+  std::future::Ready<std::io::Result<net::Network>>
 >;
 
 pub type Loading<L> = yage_core::prelude::Loading<
@@ -23,6 +29,6 @@ pub type Loading<L> = yage_core::prelude::Loading<
 pub use yage_core::App;
 
 fn test() {
-  let mut app = App::<New>::new();
+  let mut app = App::<New, ()>::new();
 }
 
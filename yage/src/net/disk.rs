@@ -0,0 +1,418 @@
+//! keeps what `Network` fetched on disk between runs. files are stored once per content hash
+//! under `objects/`, and `index` records which hash each server handed out for each name,
+//! along with when it was last used so the least recently used files go first when the cache
+//! is over its limit
+//!
+//! files are written under a temporary name and renamed into place, and checked against their
+//! hash whenever they are read, so a file cut short by a crash is thrown away rather than used
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use yage_core::asset::{Bytes, ContentHash};
+
+const INDEX_NAME: &str = "index";
+const INDEX_HEADER: &str = "yage-disk-cache 1";
+const TEMP_SUFFIX: &str = ".tmp";
+
+pub struct DiskCache {
+    root: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+    /// keeps the temporary names of concurrent writes apart
+    next_temp: AtomicU64,
+}
+
+#[derive(Default)]
+struct Index {
+    /// the hash each server last handed out for each name
+    entries: HashMap<(String, String), ContentHash>,
+    files: HashMap<ContentHash, File>,
+    /// bumped on every use, stands in for a clock
+    tick: u64,
+    /// whether `entries` or `files` changed since the index was last written
+    dirty: bool,
+}
+
+struct File {
+    size: u64,
+    last_used: u64,
+}
+
+impl DiskCache {
+    /// opens or creates a cache in `root` that holds at most `max_bytes` of files
+    pub fn open(root: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let root = root.into();
+        let objects = root.join("objects");
+        std::fs::create_dir_all(&objects)?;
+        let index = match std::fs::read_to_string(root.join(INDEX_NAME)) {
+            Ok(text) => Index::parse(&text),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Index::default(),
+            Err(error) => return Err(error),
+        };
+        // whatever was still being written when we last stopped, and files the index lost
+        // track of
+        for entry in std::fs::read_dir(&objects)? {
+            let path = entry?.path();
+            let hash = path
+                .file_name()
+                .and_then(|name| name.to_str()?.parse().ok());
+            if !hash.is_some_and(|hash| index.files.contains_key(&hash)) {
+                remove_if_present(&path)?;
+            }
+        }
+
+        let cache = Self {
+            root,
+            max_bytes,
+            index: Mutex::new(index),
+            next_temp: AtomicU64::new(0),
+        };
+        cache.prune()?;
+        Ok(cache)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// the bytes on disk, counting each distinct file once
+    pub fn size(&self) -> u64 {
+        let index = self.index.lock().unwrap();
+        index.files.values().map(|file| file.size).sum()
+    }
+
+    /// what `server` last handed out as `name`, with its hash. a file that doesn't match its hash
+    /// is deleted and reported as missing
+    pub fn get(&self, server: &str, name: &str) -> Option<(ContentHash, Bytes)> {
+        let mut index = self.index.lock().unwrap();
+        let key = (server.to_owned(), name.to_owned());
+        let hash = *index.entries.get(&key)?;
+        let path = self.object_path(hash);
+        match std::fs::read(&path) {
+            Ok(bytes) if ContentHash::of(&bytes) == hash => {
+                index.touch(hash);
+                Some((hash, Bytes::from(bytes)))
+            }
+            _ => {
+                let _ = std::fs::remove_file(path);
+                index.forget(hash);
+                None
+            }
+        }
+    }
+
+    /// records `bytes` as what `server` hands out as `name`, then prunes back under the limit.
+    /// fails with `FileTooLarge` if `bytes` alone is over the limit, and forgets whatever older
+    /// version of `name` the cache had
+    pub fn put(&self, server: &str, name: &str, bytes: &[u8]) -> io::Result<ContentHash> {
+        if server.contains(['\n', ' ']) || name.contains('\n') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "server names can't have spaces or newlines, asset names can't have newlines",
+            ));
+        }
+        if bytes.len() as u64 > self.max_bytes {
+            self.remove(server, name)?;
+            let message = format!(
+                "{} bytes don't fit in a cache of {} bytes",
+                bytes.len(),
+                self.max_bytes
+            );
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, message));
+        }
+        let hash = ContentHash::of(bytes);
+        let path = self.object_path(hash);
+        let mut temp = None;
+        let mut index = loop {
+            let index = self.index.lock().unwrap();
+            if temp.is_some() || (index.files.contains_key(&hash) && path.exists()) {
+                break index;
+            }
+            drop(index);
+            // written without the lock, so other gets and puts don't wait on the disk
+            temp = Some(self.write_temp(hash, bytes)?);
+        };
+        if let Some(temp) = temp {
+            // a put of the same bytes may have got there first, which is just as good
+            if let Err(error) = std::fs::rename(&temp, &path) {
+                let _ = std::fs::remove_file(temp);
+                return Err(error);
+            }
+            index.files.entry(hash).or_insert(File {
+                size: bytes.len() as u64,
+                last_used: 0,
+            });
+        }
+        let key = (server.to_owned(), name.to_owned());
+        if let Some(previous) = index.entries.insert(key, hash)
+            && previous != hash
+        {
+            // the old version may have been this name's alone
+            index.drop_unreferenced(&self.root)?;
+        }
+        index.touch(hash);
+        self.prune_locked(&mut index, Some(hash))?;
+        self.write_index(&mut index)?;
+        Ok(hash)
+    }
+
+    /// `bytes` in a fresh temporary file next to where `hash` goes, synced to disk
+    fn write_temp(&self, hash: ContentHash, bytes: &[u8]) -> io::Result<PathBuf> {
+        let n = self.next_temp.fetch_add(1, Ordering::Relaxed);
+        let temp = self
+            .object_path(hash)
+            .with_file_name(format!("{hash}.{n}{TEMP_SUFFIX}"));
+        let written = std::fs::File::create(&temp).and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        });
+        match written {
+            Ok(()) => Ok(temp),
+            Err(error) => {
+                let _ = std::fs::remove_file(temp);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn remove(&self, server: &str, name: &str) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        let key = (server.to_owned(), name.to_owned());
+        if index.entries.remove(&key).is_some() {
+            index.dirty = true;
+            index.drop_unreferenced(&self.root)?;
+            self.write_index(&mut index)?;
+        }
+        Ok(())
+    }
+
+    /// deletes the least recently used files until the cache fits in `max_bytes`
+    pub fn prune(&self) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        self.prune_locked(&mut index, None)?;
+        self.write_index(&mut index)
+    }
+
+    /// writes out when each file was last used. `put` writes the index anyway, this catches
+    /// the `get`s since
+    pub fn flush(&self) -> io::Result<()> {
+        self.write_index(&mut self.index.lock().unwrap())
+    }
+
+    /// like `prune`, but never deletes `keep`
+    fn prune_locked(&self, index: &mut Index, keep: Option<ContentHash>) -> io::Result<()> {
+        let mut size: u64 = index.files.values().map(|file| file.size).sum();
+        if size <= self.max_bytes {
+            return Ok(());
+        }
+        let mut by_age: Vec<_> = index
+            .files
+            .iter()
+            .filter(|(hash, _)| Some(**hash) != keep)
+            .map(|(hash, file)| (file.last_used, *hash))
+            .collect();
+        by_age.sort_unstable();
+        for (_, hash) in by_age {
+            if size <= self.max_bytes {
+                break;
+            }
+            size -= index.files[&hash].size;
+            remove_if_present(&self.object_path(hash))?;
+            index.forget(hash);
+        }
+        Ok(())
+    }
+
+    fn write_index(&self, index: &mut Index) -> io::Result<()> {
+        if !index.dirty {
+            return Ok(());
+        }
+        let path = self.root.join(INDEX_NAME);
+        let temp = self.root.join(format!("{INDEX_NAME}{TEMP_SUFFIX}"));
+        std::fs::write(&temp, index.to_text())?;
+        std::fs::rename(temp, path)?;
+        index.dirty = false;
+        Ok(())
+    }
+
+    fn object_path(&self, hash: ContentHash) -> PathBuf {
+        self.root.join("objects").join(hash.to_string())
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl Index {
+    /// reads an index back. lines that don't parse are skipped, the files behind them get
+    /// fetched again
+    fn parse(text: &str) -> Self {
+        let mut index = Self::default();
+        let mut lines = text.lines();
+        if lines.next() != Some(INDEX_HEADER) {
+            return index;
+        }
+        for line in lines {
+            // <hash> <size> <last used> <server> <name>, the name last since it may have spaces
+            let mut fields = line.splitn(5, ' ');
+            let (Some(hash), Some(size), Some(last_used), Some(server), Some(name)) = (
+                fields.next().and_then(|hash| hash.parse().ok()),
+                fields.next().and_then(|size| size.parse().ok()),
+                fields.next().and_then(|tick| tick.parse::<u64>().ok()),
+                fields.next(),
+                fields.next(),
+            ) else {
+                continue;
+            };
+            index.tick = index.tick.max(last_used);
+            index.files.insert(hash, File { size, last_used });
+            index
+                .entries
+                .insert((server.to_owned(), name.to_owned()), hash);
+        }
+        index
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{INDEX_HEADER}\n");
+        for ((server, name), hash) in &self.entries {
+            let file = &self.files[hash];
+            text += &format!("{hash} {} {} {server} {name}\n", file.size, file.last_used);
+        }
+        text
+    }
+
+    fn touch(&mut self, hash: ContentHash) {
+        self.tick += 1;
+        if let Some(file) = self.files.get_mut(&hash) {
+            file.last_used = self.tick;
+        }
+        self.dirty = true;
+    }
+
+    /// drops `hash` and every name that pointed at it
+    fn forget(&mut self, hash: ContentHash) {
+        self.files.remove(&hash);
+        self.entries.retain(|_, entry| *entry != hash);
+        self.dirty = true;
+    }
+
+    /// deletes the files no name points at any more
+    fn drop_unreferenced(&mut self, root: &Path) -> io::Result<()> {
+        let unreferenced: Vec<_> = self
+            .files
+            .keys()
+            .filter(|hash| !self.entries.values().any(|entry| entry == *hash))
+            .copied()
+            .collect();
+        for hash in unreferenced {
+            remove_if_present(&root.join("objects").join(hash.to_string()))?;
+            self.files.remove(&hash);
+        }
+        Ok(())
+    }
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn prune_and_discard() {
+        let root = TempDir::new("disk");
+        let cache = DiskCache::open(root.path(), 10).unwrap();
+
+        let a = cache.put("server", "a.txt", b"aaaa").unwrap();
+        cache.put("server", "copy of a.txt", b"aaaa").unwrap();
+        cache.put("server", "b.txt", b"bbbb").unwrap();
+        assert_eq!(cache.size(), 8);
+        assert_eq!(&*cache.get("server", "a.txt").unwrap().1, b"aaaa");
+        assert!(cache.get("other server", "a.txt").is_none());
+
+        // b was used less recently than a, so it makes room for c
+        cache.put("server", "c.txt", b"cccc").unwrap();
+        assert!(cache.get("server", "b.txt").is_none());
+        assert!(cache.get("server", "copy of a.txt").is_some());
+        drop(cache);
+
+        // a file cut short is thrown away once it is read
+        std::fs::write(root.join("objects").join(a.to_string()), b"aa").unwrap();
+        std::fs::write(root.join("objects/leftover.tmp"), b"half").unwrap();
+        let cache = DiskCache::open(root.path(), 10).unwrap();
+        assert!(!root.join("objects/leftover.tmp").exists());
+        assert_eq!(cache.size(), 8);
+        assert!(cache.get("server", "a.txt").is_none());
+        assert!(cache.get("server", "copy of a.txt").is_none());
+        assert_eq!(&*cache.get("server", "c.txt").unwrap().1, b"cccc");
+        assert_eq!(cache.size(), 4);
+    }
+
+    #[test]
+    fn replaced_files_are_deleted() {
+        let root = TempDir::new("disk-replace");
+        let objects = || std::fs::read_dir(root.join("objects")).unwrap().count();
+        let cache = DiskCache::open(root.path(), 1000).unwrap();
+        for version in ["one", "two", "three", "four"] {
+            cache
+                .put("server", "changing.txt", version.as_bytes())
+                .unwrap();
+            assert_eq!(objects(), 1);
+            assert_eq!(cache.size(), version.len() as u64);
+        }
+        cache.put("server", "other.txt", b"four").unwrap();
+        cache.put("server", "changing.txt", b"five").unwrap();
+        // "four" is still other.txt's
+        assert_eq!(objects(), 2);
+        assert_eq!(cache.size(), 8);
+        drop(cache);
+
+        // a file the index doesn't know about is swept when the cache opens
+        std::fs::write(
+            root.join("objects")
+                .join(ContentHash::of(b"lost").to_string()),
+            b"lost",
+        )
+        .unwrap();
+        std::fs::write(root.join("objects/stray"), b"stray").unwrap();
+        let cache = DiskCache::open(root.path(), 1000).unwrap();
+        assert_eq!(objects(), 2);
+        assert_eq!(cache.size(), 8);
+        assert_eq!(&*cache.get("server", "changing.txt").unwrap().1, b"five");
+    }
+
+    #[test]
+    fn oversized_files_are_refused() {
+        let root = TempDir::new("disk-oversized");
+        let cache = DiskCache::open(root.path(), 4).unwrap();
+        cache.put("server", "a.txt", b"aaaa").unwrap();
+        let error = cache.put("server", "a.txt", b"aaaaa").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+        // the version it would have replaced is out of date, so that goes too
+        assert!(cache.get("server", "a.txt").is_none());
+        assert_eq!(cache.size(), 0);
+
+        // what was just put stays, even if everything else has to go to make room
+        cache.put("server", "b.txt", b"bbbb").unwrap();
+        cache.put("server", "c.txt", b"cccc").unwrap();
+        assert!(cache.get("server", "b.txt").is_none());
+        assert_eq!(&*cache.get("server", "c.txt").unwrap().1, b"cccc");
+    }
+}
@@ -0,0 +1,603 @@
+use crate::cache::AssetCache;
+use crate::events::AssetEvent;
+use crate::fs::graph;
+use crate::fs::reader::{self, ThreadPool};
+use disk::DiskCache;
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpStream};
+use std::num::NonZero;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use yage_core::asset::strings::Locale;
+use yage_core::asset::{
+    Asset, AssetKind, BorrowedHandle, Bytes, Cache, CowHandle, DecoderRegistry, Fetch, Loader,
+};
+
+pub mod disk;
+pub mod protocol;
+pub mod server;
+
+/// how long a request may sit without the server answering
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// how many requests are in flight at once, the rest wait their turn
+const WORKERS: usize = 4;
+
+/// fetches assets from a `yage-asset-server`. connections are opened on first use and kept for
+/// the next request to the same server
+pub struct Network {
+    addr: SocketAddr,
+    cache: Arc<AssetCache>,
+    decoders: DecoderRegistry,
+    pool: Arc<Pool>,
+    workers: ThreadPool,
+    disk: Option<Arc<DiskCache>>,
+    /// the entry the network keeps for each asset it has loaded
+    loaded: Mutex<HashMap<String, NonZero<usize>>>,
+    /// fetches that have not come back yet, so a file two loads need is only fetched once
+    in_flight: Mutex<HashMap<String, reader::Request>>,
+}
+
+/// idle connections, by server
+#[derive(Default)]
+struct Pool {
+    idle: Mutex<HashMap<SocketAddr, Vec<TcpStream>>>,
+}
+
+impl Pool {
+    /// sends `request` and waits for the answer. a pooled connection the server has since
+    /// closed is retried once on a fresh one
+    fn transact(&self, addr: SocketAddr, request: &protocol::Request) -> io::Result<Vec<u8>> {
+        let frame = request.encode();
+        let pooled = self.idle.lock().unwrap().get_mut(&addr).and_then(Vec::pop);
+        let (stream, response) = match pooled {
+            Some(stream) => match Self::send(&stream, &frame) {
+                Ok(response) => (stream, response),
+                Err(_) => Self::connect_and_send(addr, &frame)?,
+            },
+            None => Self::connect_and_send(addr, &frame)?,
+        };
+
+        let body = protocol::decode_response(&response)?;
+        // error statuses still leave the connection usable
+        self.idle
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_default()
+            .push(stream);
+        Ok(body?.to_vec())
+    }
+
+    fn connect_and_send(addr: SocketAddr, frame: &[u8]) -> io::Result<(TcpStream, Vec<u8>)> {
+        let stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let response = Self::send(&stream, frame)?;
+        Ok((stream, response))
+    }
+
+    fn send(mut stream: &TcpStream, frame: &[u8]) -> io::Result<Vec<u8>> {
+        protocol::write_frame(&mut stream, frame)?;
+        protocol::read_frame(&mut BufReader::new(stream), protocol::MAX_FRAME)?
+            .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+/// a request on its way to the server and back
+pub struct Pending(reader::Request);
+
+impl Future for Pending {
+    type Output = io::Result<Bytes>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl Network {
+    /// the server `Loader::init` was given, which named loads go to
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn cache(&self) -> &Arc<AssetCache> {
+        &self.cache
+    }
+
+    pub fn decoders(&self) -> &DecoderRegistry {
+        &self.decoders
+    }
+
+    pub fn decoders_mut(&mut self) -> &mut DecoderRegistry {
+        &mut self.decoders
+    }
+
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk.as_deref()
+    }
+
+    /// keeps whole file fetches on disk, so the next run only asks the server whether they
+    /// changed rather than downloading them again
+    pub fn set_disk_cache(&mut self, disk: Option<DiskCache>) {
+        self.disk = disk.map(Arc::new);
+    }
+
+    /// runs `work` on one of the workers
+    fn spawn(&self, work: impl FnOnce(&Pool) -> io::Result<Bytes> + Send + 'static) -> Pending {
+        let (pending, slot) = reader::Request::new();
+        let pool = self.pool.clone();
+        self.workers.submit(move || slot.complete(work(&pool)));
+        Pending(pending)
+    }
+
+    /// the whole of `name`. with a disk cache, a copy from an earlier run is used if the server
+    /// says it is still current
+    pub fn get(&self, name: &str) -> Pending {
+        let Some(disk) = self.disk.clone() else {
+            return self.get_range_inner(name, 0, None);
+        };
+        let (addr, name) = (self.addr, name.to_owned());
+        self.spawn(move |pool| fetch_cached(pool, &disk, addr, &name))
+    }
+
+    /// the bytes of `name` in `range`, cut short if the file ends first. fails with
+    /// `InvalidInput` if `range` starts past the end
+    pub fn get_range(&self, name: &str, range: Range<u64>) -> Pending {
+        let len = range.end.saturating_sub(range.start);
+        self.get_range_inner(name, range.start, Some(len))
+    }
+
+    fn get_range_inner(&self, name: &str, offset: u64, len: Option<u64>) -> Pending {
+        let request = protocol::Request::Get {
+            name: name.to_owned(),
+            offset,
+            len,
+        };
+        let addr = self.addr;
+        self.spawn(move |pool| {
+            let body = pool.transact(addr, &request)?;
+            protocol::decode_data(&body).map(|(_, bytes)| Bytes::from(bytes))
+        })
+    }
+
+    /// every file the server at `addr` has, as `\n` separated names
+    pub fn list(&self, addr: SocketAddr) -> Pending {
+        self.spawn(move |pool| {
+            let body = pool.transact(addr, &protocol::Request::List)?;
+            Ok(Bytes::from(body))
+        })
+    }
+
+    /// fetches and decodes `name` from the server, like `FileSystem` does from disk, along with
+    /// everything its decoder says it depends on
+    pub fn load_named(&self, name: &str) -> NetFut<'_> {
+        NetFut::new(self, name.to_owned(), Vec::new())
+    }
+
+    /// starts fetching `name`, or joins a fetch of it that is already going
+    fn request(&self, name: &str) -> Pending {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let request = in_flight
+            .entry(name.to_owned())
+            .or_insert_with(|| self.get(name).0);
+        Pending(request.clone())
+    }
+
+    /// decodes `bytes` and puts the result in the cache, unless a load that finished first
+    /// already did
+    fn store(&self, name: &str, kind: AssetKind, bytes: Bytes) -> io::Result<NonZero<usize>> {
+        let mut asset = Asset::new(kind, bytes);
+        self.decoders
+            .decode_with(name, &mut asset, self)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut loaded = self.loaded.lock().unwrap();
+        self.in_flight.lock().unwrap().remove(name);
+        if let Some(&index) = loaded.get(name) {
+            return Ok(index);
+        }
+        let index = self.cache.insert_content(name, asset);
+        loaded.insert(name.to_owned(), index);
+        Ok(index)
+    }
+}
+
+/// `name` from the disk cache if the server at `addr` still has the same bytes, from the server
+/// otherwise
+fn fetch_cached(pool: &Pool, disk: &DiskCache, addr: SocketAddr, name: &str) -> io::Result<Bytes> {
+    let server = addr.to_string();
+    let response = match disk.get(&server, name) {
+        Some((hash, bytes)) => {
+            let request = protocol::Request::Revalidate {
+                name: name.to_owned(),
+                hash,
+            };
+            match pool.transact(addr, &request) {
+                Err(error) if is_not_modified(&error) => return Ok(bytes),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    let _ = disk.remove(&server, name);
+                    return Err(error);
+                }
+                body => body,
+            }
+        }
+        None => pool.transact(
+            addr,
+            &protocol::Request::Get {
+                name: name.to_owned(),
+                offset: 0,
+                len: None,
+            },
+        ),
+    };
+    let body = response?;
+    let (_, bytes) = protocol::decode_data(&body)?;
+    // a full disk shouldn't fail the fetch itself
+    let _ = disk.put(&server, name, bytes);
+    Ok(Bytes::from(bytes))
+}
+
+fn is_not_modified(error: &io::Error) -> bool {
+    let remote = error.get_ref().and_then(|error| error.downcast_ref());
+    remote.is_some_and(|remote: &protocol::RemoteError| {
+        remote.status == protocol::Status::NotModified
+    })
+}
+
+/// lets decoders pull in the files an asset refers to. `NetFut` loads those before decoding,
+/// so this only looks in the cache and never waits on the server
+impl Fetch for Network {
+    fn fetch(&self, name: &str) -> Option<Bytes> {
+        let index = self.loaded.lock().unwrap().get(name).copied()?;
+        Some(self.cache.lookup(&index)?.data().clone())
+    }
+}
+
+/// a fetch from the server, then the same for everything its decoder depends on (all at once,
+/// and theirs in turn), then decoding on whoever polls. the handle borrows the entry the
+/// network keeps for that name, `into_owned` gives an entry of your own
+pub struct NetFut<'a> {
+    this: &'a Network,
+    name: String,
+    kind: AssetKind,
+    /// the loads that led to this one, for spotting cycles
+    chain: Vec<String>,
+    stage: Stage<'a>,
+}
+
+enum Stage<'a> {
+    Start,
+    Fetching(Pending),
+    Dependencies {
+        bytes: Bytes,
+        /// `None` once that dependency has loaded
+        loads: Vec<Option<Pin<Box<NetFut<'a>>>>>,
+    },
+    Done,
+}
+
+impl<'a> NetFut<'a> {
+    fn new(this: &'a Network, name: String, chain: Vec<String>) -> Self {
+        Self {
+            this,
+            kind: this.decoders.kind_of(&name),
+            name,
+            chain,
+            stage: Stage::Start,
+        }
+    }
+
+    /// drives the load, and tells the cache's subscribers if it failed
+    fn poll_load(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<NonZero<usize>>> {
+        let poll = self.poll_stages(cx);
+        if let Poll::Ready(Err(error)) = &poll {
+            self.this.cache.emit(AssetEvent::failed(&self.name, error));
+        }
+        poll
+    }
+
+    fn poll_stages(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<NonZero<usize>>> {
+        let this = self.this;
+        loop {
+            match &mut self.stage {
+                Stage::Start => {
+                    if self.chain.contains(&self.name) {
+                        return Poll::Ready(Err(graph::cycle_error(&self.chain, &self.name)));
+                    }
+                    if let Some(&index) = this.loaded.lock().unwrap().get(&self.name) {
+                        return Poll::Ready(Ok(index));
+                    }
+                    self.stage = Stage::Fetching(this.request(&self.name));
+                }
+                Stage::Fetching(request) => {
+                    let bytes = match Pin::new(request).poll(cx) {
+                        Poll::Ready(Ok(bytes)) => bytes,
+                        Poll::Ready(Err(error)) => {
+                            this.in_flight.lock().unwrap().remove(&self.name);
+                            return Poll::Ready(Err(error));
+                        }
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let names = this.decoders.dependencies(&self.name, self.kind, &bytes);
+                    let mut chain = self.chain.clone();
+                    chain.push(self.name.clone());
+                    let loads = names
+                        .into_iter()
+                        .map(|name| Some(Box::pin(NetFut::new(this, name, chain.clone()))))
+                        .collect();
+                    self.stage = Stage::Dependencies { bytes, loads };
+                }
+                Stage::Dependencies { bytes, loads } => {
+                    for slot in loads.iter_mut() {
+                        let Some(load) = slot else {
+                            continue;
+                        };
+                        match load.poll_load(cx) {
+                            Poll::Ready(Ok(_)) => *slot = None,
+                            Poll::Ready(Err(error)) => {
+                                this.in_flight.lock().unwrap().remove(&self.name);
+                                let message =
+                                    format!("`{}` needs `{}`: {error}", self.name, load.name);
+                                return Poll::Ready(Err(io::Error::new(error.kind(), message)));
+                            }
+                            Poll::Pending => {}
+                        }
+                    }
+                    if loads.iter().any(Option::is_some) {
+                        return Poll::Pending;
+                    }
+                    let bytes = bytes.clone();
+                    self.stage = Stage::Done;
+                    return Poll::Ready(this.store(&self.name, self.kind, bytes));
+                }
+                Stage::Done => panic!("`NetFut` polled after it finished"),
+            }
+        }
+    }
+}
+
+impl<'a> Future for NetFut<'a> {
+    type Output = io::Result<CowHandle<'a, AssetCache>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.get_mut();
+        let this = fut.this;
+        fut.poll_load(cx)
+            .map_ok(|index| CowHandle::Borrowed(BorrowedHandle::new(index, &this.cache)))
+    }
+}
+
+/// the loader is keyed by server, so `load` fetches the listing of whatever server is at that
+/// address. named assets go through `Network::load_named`
+impl Loader<SocketAddr, SocketAddr, AssetCache> for Network {
+    type Error = io::Error;
+    type InitFuture = std::future::Ready<io::Result<Self>>;
+    type LoadFuture<'a> = NetFut<'a>;
+
+    /// nothing is sent until the first load, so a missing server only fails the loads
+    fn init(addr: SocketAddr, cache: Arc<AssetCache>) -> Self::InitFuture
+    where
+        Self: Sized,
+    {
+        std::future::ready(Ok(Self {
+            addr,
+            cache,
+            decoders: DecoderRegistry::new()
+                .with_json()
+                .with_meshes()
+                .with_images()
+                .with_fonts()
+                .with_worlds()
+                .with_strings(Locale::default())
+                .with_preload(),
+            pool: Arc::new(Pool::default()),
+            workers: ThreadPool::new("yage-net", WORKERS),
+            disk: None,
+            loaded: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    fn load(&self, addr: SocketAddr) -> Self::LoadFuture<'_> {
+        NetFut {
+            this: self,
+            name: format!("{addr}/"),
+            kind: AssetKind::Other,
+            chain: Vec::new(),
+            stage: Stage::Fetching(self.list(addr)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, block_on, waker};
+    use server::Server;
+    use std::net::TcpListener;
+    use std::sync::Condvar;
+    use yage_core::asset::mesh::Mesh;
+
+    /// a triangle using the one material in `ship.mtl`
+    const SHIP: &str = "mtllib ship.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl hull\nf 1 2 3\n";
+
+    #[test]
+    fn loopback() {
+        let root = TempDir::new("net");
+        std::fs::create_dir_all(root.join("data")).unwrap();
+        std::fs::write(root.join("data/hello.txt"), b"hello, world").unwrap();
+        std::fs::write(root.join("config.json"), br#"{"speed": 3}"#).unwrap();
+        std::fs::write(root.join("data/ship.obj"), SHIP).unwrap();
+        std::fs::write(root.join("data/ship.mtl"), "newmtl hull\nKd 1 0 0\n").unwrap();
+
+        let server = Server::bind("127.0.0.1:0", root.path()).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+        let net = block_on(Network::init(addr, Arc::new(AssetCache::new()))).unwrap();
+
+        assert_eq!(
+            &*block_on(net.get("data/hello.txt")).unwrap(),
+            b"hello, world"
+        );
+        assert_eq!(
+            &*block_on(net.get_range("data/hello.txt", 7..100)).unwrap(),
+            b"world"
+        );
+        let error = block_on(net.get_range("data/hello.txt", 20..30)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = block_on(net.get("missing.txt")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let error = block_on(net.get("../escape")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let listing = block_on(net.load(addr)).unwrap();
+        assert_eq!(
+            &**listing.asset().unwrap().data(),
+            b"config.json\ndata/hello.txt\ndata/ship.mtl\ndata/ship.obj"
+        );
+        let config = block_on(net.load_named("config.json")).unwrap();
+        assert!(config.asset().unwrap().is_decoded());
+        // the material library is fetched before the mesh decodes, not from inside the decoder
+        let ship = block_on(net.load_named("data/ship.obj")).unwrap();
+        assert!(ship.get::<Mesh>().unwrap().material("hull").is_some());
+        assert!(net.loaded.lock().unwrap().contains_key("data/ship.mtl"));
+        assert_eq!(net.pool.idle.lock().unwrap()[&addr].len(), 1);
+    }
+
+    /// serves `files` and counts what is asked for. `held` isn't answered until `release`
+    struct Counting {
+        files: &'static [(&'static str, &'static str)],
+        held: &'static str,
+        gets: Mutex<HashMap<String, usize>>,
+        released: (Mutex<bool>, Condvar),
+    }
+
+    impl Counting {
+        fn spawn(self: Arc<Self>) -> SocketAddr {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let this = self.clone();
+                    std::thread::spawn(move || this.serve(stream?));
+                }
+                io::Result::Ok(())
+            });
+            addr
+        }
+
+        fn serve(&self, stream: TcpStream) -> io::Result<()> {
+            let mut input = BufReader::new(stream.try_clone()?);
+            while let Some(frame) = protocol::read_frame(&mut input, protocol::MAX_REQUEST)? {
+                let Ok(protocol::Request::Get { name, .. }) = protocol::Request::decode(&frame)
+                else {
+                    panic!("only whole files are asked for");
+                };
+                *self.gets.lock().unwrap().entry(name.clone()).or_default() += 1;
+                if name == self.held {
+                    let (released, wake) = &self.released;
+                    let released = released.lock().unwrap();
+                    drop(wake.wait_while(released, |released| !*released).unwrap());
+                }
+                let (_, text) = self.files.iter().find(|(file, _)| *file == name).unwrap();
+                let response = protocol::encode_data(text.len() as u64, text.as_bytes());
+                protocol::write_frame(&mut &stream, &response)?;
+            }
+            Ok(())
+        }
+
+        fn release(&self) {
+            let (released, wake) = &self.released;
+            *released.lock().unwrap() = true;
+            wake.notify_all();
+        }
+    }
+
+    #[test]
+    fn shared_dependencies_are_fetched_once() {
+        let server = Arc::new(Counting {
+            files: &[
+                ("a.obj", SHIP),
+                ("b.obj", SHIP),
+                ("ship.mtl", "newmtl hull\n"),
+            ],
+            held: "ship.mtl",
+            gets: Mutex::new(HashMap::new()),
+            released: (Mutex::new(false), Condvar::new()),
+        });
+        let addr = server.clone().spawn();
+        let net = block_on(Network::init(addr, Arc::new(AssetCache::new()))).unwrap();
+
+        let mut loads = [
+            Box::pin(net.load_named("a.obj")),
+            Box::pin(net.load_named("b.obj")),
+        ];
+        let waker = waker();
+        let mut cx = Context::from_waker(&waker);
+        // both meshes are waiting on the material before the server sends it
+        loop {
+            for load in &mut loads {
+                assert!(load.as_mut().poll(&mut cx).is_pending());
+            }
+            if loads
+                .iter()
+                .all(|load| matches!(load.stage, Stage::Dependencies { .. }))
+            {
+                break;
+            }
+            std::thread::park();
+        }
+        server.release();
+        for load in loads {
+            let mesh = block_on(load).unwrap();
+            assert!(mesh.get::<Mesh>().unwrap().material("hull").is_some());
+        }
+        assert_eq!(server.gets.lock().unwrap()["ship.mtl"], 1);
+        assert!(net.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn disk_cache_revalidates() {
+        let root = TempDir::new("net-disk");
+        let served = root.join("served");
+        std::fs::create_dir_all(&served).unwrap();
+        std::fs::write(served.join("level.txt"), b"first").unwrap();
+
+        let server = Server::bind("127.0.0.1:0", &served).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+        let mut net = block_on(Network::init(addr, Arc::new(AssetCache::new()))).unwrap();
+        net.set_disk_cache(Some(DiskCache::open(root.join("cache"), 1 << 20).unwrap()));
+
+        assert_eq!(&*block_on(net.get("level.txt")).unwrap(), b"first");
+        let (hash, _) = net
+            .disk_cache()
+            .unwrap()
+            .get(&addr.to_string(), "level.txt")
+            .unwrap();
+        let request = protocol::Request::Revalidate {
+            name: "level.txt".into(),
+            hash,
+        };
+        assert!(is_not_modified(
+            &net.pool.transact(addr, &request).unwrap_err()
+        ));
+        assert_eq!(&*block_on(net.get("level.txt")).unwrap(), b"first");
+
+        std::fs::write(served.join("level.txt"), b"second").unwrap();
+        assert_eq!(&*block_on(net.get("level.txt")).unwrap(), b"second");
+        let disk = net.disk_cache().unwrap();
+        assert_eq!(
+            &*disk.get(&addr.to_string(), "level.txt").unwrap().1,
+            b"second"
+        );
+
+        std::fs::remove_file(served.join("level.txt")).unwrap();
+        assert!(block_on(net.get("level.txt")).is_err());
+        assert!(disk.get(&addr.to_string(), "level.txt").is_none());
+    }
+}
@@ -0,0 +1,278 @@
+//! the asset fetch protocol. every message is a frame: a little-endian u32 length, then that many
+//! bytes. a connection carries any number of requests, each answered before the next is sent
+//!
+//! a request is the protocol version, an op, then for the op:
+//! - `get`: offset u64, length u64 (`u64::MAX` reads to the end), then the name as utf-8
+//! - `list`: nothing
+//! - `revalidate`: the sha256 of the copy the client has, then the name. answered like a whole
+//!   file `get`, or with `not modified` if the server's copy hashes the same
+//!
+//! a response is a status, then for `ok` the file's full size as u64 followed by the requested
+//! bytes (`list` answers with names joined by `\n`), or a utf-8 message for anything else
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use yage_core::asset::ContentHash;
+
+pub const VERSION: u8 = 1;
+
+/// the largest response a client accepts
+pub const MAX_FRAME: u32 = 256 << 20;
+/// the largest request a server accepts, names are short
+pub const MAX_REQUEST: u32 = 64 << 10;
+
+const OP_GET: u8 = 1;
+const OP_LIST: u8 = 2;
+const OP_REVALIDATE: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Get {
+        name: String,
+        offset: u64,
+        /// `None` reads to the end of the file
+        len: Option<u64>,
+    },
+    List,
+    Revalidate {
+        name: String,
+        hash: ContentHash,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 0,
+    NotFound = 1,
+    BadRequest = 2,
+    /// the offset is past the end of the file
+    BadRange = 3,
+    UnsupportedVersion = 4,
+    Internal = 5,
+    /// the client's copy is still current
+    NotModified = 6,
+}
+
+/// a status other than `ok` that the server answered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteError {
+    pub status: Status,
+    pub message: String,
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        match self {
+            Self::Get { name, offset, len } => {
+                out.push(OP_GET);
+                out.extend_from_slice(&offset.to_le_bytes());
+                out.extend_from_slice(&len.unwrap_or(u64::MAX).to_le_bytes());
+                out.extend_from_slice(name.as_bytes());
+            }
+            Self::List => out.push(OP_LIST),
+            Self::Revalidate { name, hash } => {
+                out.push(OP_REVALIDATE);
+                out.extend_from_slice(hash.as_bytes());
+                out.extend_from_slice(name.as_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn decode(frame: &[u8]) -> Result<Self, RemoteError> {
+        let bad = |message: &str| RemoteError::new(Status::BadRequest, message);
+        match frame {
+            [VERSION, OP_GET, rest @ ..] if rest.len() >= 16 => {
+                let offset = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+                let len = u64::from_le_bytes(rest[8..16].try_into().unwrap());
+                let name = std::str::from_utf8(&rest[16..])
+                    .map_err(|_| bad("the name is not valid utf-8"))?;
+                Ok(Self::Get {
+                    name: name.to_owned(),
+                    offset,
+                    len: (len != u64::MAX).then_some(len),
+                })
+            }
+            [VERSION, OP_LIST] => Ok(Self::List),
+            [VERSION, OP_REVALIDATE, rest @ ..] if rest.len() >= 32 => {
+                let hash = ContentHash(rest[..32].try_into().unwrap());
+                let name = std::str::from_utf8(&rest[32..])
+                    .map_err(|_| bad("the name is not valid utf-8"))?;
+                Ok(Self::Revalidate {
+                    name: name.to_owned(),
+                    hash,
+                })
+            }
+            [VERSION, ..] => Err(bad("unknown op or truncated request")),
+            [version, ..] => Err(RemoteError::new(
+                Status::UnsupportedVersion,
+                &format!("protocol version {version}, this server speaks {VERSION}"),
+            )),
+            [] => Err(bad("empty request")),
+        }
+    }
+}
+
+impl Status {
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => Self::Ok,
+            1 => Self::NotFound,
+            2 => Self::BadRequest,
+            3 => Self::BadRange,
+            4 => Self::UnsupportedVersion,
+            5 => Self::Internal,
+            6 => Self::NotModified,
+            _ => return None,
+        })
+    }
+
+    /// the closest `io::ErrorKind`, so callers can match on it like any other read error
+    pub fn kind(self) -> io::ErrorKind {
+        match self {
+            Self::Ok | Self::NotModified => io::ErrorKind::Other,
+            Self::NotFound => io::ErrorKind::NotFound,
+            Self::BadRequest | Self::BadRange => io::ErrorKind::InvalidInput,
+            Self::UnsupportedVersion => io::ErrorKind::Unsupported,
+            Self::Internal => io::ErrorKind::Other,
+        }
+    }
+
+    /// the status for a server side read failing with `error`
+    pub fn of(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::InvalidInput => Self::BadRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::NotFound => "not found",
+            Self::BadRequest => "bad request",
+            Self::BadRange => "bad range",
+            Self::UnsupportedVersion => "unsupported version",
+            Self::Internal => "internal error",
+            Self::NotModified => "not modified",
+        })
+    }
+}
+
+impl RemoteError {
+    pub fn new(status: Status, message: &str) -> Self {
+        Self {
+            status,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "server answered {}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for RemoteError {}
+
+impl From<RemoteError> for io::Error {
+    fn from(error: RemoteError) -> Self {
+        io::Error::new(error.status.kind(), error)
+    }
+}
+
+/// an `ok` response to a `get`: the file's full size, then the bytes that were asked for
+pub fn encode_data(size: u64, bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(9 + bytes.len());
+    out.push(Status::Ok as u8);
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(bytes);
+    out
+}
+
+pub fn encode_list<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<u8> {
+    let mut out = vec![Status::Ok as u8];
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 {
+            out.push(b'\n');
+        }
+        out.extend_from_slice(name.as_bytes());
+    }
+    out
+}
+
+pub fn encode_error(error: &RemoteError) -> Vec<u8> {
+    let mut out = vec![error.status as u8];
+    out.extend_from_slice(error.message.as_bytes());
+    out
+}
+
+/// the body of an `ok` response, or the error the server sent instead
+pub fn decode_response(frame: &[u8]) -> io::Result<Result<&[u8], RemoteError>> {
+    let (&code, body) = frame
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty response"))?;
+    match Status::from_code(code) {
+        Some(Status::Ok) => Ok(Ok(body)),
+        Some(status) => Ok(Err(RemoteError {
+            status,
+            message: String::from_utf8_lossy(body).into_owned(),
+        })),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown status {code}"),
+        )),
+    }
+}
+
+/// splits the body of a `get` response into the full size and the bytes
+pub fn decode_data(body: &[u8]) -> io::Result<(u64, &[u8])> {
+    if body.len() < 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated get response",
+        ));
+    }
+    let size = u64::from_le_bytes(body[0..8].try_into().unwrap());
+    Ok((size, &body[8..]))
+}
+
+pub fn write_frame(out: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(payload)?;
+    out.flush()
+}
+
+/// reads one frame of at most `limit` bytes. `None` if the peer closed the connection between
+/// frames
+pub fn read_frame(input: &mut impl Read, limit: u32) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match input.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    let len = u32::from_le_bytes(len);
+    if len > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{len} byte frame is over the {limit} byte limit"),
+        ));
+    }
+    let mut payload = vec![0; len as usize];
+    input.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
@@ -0,0 +1,158 @@
+//! serves a directory or `.pak` archive over the asset fetch protocol. meant for local
+//! development: a fixed set of threads each serve one connection at a time, and whoever
+//! connects past that waits in the listen backlog
+
+use super::protocol::{self, RemoteError, Request, Status};
+use crate::fs::vfs::{Layer, Vfs};
+use std::io::{self, BufReader, BufWriter};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use yage_core::asset::ContentHash;
+
+/// how many connections are served at once
+const WORKERS: usize = 16;
+/// connections that send nothing for this long are closed, so idle clients don't keep a
+/// worker. clients reconnect on their next request
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Server {
+    listener: TcpListener,
+    vfs: Arc<Vfs>,
+}
+
+impl Server {
+    /// listens on `addr` and serves everything under `root`, which can also be a `.pak` file
+    pub fn bind(addr: impl ToSocketAddrs, root: impl Into<PathBuf>) -> io::Result<Self> {
+        let mut vfs = Vfs::new();
+        vfs.mount("", Layer::open(root)?)?;
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            vfs: Arc::new(vfs),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// accepts connections until accepting fails
+    pub fn run(self) -> io::Result<()> {
+        let mut workers = Vec::with_capacity(WORKERS);
+        for i in 0..WORKERS {
+            let (listener, vfs) = (self.listener.try_clone()?, self.vfs.clone());
+            let worker = std::thread::Builder::new()
+                .name(format!("yage-asset-server-{i}"))
+                .spawn(move || accept(&listener, &vfs))?;
+            workers.push(worker);
+        }
+        let mut result = Ok(());
+        for worker in workers {
+            let accepted = worker.join().expect("asset server worker panicked");
+            result = result.and(accepted);
+        }
+        result
+    }
+}
+
+/// one worker's share of the connections
+fn accept(listener: &TcpListener, vfs: &Vfs) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+        // a connection going away mid request is the client's problem
+        let _ = serve(vfs, stream);
+    }
+}
+
+fn serve(vfs: &Vfs, stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = BufWriter::new(stream);
+    while let Some(frame) = protocol::read_frame(&mut input, protocol::MAX_REQUEST)? {
+        let response = match Request::decode(&frame).and_then(|request| answer(vfs, request)) {
+            Ok(response) => response,
+            Err(error) => protocol::encode_error(&error),
+        };
+        protocol::write_frame(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+fn answer(vfs: &Vfs, request: Request) -> Result<Vec<u8>, RemoteError> {
+    match request {
+        Request::Get { name, offset, len } => {
+            let bytes = vfs
+                .read(&name)
+                .map_err(|error| RemoteError::new(Status::of(&error), &error.to_string()))?;
+            let size = bytes.len() as u64;
+            if offset > size {
+                return Err(RemoteError::new(
+                    Status::BadRange,
+                    &format!("offset {offset} is past the end of `{name}` ({size} bytes)"),
+                ));
+            }
+            let end = len.map_or(size, |len| offset.saturating_add(len).min(size));
+            Ok(protocol::encode_data(
+                size,
+                &bytes[offset as usize..end as usize],
+            ))
+        }
+        Request::Revalidate { name, hash } => {
+            let bytes = vfs
+                .read(&name)
+                .map_err(|error| RemoteError::new(Status::of(&error), &error.to_string()))?;
+            if ContentHash::of(&bytes) == hash {
+                return Err(RemoteError::new(Status::NotModified, &name));
+            }
+            Ok(protocol::encode_data(bytes.len() as u64, &bytes))
+        }
+        Request::List => {
+            let mut names = Vec::new();
+            for mount in vfs.mounts() {
+                list(mount.layer(), &mut names)
+                    .map_err(|error| RemoteError::new(Status::Internal, &error.to_string()))?;
+            }
+            names.sort_unstable();
+            names.dedup();
+            Ok(protocol::encode_list(names.iter().map(String::as_str)))
+        }
+    }
+}
+
+/// every file name in `layer`
+fn list(layer: &Layer, names: &mut Vec<String>) -> io::Result<()> {
+    match layer {
+        Layer::Pak(_, archive) => {
+            names.extend(archive.entries().map(|(name, _)| name.to_owned()));
+            Ok(())
+        }
+        Layer::Dir(root) | Layer::UserDir(root) => {
+            let mut pending = vec![root.clone()];
+            while let Some(dir) = pending.pop() {
+                for entry in std::fs::read_dir(&dir)? {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        pending.push(path);
+                    } else if let Some(name) = relative_name(root, &path) {
+                        names.push(name);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Layer::Memory(_) => Ok(()),
+    }
+}
+
+/// `path` relative to `root` and `/` separated, unless it isn't utf-8
+fn relative_name(root: &Path, path: &Path) -> Option<String> {
+    let parts = path
+        .strip_prefix(root)
+        .ok()?
+        .components()
+        .map(|part| part.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("/"))
+}
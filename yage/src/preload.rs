@@ -0,0 +1,269 @@
+//! a loader plugin that loads whole groups out of a preload manifest, so the loading stage
+//! doesn't have to call `load` for every asset by hand
+
+use crate::cache::AssetCache;
+use crate::fs::FileSystem;
+use crate::net::Network;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll, ready};
+use yage_core::asset::preload::{PreloadEntry, PreloadManifest};
+use yage_core::asset::{Loader, OwnedHandle};
+use yage_core::plugin::Plugin;
+use yage_core::prelude::LoaderContext;
+
+/// one asset of a loaded group
+pub struct LoadedAsset {
+    path: String,
+    tags: Vec<String>,
+    handle: OwnedHandle<AssetCache>,
+}
+
+impl LoadedAsset {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn handle(&self) -> &OwnedHandle<AssetCache> {
+        &self.handle
+    }
+}
+
+/// what a `Preload` loaded, by group. every asset is an entry of the group's own, nothing else
+/// keeps it in the cache, so unloading a group frees what only it used
+#[derive(Default)]
+pub struct Preloaded {
+    groups: HashMap<String, Vec<LoadedAsset>>,
+}
+
+impl Preloaded {
+    /// in the order they finished loading, so highest priority first
+    pub fn group(&self, name: &str) -> Option<&[LoadedAsset]> {
+        self.groups.get(name).map(Vec::as_slice)
+    }
+
+    pub fn group_names(&self) -> impl Iterator<Item = &str> {
+        self.groups.keys().map(String::as_str)
+    }
+
+    /// `path` from whichever group has it
+    pub fn get(&self, path: &str) -> Option<&OwnedHandle<AssetCache>> {
+        self.assets()
+            .find(|asset| asset.path == path)
+            .map(LoadedAsset::handle)
+    }
+
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a LoadedAsset> {
+        self.assets().filter(move |asset| asset.has_tag(tag))
+    }
+
+    fn assets(&self) -> impl Iterator<Item = &LoadedAsset> {
+        self.groups.values().flatten()
+    }
+
+    /// drops every handle in `name`, e.g. when the level it was for ends. `false` if no such
+    /// group was loaded
+    pub fn unload(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+}
+
+/// loads groups from a preload manifest through the loader context, highest priority first.
+/// assets with the same priority load at the same time. once everything is in, `then` turns
+/// the `Preloaded` groups into the plugin for the init stage
+pub struct Preload<F, I> {
+    manifest: String,
+    groups: Vec<String>,
+    then: Option<F>,
+    /// every asset still to load, with the index of its group, highest priority first.
+    /// `None` until the manifest itself has loaded
+    queue: Option<Vec<(usize, PreloadEntry)>>,
+    loaded: Preloaded,
+    _output: PhantomData<fn() -> I>,
+}
+
+// nothing is ever pinned through a `Preload`
+impl<F, I> Unpin for Preload<F, I> {}
+
+impl<F, I> Preload<F, I>
+where
+    F: FnOnce(Preloaded) -> I,
+{
+    pub fn new(manifest: impl Into<String>, groups: &[&str], then: F) -> Self {
+        Self {
+            manifest: manifest.into(),
+            groups: groups.iter().map(|&group| group.into()).collect(),
+            then: Some(then),
+            queue: None,
+            loaded: Preloaded::default(),
+            _output: PhantomData,
+        }
+    }
+
+    /// plugins get their context afresh every poll, so loads can't be kept between polls.
+    /// they are started again instead, which is cheap: the file system shares reads that are
+    /// still going and hands back finished ones straight away
+    fn poll_load(&mut self, cx: &mut Context<'_>, fs: &mut FileSystem) -> Poll<io::Result<()>> {
+        let queue = match &mut self.queue {
+            Some(queue) => queue,
+            None => {
+                let manifest = {
+                    let handle = ready!(pin!(fs.load(&self.manifest)).poll(cx))?;
+                    let manifest = handle.get::<PreloadManifest>().map_err(|error| {
+                        let message =
+                            format!("`{}` isn't a preload manifest: {error}", self.manifest);
+                        io::Error::new(io::ErrorKind::InvalidData, message)
+                    })?;
+                    manifest.clone()
+                };
+                fs.unload(&self.manifest);
+
+                let mut queue = Vec::new();
+                for (index, name) in self.groups.iter().enumerate() {
+                    let group = manifest.group(name).ok_or_else(|| {
+                        let message = format!("`{}` has no group `{name}`", self.manifest);
+                        io::Error::new(io::ErrorKind::NotFound, message)
+                    })?;
+                    queue.extend(group.entries.iter().map(|entry| (index, entry.clone())));
+                }
+                // stable, so the same priority keeps group and file order
+                queue.sort_by_key(|(_, entry)| Reverse(entry.priority));
+                self.queue.insert(queue)
+            }
+        };
+
+        loop {
+            let Some((_, first)) = queue.first() else {
+                return Poll::Ready(Ok(()));
+            };
+            let priority = first.priority;
+            let tier = queue
+                .iter()
+                .take_while(|(_, entry)| entry.priority == priority)
+                .count();
+
+            let mut finished = Vec::new();
+            let mut pending = false;
+            for (i, (group, entry)) in queue[..tier].iter().enumerate() {
+                match pin!(fs.load(&entry.path)).poll(cx) {
+                    Poll::Ready(Ok(handle)) => finished.push((i, handle.into_owned())),
+                    Poll::Ready(Err(error)) => {
+                        let message = format!(
+                            "preloading `{}` for group `{}`: {error}",
+                            entry.path, self.groups[*group]
+                        );
+                        return Poll::Ready(Err(io::Error::new(error.kind(), message)));
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+
+            let mut done: Vec<_> = finished
+                .into_iter()
+                .rev()
+                .map(|(i, handle)| (queue.remove(i), handle))
+                .collect();
+            done.reverse();
+            for ((group, entry), handle) in done {
+                // the group's copy is what keeps the asset around from now on
+                fs.unload(&entry.path);
+                let group = self.loaded.groups.entry(self.groups[group].clone());
+                group.or_default().push(LoadedAsset {
+                    path: entry.path,
+                    tags: entry.tags,
+                    handle,
+                });
+            }
+            if pending {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl<'a, F, I> Plugin<&'a mut LoaderContext<FileSystem, Network, AssetCache>> for Preload<F, I>
+where
+    F: FnOnce(Preloaded) -> I,
+{
+    type Output = I;
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        input: &'a mut LoaderContext<FileSystem, Network, AssetCache>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().poll_load(cx, input.filesystem_mut())
+    }
+
+    fn poll_transform(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<I>> {
+        let this = self.get_mut();
+        let then = this
+            .then
+            .take()
+            .expect("`Preload` polled after it finished");
+        Poll::Ready(Ok(then(std::mem::take(&mut this.loaded))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, waker};
+    use std::sync::Arc;
+    use yage_core::states::new::SearchPaths;
+
+    #[test]
+    fn loads_and_unloads_groups() {
+        let root = TempDir::new("preload");
+        std::fs::create_dir_all(root.join("data/levels")).unwrap();
+        let manifest = "[common]\nui.json priority=10 tags=ui\n\n[forest]\nlevels/forest.json\n";
+        std::fs::write(root.join("data/game.preload"), manifest).unwrap();
+        std::fs::write(root.join("data/ui.json"), "[1, 2]").unwrap();
+        std::fs::write(root.join("data/levels/forest.json"), "{}").unwrap();
+
+        let path: &'static str = Box::leak(root.path().to_str().unwrap().into());
+        let paths = vec![path];
+        let mut fs = FileSystem::init(SearchPaths { paths }, Arc::new(AssetCache::new()))
+            .into_inner()
+            .unwrap();
+        let mut preload = Preload::new("data/game.preload", &["common", "forest"], |groups| groups);
+
+        let waker = waker();
+        let mut cx = Context::from_waker(&waker);
+        while preload.poll_load(&mut cx, &mut fs).is_pending() {
+            std::thread::park();
+        }
+        let mut loaded = (preload.then.take().unwrap())(std::mem::take(&mut preload.loaded));
+
+        assert!(loaded.get("data/ui.json").is_some());
+        assert_eq!(loaded.tagged("ui").count(), 1);
+        let cache = fs.cache().clone();
+        let entries = cache.len();
+        // the file system let go of its own entries, the groups hold the only ones
+        assert!(!fs.unload("data/levels/forest.json"));
+        assert!(loaded.unload("forest"));
+        assert_eq!(cache.len(), entries - 1);
+        assert!(loaded.group("forest").is_none());
+
+        let mut missing = Preload::new("data/game.preload", &["desert"], |groups| groups);
+        let error = loop {
+            match missing.poll_load(&mut cx, &mut fs) {
+                Poll::Ready(result) => break result.unwrap_err(),
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
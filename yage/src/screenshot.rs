@@ -0,0 +1,290 @@
+//! saving what a `Renderer` shows as PNG or PPM, and golden-image tests that compare a frame
+//! with a reference picture and draw a diff of where they disagree
+
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+use yage_core::asset::image::{Image, ImageError};
+use yage_core::renderer::{PixelFormat, Renderer, Rgba};
+
+/// how mismatched pixels show up in a diff image
+const MISMATCH: Rgba = Rgba::opaque(255, 0, 0);
+
+pub fn save_png<R>(renderer: &R, path: impl AsRef<Path>) -> io::Result<()>
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    fs::write(path, Image::capture(renderer).encode_png())
+}
+
+/// PPM has no alpha channel, so transparency is lost
+pub fn save_ppm<R>(renderer: &R, path: impl AsRef<Path>) -> io::Result<()>
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    fs::write(path, Image::capture(renderer).encode_ppm())
+}
+
+/// compares frames with a reference image. a pixel matches when none of its channels, alpha
+/// included, is further than `tolerance` from the reference.
+///
+/// when a frame doesn't match, it is written next to the reference as `name.actual.png`, and
+/// a copy of the reference faded to grey with the mismatched pixels in red as `name.diff.png`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Golden {
+    reference: PathBuf,
+    tolerance: u8,
+    update: bool,
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    /// the reference isn't an image we can read
+    Decode(ImageError),
+    /// there is no reference yet. the frame was written to `actual`, to be looked at and
+    /// renamed into place
+    MissingReference {
+        actual: PathBuf,
+    },
+    SizeMismatch {
+        expected: (u32, u32),
+        found: (u32, u32),
+        actual: PathBuf,
+    },
+    Mismatch {
+        /// how many pixels are off by more than the tolerance
+        pixels: usize,
+        /// the biggest difference in any channel
+        worst: u8,
+        diff: PathBuf,
+    },
+}
+
+impl Golden {
+    /// compares exactly
+    pub fn new(reference: impl Into<PathBuf>) -> Self {
+        Self {
+            reference: reference.into(),
+            tolerance: 0,
+            update: false,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// overwrites the reference with the frame instead of comparing, for when the rendering
+    /// was changed on purpose
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn reference(&self) -> &Path {
+        &self.reference
+    }
+
+    /// where a frame that doesn't match is written
+    pub fn actual_path(&self) -> PathBuf {
+        self.sibling("actual")
+    }
+
+    /// where the picture of the mismatched pixels is written
+    pub fn diff_path(&self) -> PathBuf {
+        self.sibling("diff")
+    }
+
+    /// `dir/name.png` becomes `dir/name.<suffix>.png`
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let stem = self.reference.file_stem().unwrap_or_default();
+        let mut name = stem.to_os_string();
+        name.push(format!(".{suffix}.png"));
+        self.reference.with_file_name(name)
+    }
+
+    pub fn check<R>(&self, renderer: &R) -> Result<(), GoldenError>
+    where
+        R: Renderer,
+        R::Pixel: PixelFormat,
+    {
+        self.check_image(&Image::capture(renderer))
+    }
+
+    pub fn check_image(&self, frame: &Image) -> Result<(), GoldenError> {
+        if self.update {
+            return Ok(write_png(&self.reference, frame)?);
+        }
+        let bytes = match fs::read(&self.reference) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                let actual = self.actual_path();
+                write_png(&actual, frame)?;
+                return Err(GoldenError::MissingReference { actual });
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let reference = Image::decode(&bytes).map_err(GoldenError::Decode)?;
+
+        let expected = (reference.width(), reference.height());
+        let found = (frame.width(), frame.height());
+        if expected != found {
+            let actual = self.actual_path();
+            write_png(&actual, frame)?;
+            return Err(GoldenError::SizeMismatch {
+                expected,
+                found,
+                actual,
+            });
+        }
+
+        let (mut pixels, mut worst) = (0, 0);
+        let mut diff = Vec::with_capacity(reference.pixels().len());
+        for (&want, &got) in reference.pixels().iter().zip(frame.pixels()) {
+            let off = [
+                want.r.abs_diff(got.r),
+                want.g.abs_diff(got.g),
+                want.b.abs_diff(got.b),
+                want.a.abs_diff(got.a),
+            ];
+            let off = off.into_iter().max().unwrap_or(0);
+            worst = worst.max(off);
+            if off > self.tolerance {
+                pixels += 1;
+                diff.push(MISMATCH);
+            } else {
+                diff.push(faded(want));
+            }
+        }
+        if pixels == 0 {
+            return Ok(());
+        }
+
+        let diff_image = Image::from_pixels(expected.0, expected.1, diff).unwrap();
+        let diff = self.diff_path();
+        write_png(&diff, &diff_image)?;
+        write_png(&self.actual_path(), frame)?;
+        Err(GoldenError::Mismatch {
+            pixels,
+            worst,
+            diff,
+        })
+    }
+}
+
+fn write_png(path: &Path, image: &Image) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, image.encode_png())
+}
+
+/// a light grey version of `color`, so the red of the mismatches stands out
+fn faded(color: Rgba) -> Rgba {
+    let luma = (color.r as u32 * 77 + color.g as u32 * 150 + color.b as u32 * 29) >> 8;
+    let luma = luma * color.a as u32 / 255;
+    let grey = (128 + luma / 2) as u8;
+    Rgba::opaque(grey, grey, grey)
+}
+
+/// `Golden::check` that panics, for tests
+#[track_caller]
+pub fn assert_golden<R>(renderer: &R, reference: impl Into<PathBuf>, tolerance: u8)
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    let golden = Golden::new(reference).with_tolerance(tolerance);
+    if let Err(error) = golden.check(renderer) {
+        panic!("{}: {error}", golden.reference().display());
+    }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Decode(error) => write!(f, "can't read the reference: {error}"),
+            Self::MissingReference { actual } => {
+                write!(
+                    f,
+                    "no reference image, wrote the frame to {}",
+                    actual.display()
+                )
+            }
+            Self::SizeMismatch {
+                expected,
+                found,
+                actual,
+            } => write!(
+                f,
+                "expected {}x{}, got {}x{}, wrote the frame to {}",
+                expected.0,
+                expected.1,
+                found.0,
+                found.1,
+                actual.display()
+            ),
+            Self::Mismatch {
+                pixels,
+                worst,
+                diff,
+            } => write!(
+                f,
+                "{pixels} pixels differ, by up to {worst}, see {}",
+                diff.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<io::Error> for GoldenError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn golden_images() {
+        let root = TempDir::new("golden");
+        let golden = Golden::new(root.join("frame.png"));
+        let mut fb: Framebuffer<Rgba> = Framebuffer::new(4, 3).unwrap();
+        fb.clear(Rgba::BLACK);
+        fb.pixel(1, 1, Rgba::opaque(200, 10, 10)).unwrap();
+
+        let error = golden.check(&fb).unwrap_err();
+        assert!(matches!(error, GoldenError::MissingReference { .. }));
+        golden.clone().with_update(true).check(&fb).unwrap();
+        golden.check(&fb).unwrap();
+
+        fb.pixel(1, 1, Rgba::opaque(204, 10, 10)).unwrap();
+        fb.pixel(3, 2, Rgba::WHITE).unwrap();
+        match golden.clone().with_tolerance(4).check(&fb) {
+            Err(GoldenError::Mismatch {
+                pixels: 1,
+                worst: 255,
+                diff,
+            }) => {
+                let diff = Image::decode(&fs::read(diff).unwrap()).unwrap();
+                assert_eq!(diff.get(3, 2), Some(MISMATCH));
+                assert_ne!(diff.get(1, 1), Some(MISMATCH));
+            }
+            other => panic!("{other:?}"),
+        }
+
+        save_ppm(&fb, root.join("frame.ppm")).unwrap();
+        let ppm = Image::decode(&fs::read(root.join("frame.ppm")).unwrap()).unwrap();
+        assert_eq!(ppm.get(3, 2), Some(Rgba::WHITE));
+    }
+}
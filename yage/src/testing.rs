@@ -0,0 +1,60 @@
+//! helpers shared by the tests: a bare executor, which parks the test thread until the future it
+//! is driving wakes it, and scratch directories that clean up after themselves
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// wakes the current thread out of `std::thread::park`
+pub(crate) fn waker() -> Waker {
+    Waker::from(Arc::new(Unpark(std::thread::current())))
+}
+
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    let waker = waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = std::pin::pin!(fut);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// an empty directory under the system temp dir, deleted with everything in it when this is
+/// dropped. that includes a test panicking halfway through
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` only has to be unique among the tests
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("yage-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,179 @@
+//! cheap to clone, read-only bytes for the cache to hold. usually they are an `Arc<[u8]>`, but
+//! anything that owns a byte slice can back them (a memory mapped file, a region of one), so
+//! the cache and handles don't care where an asset's bytes actually live
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Bound, Deref, RangeBounds};
+
+/// something that owns bytes and never moves or changes them while it is alive
+pub trait ByteSource: Send + Sync + 'static {
+    fn bytes(&self) -> &[u8];
+}
+
+#[derive(Clone)]
+enum Repr {
+    Shared(Arc<[u8]>),
+    External(Arc<dyn ByteSource>),
+}
+
+/// a view of `start..end` into shared bytes. cloning and slicing only bump a refcount
+#[derive(Clone)]
+pub struct Bytes {
+    repr: Repr,
+    start: usize,
+    end: usize,
+}
+
+impl Bytes {
+    pub fn new() -> Self {
+        Self::from(Arc::<[u8]>::from([]))
+    }
+
+    /// bytes kept alive by `source`, without copying them
+    pub fn from_source(source: impl ByteSource) -> Self {
+        let end = source.bytes().len();
+        Self {
+            repr: Repr::External(Arc::new(source)),
+            start: 0,
+            end,
+        }
+    }
+
+    /// whether these are backed by a `ByteSource` rather than an allocation of their own
+    pub fn is_external(&self) -> bool {
+        matches!(self.repr, Repr::External(_))
+    }
+
+    /// a view of `range` that shares the same bytes. panics if it is out of bounds, like
+    /// slicing would
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end && end <= self.len(),
+            "{start}..{end} is out of bounds for {} bytes",
+            self.len()
+        );
+        Self {
+            repr: self.repr.clone(),
+            start: self.start + start,
+            end: self.start + end,
+        }
+    }
+
+    /// the bytes as an `Arc<[u8]>`. free if that is what they already are, a copy otherwise
+    pub fn to_arc(&self) -> Arc<[u8]> {
+        match &self.repr {
+            Repr::Shared(arc) if self.start == 0 && self.end == arc.len() => arc.clone(),
+            _ => Arc::from(&**self),
+        }
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        let all = match &self.repr {
+            Repr::Shared(arc) => &arc[..],
+            Repr::External(source) => source.bytes(),
+        };
+        &all[self.start..self.end]
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Arc<[u8]>> for Bytes {
+    fn from(arc: Arc<[u8]>) -> Self {
+        let end = arc.len();
+        Self {
+            repr: Repr::Shared(arc),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from(Arc::<[u8]>::from(bytes))
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self::from(Arc::<[u8]>::from(bytes))
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Bytes {}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bytes")
+            .field("len", &self.len())
+            .field("external", &self.is_external())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Static(&'static [u8]);
+
+    impl ByteSource for Static {
+        fn bytes(&self) -> &[u8] {
+            self.0
+        }
+    }
+
+    #[test]
+    fn slices_share() {
+        let bytes = Bytes::from_source(Static(b"hello world"));
+        assert!(bytes.is_external());
+        let world = bytes.slice(6..);
+        assert_eq!(&*world, b"world");
+        assert_eq!(&*world.slice(1..=2), b"or");
+        assert_eq!(&*world.to_arc(), b"world");
+
+        let shared = Bytes::from(alloc::vec![1, 2, 3]);
+        let arc = shared.to_arc();
+        assert!(Arc::ptr_eq(&arc, &shared.to_arc()));
+        assert_eq!(shared.slice(..0), Bytes::new());
+    }
+}
@@ -0,0 +1,155 @@
+//! compressed assets. a loose file is compressed when its name ends in a codec's extension
+//! (`level.json.lz4`), an archive entry when its compression flag says so; either way it is
+//! decompressed before any decoder sees it, under the name without the extension
+//!
+//! an `.lz4` file is the original size as a little-endian u64 followed by one LZ4 block, a
+//! `.deflate` file is a zlib stream. `compress` writes both, for tools that pack assets
+
+use super::deflate::{deflate_zlib, inflate_zlib, InflateError};
+use super::lz4::{compress_block, decompress_block, Lz4Error};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// the most one asset may decompress to unless the loader says otherwise, so a small file
+/// can't claim gigabytes
+pub const DEFAULT_LIMIT: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    Lz4,
+    Deflate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecompressError {
+    /// the asset would decompress to more than the limit allows
+    TooLarge,
+    /// the size in front of an lz4 block is missing, or doesn't match the block
+    BadSize,
+    Lz4(Lz4Error),
+    Inflate(InflateError),
+}
+
+impl Codec {
+    pub const ALL: [Self; 2] = [Self::Lz4, Self::Deflate];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Lz4 => "lz4",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| extension.eq_ignore_ascii_case(codec.extension()))
+    }
+
+    /// the codec `name`'s last extension picks, and `name` without it
+    pub fn of_name(name: &str) -> Option<(Self, &str)> {
+        let (stem, extension) = name.rsplit_once('.')?;
+        if stem.is_empty() || stem.ends_with('/') {
+            return None;
+        }
+        Some((Self::from_extension(extension)?, stem))
+    }
+}
+
+pub fn compress(codec: Codec, data: &[u8]) -> Vec<u8> {
+    match codec {
+        Codec::Lz4 => {
+            let mut out = Vec::from((data.len() as u64).to_le_bytes());
+            out.extend_from_slice(&compress_block(data));
+            out
+        }
+        Codec::Deflate => deflate_zlib(data),
+    }
+}
+
+/// undoes `compress`, failing rather than growing the output past `limit` bytes
+pub fn decompress(codec: Codec, data: &[u8], limit: usize) -> Result<Vec<u8>, DecompressError> {
+    match codec {
+        Codec::Lz4 => {
+            let (size, block) = data
+                .split_first_chunk::<8>()
+                .ok_or(DecompressError::BadSize)?;
+            let size = u64::from_le_bytes(*size);
+            if size > limit as u64 {
+                return Err(DecompressError::TooLarge);
+            }
+            let out = decompress_block(block, size as usize).map_err(|error| match error {
+                // the block is longer than its size says
+                Lz4Error::TooLarge => DecompressError::BadSize,
+                error => DecompressError::Lz4(error),
+            })?;
+            if out.len() as u64 != size {
+                return Err(DecompressError::BadSize);
+            }
+            Ok(out)
+        }
+        Codec::Deflate => inflate_zlib(data, limit).map_err(|error| match error {
+            InflateError::TooLarge => DecompressError::TooLarge,
+            error => DecompressError::Inflate(error),
+        }),
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => f.write_str("decompresses to more than the limit allows"),
+            Self::BadSize => f.write_str("lz4 size header is missing or wrong"),
+            Self::Lz4(error) => write!(f, "lz4: {error}"),
+            Self::Inflate(error) => write!(f, "deflate: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for DecompressError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_and_limits() {
+        assert_eq!(
+            Codec::of_name("maps/level.json.LZ4"),
+            Some((Codec::Lz4, "maps/level.json"))
+        );
+        assert_eq!(Codec::of_name("maps/.deflate"), None);
+        assert_eq!(Codec::of_name("level.json"), None);
+
+        let data = b"hello hello hello hello, yage!".repeat(100);
+        for codec in Codec::ALL {
+            let compressed = compress(codec, &data);
+            assert!(compressed.len() < data.len() / 10);
+            assert_eq!(decompress(codec, &compressed, data.len()).unwrap(), data);
+            assert_eq!(
+                decompress(codec, &compressed, data.len() - 1),
+                Err(DecompressError::TooLarge)
+            );
+        }
+
+        // a tiny file claiming a huge size is turned away before anything is allocated
+        let mut bomb = Vec::from(u64::MAX.to_le_bytes());
+        bomb.push(0);
+        assert_eq!(
+            decompress(Codec::Lz4, &bomb, DEFAULT_LIMIT),
+            Err(DecompressError::TooLarge)
+        );
+        let mut lying = compress(Codec::Lz4, &data);
+        lying[0] -= 1;
+        assert_eq!(
+            decompress(Codec::Lz4, &lying, DEFAULT_LIMIT),
+            Err(DecompressError::BadSize)
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use super::{Asset, AssetKind, Bytes};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+/// the type-erased value a decoder leaves behind in the cache
+pub type DecodedValue = Arc<dyn Any + Send + Sync>;
+
+/// turns the raw bytes of an asset into a typed value.
+/// decoders run once, when the asset is loaded, and the output is what handles hand out
+pub trait AssetDecoder: Send + Sync + 'static {
+    type Output: Any + Send + Sync;
+    type Error: core::error::Error + Send + Sync + 'static;
+
+    fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Result<Self::Output, Self::Error>;
+
+    /// names of the other assets this one needs, from a quick look at `bytes`. loaders fetch
+    /// these first (and theirs, recursively), so `fetch_relative` finds them already loaded
+    fn dependencies(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+        let _ = (cx, bytes);
+        Vec::new()
+    }
+}
+
+/// lets a decoder read the files its asset refers to (materials, textures, ...),
+/// going through the same lookup the loader itself uses
+pub trait Fetch {
+    fn fetch(&self, name: &str) -> Option<Bytes>;
+}
+
+/// everything a decoder knows about the asset besides its bytes
+pub struct DecodeContext<'a> {
+    pub name: &'a str,
+    pub kind: AssetKind,
+    fetch: Option<&'a dyn Fetch>,
+}
+
+impl<'a> DecodeContext<'a> {
+    pub fn new(name: &'a str, kind: AssetKind) -> Self {
+        Self {
+            name,
+            kind,
+            fetch: None,
+        }
+    }
+
+    pub fn with_fetch(mut self, fetch: &'a dyn Fetch) -> Self {
+        self.fetch = Some(fetch);
+        self
+    }
+
+    /// resolves `path` against the directory this asset lives in. `..` pops a directory,
+    /// but never past the root of the search path
+    pub fn relative(&self, path: &str) -> String {
+        let path = path.replace('\\', "/");
+        if let Some(absolute) = path.strip_prefix('/') {
+            return normalize(absolute);
+        }
+        let dir = match self.name.rfind('/') {
+            Some(end) => &self.name[..end + 1],
+            None => "",
+        };
+        normalize(&alloc::format!("{dir}{path}"))
+    }
+
+    /// fetches `path`, relative to this asset. `None` if it could not be found,
+    /// or if the loader did not give the decoder a way to read files
+    pub fn fetch_relative(&self, path: &str) -> Option<Bytes> {
+        self.fetch?.fetch(&self.relative(path))
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+type ErasedDecoder =
+    dyn Fn(&DecodeContext<'_>, &[u8]) -> Result<DecodedValue, DecodeError> + Send + Sync;
+type ErasedDependencies = dyn Fn(&DecodeContext<'_>, &[u8]) -> Vec<String> + Send + Sync;
+
+struct Entry {
+    kind: AssetKind,
+    decode: Box<ErasedDecoder>,
+    dependencies: Box<ErasedDependencies>,
+}
+
+/// decoders keyed by file extension first, then by `AssetKind`
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: Vec<Entry>,
+    by_extension: BTreeMap<String, usize>,
+    by_kind: BTreeMap<AssetKind, usize>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registers `decoder` for `kind` and every extension in `extensions` (without the dot).
+    /// later registrations win over earlier ones
+    pub fn register<D: AssetDecoder>(&mut self, kind: AssetKind, extensions: &[&str], decoder: D) {
+        let index = self.decoders.len();
+        let decoder = Arc::new(decoder);
+        let scanner = decoder.clone();
+        self.decoders.push(Entry {
+            kind,
+            decode: Box::new(move |cx, bytes| match decoder.decode(cx, bytes) {
+                Ok(value) => Ok(Arc::new(value) as DecodedValue),
+                Err(error) => Err(DecodeError::new(cx.name, cx.kind, error)),
+            }),
+            dependencies: Box::new(move |cx, bytes| scanner.dependencies(cx, bytes)),
+        });
+        for ext in extensions {
+            self.by_extension.insert(ext.to_ascii_lowercase(), index);
+        }
+        self.by_kind.insert(kind, index);
+    }
+
+    /// the kind an asset called `name` will be tagged with, based on its extension
+    pub fn kind_of(&self, name: &str) -> AssetKind {
+        self.by_extension(name)
+            .map(|entry| entry.kind)
+            .unwrap_or(AssetKind::RawData)
+    }
+
+    pub fn has_decoder(&self, name: &str, kind: AssetKind) -> bool {
+        self.find(name, kind).is_some()
+    }
+
+    /// what the decoder for `name` says it depends on. empty if nothing is registered
+    pub fn dependencies(&self, name: &str, kind: AssetKind, bytes: &[u8]) -> Vec<String> {
+        match self.find(name, kind) {
+            Some(entry) => (entry.dependencies)(&DecodeContext::new(name, kind), bytes),
+            None => Vec::new(),
+        }
+    }
+
+    /// runs the matching decoder over `asset` and stores the result inside it.
+    /// returns `Ok(false)` if nothing is registered for the asset, which leaves it as raw bytes
+    pub fn decode<B>(&self, name: &str, asset: &mut Asset<B>) -> Result<bool, DecodeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.decode_in(DecodeContext::new(name, asset.kind), asset)
+    }
+
+    /// same as `decode`, but decoders can read the files the asset refers to through `fetch`
+    pub fn decode_with<B>(
+        &self,
+        name: &str,
+        asset: &mut Asset<B>,
+        fetch: &dyn Fetch,
+    ) -> Result<bool, DecodeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.decode_in(
+            DecodeContext::new(name, asset.kind).with_fetch(fetch),
+            asset,
+        )
+    }
+
+    fn decode_in<B>(&self, cx: DecodeContext<'_>, asset: &mut Asset<B>) -> Result<bool, DecodeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let Some(entry) = self.find(cx.name, cx.kind) else {
+            return Ok(false);
+        };
+        asset.value = Some((entry.decode)(&cx, asset.data.as_ref())?);
+        Ok(true)
+    }
+
+    fn find(&self, name: &str, kind: AssetKind) -> Option<&Entry> {
+        self.by_extension(name)
+            .or_else(|| self.by_kind.get(&kind).map(|&i| &self.decoders[i]))
+    }
+
+    fn by_extension(&self, name: &str) -> Option<&Entry> {
+        let ext = extension(name)?;
+        // extensions are stored lowercased, only allocate when we actually have to
+        let index = match self.by_extension.get(ext) {
+            Some(index) => *index,
+            None => *self.by_extension.get(&ext.to_ascii_lowercase())?,
+        };
+        Some(&self.decoders[index])
+    }
+}
+
+fn extension(name: &str) -> Option<&str> {
+    let file = name.rsplit(['/', '\\']).next()?;
+    let (stem, ext) = file.rsplit_once('.')?;
+    (!stem.is_empty()).then_some(ext)
+}
+
+pub struct DecodeError {
+    name: String,
+    kind: AssetKind,
+    source: Box<dyn core::error::Error + Send + Sync>,
+}
+
+impl DecodeError {
+    pub fn new<E>(name: &str, kind: AssetKind, source: E) -> Self
+    where
+        E: core::error::Error + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            kind,
+            source: Box::new(source),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> AssetKind {
+        self.kind
+    }
+}
+
+impl fmt::Debug for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecodeError")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to decode `{}` as {:?}: {}",
+            self.name, self.kind, self.source
+        )
+    }
+}
+
+impl core::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// decodes to the length of the bytes, tagged with which decoder ran
+    struct Tagged(&'static str);
+
+    #[derive(Debug)]
+    struct Never;
+
+    impl fmt::Display for Never {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("never")
+        }
+    }
+
+    impl core::error::Error for Never {}
+
+    impl AssetDecoder for Tagged {
+        type Output = (&'static str, usize);
+        type Error = Never;
+
+        fn decode(&self, _: &DecodeContext<'_>, bytes: &[u8]) -> Result<Self::Output, Never> {
+            Ok((self.0, bytes.len()))
+        }
+
+        fn dependencies(&self, _: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+            core::str::from_utf8(bytes)
+                .unwrap()
+                .split_whitespace()
+                .map(ToString::to_string)
+                .collect()
+        }
+    }
+
+    fn decoded_by(registry: &DecoderRegistry, name: &str, kind: AssetKind) -> Option<&'static str> {
+        let mut asset = Asset::new(kind, Arc::<[u8]>::from(&b"a b"[..]));
+        match registry.decode(name, &mut asset).unwrap() {
+            true => Some(asset.downcast::<(&'static str, usize)>().unwrap().0),
+            false => None,
+        }
+    }
+
+    #[test]
+    fn dispatch_by_extension_then_kind() {
+        let mut registry = DecoderRegistry::new();
+        registry.register(AssetKind::Mesh, &["obj"], Tagged("mesh"));
+        registry.register(AssetKind::JsonBody, &["json", "JSN"], Tagged("json"));
+
+        assert_eq!(registry.kind_of("models/ship.OBJ"), AssetKind::Mesh);
+        assert_eq!(registry.kind_of("notes.txt"), AssetKind::RawData);
+        assert_eq!(registry.kind_of(".json"), AssetKind::RawData);
+        assert_eq!(
+            decoded_by(&registry, "a/b.jsn", AssetKind::RawData),
+            Some("json")
+        );
+        // the extension wins over the kind the asset was tagged with
+        assert_eq!(
+            decoded_by(&registry, "ship.obj", AssetKind::JsonBody),
+            Some("mesh")
+        );
+        assert_eq!(decoded_by(&registry, "ship", AssetKind::Mesh), Some("mesh"));
+        assert_eq!(decoded_by(&registry, "notes.txt", AssetKind::RawData), None);
+        assert_eq!(
+            registry.dependencies("level.json", AssetKind::RawData, b"x.obj y.obj"),
+            ["x.obj", "y.obj"]
+        );
+
+        registry.register(AssetKind::Other, &["obj"], Tagged("newer"));
+        assert_eq!(
+            decoded_by(&registry, "ship.obj", AssetKind::Mesh),
+            Some("newer")
+        );
+    }
+
+    #[test]
+    fn relative_paths() {
+        let cx = DecodeContext::new("models/ships/ship.obj", AssetKind::Mesh);
+        assert_eq!(cx.relative("ship.mtl"), "models/ships/ship.mtl");
+        assert_eq!(cx.relative("../../../tex\\hull.png"), "tex/hull.png");
+        assert_eq!(cx.relative("/top.png"), "top.png");
+        assert_eq!(cx.fetch_relative("ship.mtl"), None);
+    }
+}
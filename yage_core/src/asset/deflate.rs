@@ -0,0 +1,627 @@
+//! DEFLATE (RFC 1951) and the zlib wrapper around it (RFC 1950)
+
+use super::checksum::adler32;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    UnexpectedEof,
+    InvalidBlockType,
+    InvalidStoredLength,
+    InvalidCodeLengths,
+    InvalidSymbol,
+    InvalidDistance,
+    /// the output grew past the limit the caller allowed
+    TooLarge,
+    InvalidZlibHeader,
+    ChecksumMismatch,
+}
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnexpectedEof => "compressed data ended early",
+            Self::InvalidBlockType => "invalid block type",
+            Self::InvalidStoredLength => "stored block length does not match its complement",
+            Self::InvalidCodeLengths => "invalid huffman code lengths",
+            Self::InvalidSymbol => "invalid huffman symbol",
+            Self::InvalidDistance => "back reference points before the start of the output",
+            Self::TooLarge => "decompressed data is larger than allowed",
+            Self::InvalidZlibHeader => "invalid zlib header",
+            Self::ChecksumMismatch => "checksum mismatch",
+        })
+    }
+}
+
+impl core::error::Error for InflateError {}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// the order code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buf: 0,
+            count: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let Some(&byte) = self.data.get(self.pos) else {
+                break;
+            };
+            self.buf |= (byte as u64) << self.count;
+            self.count += 8;
+            self.pos += 1;
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        if self.count < n {
+            self.refill();
+            if self.count < n {
+                return Err(InflateError::UnexpectedEof);
+            }
+        }
+        let value = (self.buf & ((1u64 << n) - 1)) as u32;
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// throws away the rest of the current byte and hands back everything after it
+    fn align(&mut self) -> &'a [u8] {
+        let buffered = (self.count / 8) as usize;
+        self.pos -= buffered;
+        self.buf = 0;
+        self.count = 0;
+        &self.data[self.pos..]
+    }
+}
+
+/// canonical huffman decoding through one flat table indexed by the next `max_len` bits
+struct Huffman {
+    // symbol << 4 | code length, 0 for bit patterns no code starts with
+    table: Vec<u16>,
+    max_len: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // over-subscribed sets can't be decoded. incomplete ones can, and show up in practice
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(InflateError::InvalidCodeLengths);
+            }
+        }
+
+        let mut next = [0u32; 16];
+        let mut code = 0;
+        for len in 1..16 {
+            code = (code + counts[len - 1] as u32) << 1;
+            next[len] = code;
+        }
+
+        let max_len = lengths.iter().copied().max().unwrap_or(0) as u32;
+        let mut table = vec![0u16; 1 << max_len];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let len = len as u32;
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            // codes are packed starting from their most significant bit
+            let reversed = code.reverse_bits() >> (32 - len);
+            let entry = ((symbol as u16) << 4) | len as u16;
+            for slot in (reversed as usize..table.len()).step_by(1 << len) {
+                table[slot] = entry;
+            }
+        }
+        Ok(Self { table, max_len })
+    }
+
+    fn decode(&self, reader: &mut BitReader<'_>) -> Result<u16, InflateError> {
+        if reader.count < self.max_len {
+            reader.refill();
+        }
+        let bits = (reader.buf & ((1u64 << self.max_len) - 1)) as usize;
+        let entry = *self.table.get(bits).ok_or(InflateError::InvalidSymbol)?;
+        let len = (entry & 0xf) as u32;
+        if entry == 0 {
+            return Err(if reader.count < self.max_len {
+                InflateError::UnexpectedEof
+            } else {
+                InflateError::InvalidSymbol
+            });
+        }
+        if len > reader.count {
+            return Err(InflateError::UnexpectedEof);
+        }
+        reader.buf >>= len;
+        reader.count -= len;
+        Ok(entry >> 4)
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let literal = Huffman::new(&lengths).expect("the fixed code is valid");
+    let distance = Huffman::new(&[5; 30]).expect("the fixed code is valid");
+    (literal, distance)
+}
+
+fn dynamic_tables(reader: &mut BitReader<'_>) -> Result<(Huffman, Huffman), InflateError> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(InflateError::InvalidCodeLengths);
+    }
+
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths)?;
+
+    let mut lengths = [0u8; 286 + 30];
+    let total = literals + distances;
+    let mut i = 0;
+    while i < total {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i]
+                    .last()
+                    .ok_or(InflateError::InvalidCodeLengths)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(InflateError::InvalidCodeLengths),
+        };
+        if i + repeat > total {
+            return Err(InflateError::InvalidCodeLengths);
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    // without an end-of-block code the block could never finish
+    if lengths[256] == 0 {
+        return Err(InflateError::InvalidCodeLengths);
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..total])?,
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader<'_>,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = literal.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                if out.len() >= limit {
+                    return Err(InflateError::TooLarge);
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let len =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distance.decode(reader)? as usize;
+                if index >= 30 {
+                    return Err(InflateError::InvalidSymbol);
+                }
+                let dist =
+                    DIST_BASE[index] as usize + reader.bits(DIST_EXTRA[index] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(InflateError::InvalidDistance);
+                }
+                if out.len() + len > limit {
+                    return Err(InflateError::TooLarge);
+                }
+                let start = out.len() - dist;
+                if dist >= len {
+                    out.extend_from_within(start..start + len);
+                } else {
+                    // the copy overlaps what it is producing, so it has to go a byte at a time
+                    for i in 0..len {
+                        out.push(out[start + i]);
+                    }
+                }
+            }
+            _ => return Err(InflateError::InvalidSymbol),
+        }
+    }
+}
+
+fn inflate_raw<'a>(
+    mut reader: BitReader<'a>,
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> Result<&'a [u8], InflateError> {
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                let rest = reader.align();
+                let [len_lo, len_hi, nlen_lo, nlen_hi, ..] = *rest else {
+                    return Err(InflateError::UnexpectedEof);
+                };
+                let len = u16::from_le_bytes([len_lo, len_hi]);
+                if len != !u16::from_le_bytes([nlen_lo, nlen_hi]) {
+                    return Err(InflateError::InvalidStoredLength);
+                }
+                let bytes = rest
+                    .get(4..4 + len as usize)
+                    .ok_or(InflateError::UnexpectedEof)?;
+                if out.len() + bytes.len() > limit {
+                    return Err(InflateError::TooLarge);
+                }
+                out.extend_from_slice(bytes);
+                reader.pos += 4 + len as usize;
+            }
+            1 => {
+                let (literal, distance) = fixed_tables();
+                inflate_block(&mut reader, out, limit, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, out, limit, &literal, &distance)?;
+            }
+            _ => return Err(InflateError::InvalidBlockType),
+        }
+        if last {
+            return Ok(reader.align());
+        }
+    }
+}
+
+/// decompresses a raw DEFLATE stream, failing once the output would grow past `limit` bytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    let mut out = Vec::new();
+    inflate_raw(BitReader::new(data), &mut out, limit)?;
+    Ok(out)
+}
+
+/// decompresses a zlib stream and checks its adler-32. preset dictionaries are not supported
+pub fn inflate_zlib(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    let [cmf, flg, ref body @ ..] = *data else {
+        return Err(InflateError::UnexpectedEof);
+    };
+    if cmf & 0x0f != 8
+        || cmf >> 4 > 7
+        || u16::from_be_bytes([cmf, flg]) % 31 != 0
+        || flg & 0x20 != 0
+    {
+        return Err(InflateError::InvalidZlibHeader);
+    }
+    let mut out = Vec::new();
+    let rest = inflate_raw(BitReader::new(body), &mut out, limit)?;
+    let checksum = rest.get(..4).ok_or(InflateError::UnexpectedEof)?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(InflateError::ChecksumMismatch);
+    }
+    Ok(out)
+}
+
+/// the longest match DEFLATE can express, the shortest worth taking, and how far back one can
+/// point
+const MAX_MATCH: usize = 258;
+const MIN_MATCH: usize = 3;
+const WINDOW: usize = 32768;
+const HASH_BITS: u32 = 15;
+/// how many earlier positions with the same hash a match search looks at
+const MAX_CHAIN: usize = 64;
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            out: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// least significant bit first, like everything but huffman codes
+    fn write(&mut self, value: u32, len: u8) {
+        self.bits |= (value as u64) << self.count;
+        self.count += len as u32;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// huffman codes go most significant bit first
+    fn write_code(&mut self, code: u32, len: u8) {
+        self.write(code.reverse_bits() >> (32 - len as u32), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+/// a literal/length symbol in the fixed huffman code
+fn write_fixed(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, len: usize, distance: usize) {
+    let code = LENGTH_BASE.partition_point(|&base| base as usize <= len) - 1;
+    write_fixed(writer, 257 + code as u16);
+    writer.write(
+        (len - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code],
+    );
+    let code = DIST_BASE.partition_point(|&base| base as usize <= distance) - 1;
+    writer.write_code(code as u32, 5);
+    writer.write(
+        (distance - DIST_BASE[code] as usize) as u32,
+        DIST_EXTRA[code],
+    );
+}
+
+fn hash3(data: &[u8], at: usize) -> usize {
+    let bytes = (data[at] as u32) << 16 | (data[at + 1] as u32) << 8 | data[at + 2] as u32;
+    (bytes.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// earlier positions by the hash of the three bytes there, each linked to the one before it
+struct Chains {
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+impl Chains {
+    const EMPTY: u32 = u32::MAX;
+
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let hash = hash3(data, at);
+            self.prev[at % WINDOW] = self.head[hash];
+            self.head[hash] = at as u32;
+        }
+    }
+
+    /// the longest earlier match for what starts at `at`, as (length, distance)
+    fn longest(&self, data: &[u8], at: usize) -> (usize, usize) {
+        if at + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max = (data.len() - at).min(MAX_MATCH);
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash3(data, at)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == Self::EMPTY || at - candidate as usize > WINDOW {
+                break;
+            }
+            let from = candidate as usize;
+            let len = data[from..from + max]
+                .iter()
+                .zip(&data[at..at + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best.0 {
+                best = (len, at - from);
+                if len == max {
+                    break;
+                }
+            }
+            // positions older than a window have been written over by newer ones
+            candidate = self.prev[from % WINDOW];
+        }
+        best
+    }
+}
+
+/// `data` as stored blocks, for when compressing it doesn't pay
+fn stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 5 * (data.len() / 65535 + 1));
+    let mut chunks = data.chunks(65535).peekable();
+    if chunks.peek().is_none() {
+        return alloc::vec![1, 0, 0, 0xff, 0xff];
+    }
+    while let Some(chunk) = chunks.next() {
+        out.push(chunks.peek().is_none() as u8);
+        out.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// compresses `data` into a raw DEFLATE stream. greedy LZ77 matching and only the fixed huffman
+/// codes, so it is a bit bigger than what zlib would make, with stored blocks when even that
+/// doesn't come out smaller
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // one final block, fixed codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut chains = Chains {
+        head: vec![Chains::EMPTY; 1 << HASH_BITS],
+        prev: vec![Chains::EMPTY; WINDOW],
+    };
+    let mut at = 0;
+    while at < data.len() {
+        let (len, distance) = chains.longest(data, at);
+        if len >= MIN_MATCH {
+            write_match(&mut writer, len, distance);
+            for i in at..at + len {
+                chains.insert(data, i);
+            }
+            at += len;
+        } else {
+            write_fixed(&mut writer, data[at] as u16);
+            chains.insert(data, at);
+            at += 1;
+        }
+    }
+    write_fixed(&mut writer, 256);
+    let fixed = writer.finish();
+    if fixed.len() <= data.len() + 5 * (data.len() / 65535 + 1) {
+        fixed
+    } else {
+        stored(data)
+    }
+}
+
+/// `deflate` wrapped in a zlib header and adler-32
+pub fn deflate_zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = alloc::vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // zlib.compress(b"hello hello hello hello, yage!", level=9)
+    const FIXED: &[u8] = &[
+        120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 39, 117, 20, 42, 19, 211, 83, 21, 1, 172,
+        207, 10, 196,
+    ];
+
+    #[test]
+    fn inflates_dynamic_blocks() {
+        let expected = "cbebhhhgdbhaggahedbfaaa agdga dhh dfddheag bcebf g deeh gahdggcf fbh bc gfhahaegcc dad  dg ffhe ag c";
+        let compressed = [
+            120, 218, 13, 204, 65, 17, 0, 48, 12, 2, 65, 43, 88, 35, 129, 128, 127, 5, 237, 251,
+            118, 110, 199, 211, 54, 154, 50, 97, 173, 57, 146, 96, 20, 66, 45, 116, 82, 205, 96,
+            214, 115, 8, 100, 23, 159, 42, 217, 195, 77, 127, 65, 174, 44, 157, 93, 136, 2, 20,
+            220, 213, 255, 131, 125, 39, 85, 35, 13,
+        ];
+        assert_eq!(
+            inflate_zlib(&compressed, usize::MAX).unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn inflates_fixed_blocks() {
+        assert_eq!(
+            inflate_zlib(FIXED, usize::MAX).unwrap(),
+            b"hello hello hello hello, yage!"
+        );
+        assert_eq!(inflate_zlib(FIXED, 10), Err(InflateError::TooLarge));
+    }
+
+    #[test]
+    fn inflates_stored_blocks() {
+        // zlib.compress(b"abc", level=0)
+        let stored = [120, 1, 1, 3, 0, 252, 255, 97, 98, 99, 2, 77, 1, 39];
+        assert_eq!(inflate_zlib(&stored, usize::MAX).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let mut inputs: Vec<Vec<u8>> = vec![Vec::new(), b"abc".to_vec()];
+        inputs.push(b"hello hello hello hello, yage!".repeat(50));
+        inputs.push((0..200_000u32).map(|i| (i * 7 % 256) as u8).collect());
+        // incompressible, goes out as stored blocks
+        let mut state = 1u32;
+        inputs.push(
+            (0..70_000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect(),
+        );
+        for input in &inputs {
+            let compressed = deflate_zlib(input);
+            assert_eq!(&inflate_zlib(&compressed, usize::MAX).unwrap(), input);
+        }
+        assert!(deflate_zlib(&inputs[2]).len() < 100);
+        assert!(deflate_zlib(&inputs[4]).len() < 70_100);
+    }
+
+    #[test]
+    fn rejects_corrupt_streams() {
+        let mut corrupt = FIXED.to_vec();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert_eq!(
+            inflate_zlib(&corrupt, usize::MAX),
+            Err(InflateError::ChecksumMismatch)
+        );
+        assert_eq!(
+            inflate_zlib(&FIXED[..10], usize::MAX),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+}
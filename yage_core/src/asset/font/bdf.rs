@@ -0,0 +1,178 @@
+//! Glyph Bitmap Distribution Format, the text bitmap fonts X11 used
+
+use super::{unpack_rows, BitmapFont, BitmapGlyph, FontError, GlyphBitmap};
+use alloc::vec::Vec;
+
+#[derive(Default)]
+struct Char {
+    encoding: Option<u32>,
+    advance: Option<u32>,
+    /// width, height, and the offset of the bottom left corner from the pen
+    bbx: Option<[i32; 4]>,
+    rows: Vec<u8>,
+}
+
+pub(super) fn decode(bytes: &[u8]) -> Result<BitmapFont, FontError> {
+    let text = core::str::from_utf8(bytes).map_err(|_| FontError::Invalid("bdf isn't utf-8"))?;
+    let mut font = BitmapFont::default();
+    let mut bounds = None;
+    let (mut ascent, mut descent) = (None, None);
+    let mut current: Option<Char> = None;
+    let mut in_bitmap = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message| FontError::Bdf {
+            line: index + 1,
+            message,
+        };
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let mut numbers = || -> Result<[i32; 4], FontError> {
+            let mut out = [0; 4];
+            for slot in &mut out {
+                *slot = match words.next() {
+                    Some(word) => word.parse().map_err(|_| error("expected a number"))?,
+                    None => break,
+                };
+            }
+            Ok(out)
+        };
+
+        if in_bitmap {
+            let Some(glyph) = current.as_mut() else {
+                return Err(error("`BITMAP` outside of a char"));
+            };
+            if keyword == "ENDCHAR" {
+                in_bitmap = false;
+            } else {
+                let hex = |pair: &[u8]| {
+                    let pair = core::str::from_utf8(pair).ok()?;
+                    u8::from_str_radix(pair, 16).ok()
+                };
+                let mut row = Vec::new();
+                for pair in keyword.as_bytes().chunks(2) {
+                    row.push(hex(pair).ok_or_else(|| error("bad bitmap row"))?);
+                }
+                // some fonts pad rows further than the width needs
+                let width = glyph
+                    .bbx
+                    .or(bounds)
+                    .map_or(0, |[width, ..]| width.max(0) as u32);
+                row.resize(width.div_ceil(8) as usize, 0);
+                glyph.rows.extend(row);
+                continue;
+            }
+        }
+
+        match keyword {
+            "FONTBOUNDINGBOX" => bounds = Some(numbers()?),
+            "FONT_ASCENT" => ascent = Some(numbers()?[0]),
+            "FONT_DESCENT" => descent = Some(numbers()?[0]),
+            "STARTCHAR" => current = Some(Char::default()),
+            "ENCODING" => {
+                let glyph = current
+                    .as_mut()
+                    .ok_or_else(|| error("`ENCODING` outside of a char"))?;
+                // -1 means the glyph has no standard encoding, which we can't look up anyway
+                glyph.encoding = u32::try_from(numbers()?[0]).ok();
+            }
+            "DWIDTH" => {
+                let glyph = current
+                    .as_mut()
+                    .ok_or_else(|| error("`DWIDTH` outside of a char"))?;
+                glyph.advance = u32::try_from(numbers()?[0]).ok();
+            }
+            "BBX" => {
+                let glyph = current
+                    .as_mut()
+                    .ok_or_else(|| error("`BBX` outside of a char"))?;
+                glyph.bbx = Some(numbers()?);
+            }
+            "BITMAP" => {
+                // the rows come next, but the row stride depends on the width
+                in_bitmap = true;
+            }
+            "ENDCHAR" => {
+                let glyph = current
+                    .take()
+                    .ok_or_else(|| error("`ENDCHAR` outside of a char"))?;
+                let [width, height, x, y] = glyph
+                    .bbx
+                    .or(bounds)
+                    .ok_or_else(|| error("char without `BBX` or `FONTBOUNDINGBOX`"))?;
+                let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+                let Some(c) = glyph.encoding.and_then(char::from_u32) else {
+                    continue;
+                };
+                let bitmap = GlyphBitmap {
+                    width,
+                    height,
+                    left: x,
+                    top: -(y + height as i32),
+                    coverage: unpack_rows(&glyph.rows, width, height),
+                };
+                if bitmap.coverage.len() != (width * height) as usize {
+                    return Err(error("fewer bitmap rows than `BBX` says"));
+                }
+                let advance = glyph.advance.unwrap_or(width);
+                font.push([c], BitmapGlyph { advance, bitmap });
+            }
+            "ENDFONT" => break,
+            _ => {}
+        }
+    }
+
+    let [_, height, _, y] = bounds.unwrap_or_default();
+    font.ascent = ascent.unwrap_or(height + y).max(0) as u32;
+    font.descent = descent.unwrap_or(-y).max(0) as u32;
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn reads_glyphs() {
+        let bdf = "STARTFONT 2.1
+FONT -misc-tiny
+SIZE 4 75 75
+FONTBOUNDINGBOX 3 4 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 3
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR A
+ENCODING 65
+DWIDTH 4 0
+BBX 3 3 0 0
+BITMAP
+40
+A0
+E0
+ENDCHAR
+STARTCHAR unnamed
+ENCODING -1
+BBX 1 1 0 0
+BITMAP
+80
+ENDCHAR
+ENDFONT
+";
+        let font = decode(bdf.as_bytes()).unwrap();
+        assert_eq!((font.ascent(), font.line_height()), (3, 4));
+        assert_eq!(font.chars().count(), 1);
+        let a = font.glyph('A').unwrap();
+        assert_eq!(a.advance, 4);
+        assert_eq!((a.bitmap.left, a.bitmap.top), (0, -3));
+        assert_eq!(a.bitmap.coverage, [0, 255, 0, 255, 0, 255, 255, 255, 255]);
+
+        let broken = bdf.replace("E0", "ZZ");
+        let error = decode(broken.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "line 17: bad bitmap row");
+    }
+}
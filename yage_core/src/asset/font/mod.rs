@@ -0,0 +1,171 @@
+//! fonts for `AssetKind::Font`: BDF and PSF bitmap fonts, and TrueType outline fonts. drawing
+//! them is up to `renderer::text`
+
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+mod bdf;
+mod psf;
+pub mod truetype;
+
+pub use truetype::TrueType;
+
+#[derive(Debug, Clone)]
+pub enum Font {
+    Bitmap(BitmapFont),
+    TrueType(TrueType),
+}
+
+impl Font {
+    /// decodes any supported format, going by the first bytes
+    pub fn decode(bytes: &[u8]) -> Result<Self, FontError> {
+        match FontFormat::sniff(bytes).ok_or(FontError::UnknownFormat)? {
+            FontFormat::Bdf => bdf::decode(bytes).map(Self::Bitmap),
+            FontFormat::Psf => psf::decode(bytes).map(Self::Bitmap),
+            FontFormat::TrueType => TrueType::parse(bytes.into()).map(Self::TrueType),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontFormat {
+    Bdf,
+    /// PSF1 and PSF2 console fonts
+    Psf,
+    TrueType,
+}
+
+impl FontFormat {
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'S', b'T', b'A', b'R', b'T', b'F', b'O', b'N', b'T', ..] => Some(Self::Bdf),
+            [0x36, 0x04, ..] | [0x72, 0xb5, 0x4a, 0x86, ..] => Some(Self::Psf),
+            [0, 1, 0, 0, ..] | [b't', b'r', b'u', b'e', ..] => Some(Self::TrueType),
+            _ => None,
+        }
+    }
+}
+
+/// an 8-bit coverage mask of one glyph, placed relative to the pen on the baseline
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// from the pen to the left column
+    pub left: i32,
+    /// from the baseline down to the top row, so usually negative
+    pub top: i32,
+    /// `width * height` bytes row by row, 0 is empty and 255 fully covered
+    pub coverage: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitmapGlyph {
+    /// how far the pen moves after this glyph
+    pub advance: u32,
+    pub bitmap: GlyphBitmap,
+}
+
+/// glyphs of one fixed size, all fully on or off
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitmapFont {
+    glyphs: Vec<BitmapGlyph>,
+    chars: BTreeMap<char, usize>,
+    ascent: u32,
+    descent: u32,
+}
+
+impl BitmapFont {
+    pub fn glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.chars.get(&c).map(|&index| &self.glyphs[index])
+    }
+
+    /// from the baseline up to the top of the font
+    pub fn ascent(&self) -> u32 {
+        self.ascent
+    }
+
+    /// from the baseline down to the bottom of the font
+    pub fn descent(&self) -> u32 {
+        self.descent
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.ascent + self.descent
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chars.keys().copied()
+    }
+
+    fn push(&mut self, chars: impl IntoIterator<Item = char>, glyph: BitmapGlyph) {
+        let index = self.glyphs.len();
+        self.glyphs.push(glyph);
+        for c in chars {
+            self.chars.entry(c).or_insert(index);
+        }
+    }
+}
+
+/// turns rows of `(width + 7) / 8` bytes, leftmost pixel in the top bit, into coverage
+fn unpack_rows(rows: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let stride = width.div_ceil(8) as usize;
+    let mut coverage = Vec::with_capacity(width as usize * height as usize);
+    for row in rows.chunks(stride.max(1)).take(height as usize) {
+        for x in 0..width as usize {
+            let on = row
+                .get(x / 8)
+                .is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0);
+            coverage.push(if on { 255 } else { 0 });
+        }
+    }
+    coverage
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    UnknownFormat,
+    UnexpectedEof,
+    Invalid(&'static str),
+    Unsupported(&'static str),
+    /// `line` is 1-based
+    Bdf {
+        line: usize,
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => f.write_str("unknown font format"),
+            Self::UnexpectedEof => f.write_str("font data ended early"),
+            Self::Invalid(what) => write!(f, "invalid font: {what}"),
+            Self::Unsupported(what) => write!(f, "unsupported font: {what}"),
+            Self::Bdf { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl core::error::Error for FontError {}
+
+pub struct FontDecoder;
+
+impl AssetDecoder for FontDecoder {
+    type Output = Font;
+    type Error = FontError;
+
+    fn decode(&self, _: &DecodeContext<'_>, bytes: &[u8]) -> Result<Font, FontError> {
+        Font::decode(bytes)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.bdf`, `.psf` and `.ttf` files into a `Font`
+    pub fn with_fonts(mut self) -> Self {
+        self.register(AssetKind::Font, &["bdf", "psf", "ttf"], FontDecoder);
+        self
+    }
+}
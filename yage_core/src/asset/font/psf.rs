@@ -0,0 +1,140 @@
+//! PC Screen Font, the linux console's bitmap fonts, versions 1 and 2
+
+use super::{unpack_rows, BitmapFont, BitmapGlyph, FontError, GlyphBitmap};
+use alloc::vec::Vec;
+
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HASTAB: u8 = 0x02;
+const PSF1_MODE_SEQ: u8 = 0x04;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+
+pub(super) fn decode(bytes: &[u8]) -> Result<BitmapFont, FontError> {
+    match bytes {
+        [0x36, 0x04, mode, height, rest @ ..] => psf1(*mode, *height as u32, rest),
+        _ => psf2(bytes),
+    }
+}
+
+fn psf1(mode: u8, height: u32, rest: &[u8]) -> Result<BitmapFont, FontError> {
+    let count = match mode & PSF1_MODE_512 {
+        0 => 256,
+        _ => 512,
+    };
+    let size = height as usize;
+    let glyphs = rest.get(..count * size).ok_or(FontError::UnexpectedEof)?;
+    let mut table = rest[count * size..]
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+
+    let mut font = new_font(height);
+    for (index, rows) in glyphs.chunks_exact(size.max(1)).enumerate() {
+        let mut chars = Vec::new();
+        if mode & (PSF1_MODE_HASTAB | PSF1_MODE_SEQ) != 0 {
+            // single code points until 0xfffe starts the sequences or 0xffff ends the glyph
+            let mut in_sequences = false;
+            for unit in table.by_ref() {
+                match unit {
+                    0xffff => break,
+                    0xfffe => in_sequences = true,
+                    _ if in_sequences => {}
+                    _ => chars.extend(char::from_u32(unit as u32)),
+                }
+            }
+        } else {
+            chars.extend(char::from_u32(index as u32));
+        }
+        font.push(chars, glyph(rows, 8, height));
+    }
+    Ok(font)
+}
+
+fn psf2(bytes: &[u8]) -> Result<BitmapFont, FontError> {
+    let word = |index: usize| {
+        let at = index * 4;
+        let word = bytes.get(at..at + 4).ok_or(FontError::UnexpectedEof)?;
+        Ok(u32::from_le_bytes(word.try_into().unwrap()))
+    };
+    let (header_size, flags, count) = (word(2)? as usize, word(3)?, word(4)? as usize);
+    let (size, height, width) = (word(5)? as usize, word(6)?, word(7)?);
+    if size != width.div_ceil(8) as usize * height as usize {
+        return Err(FontError::Invalid(
+            "psf2 glyph size doesn't match its width and height",
+        ));
+    }
+    let end = count
+        .checked_mul(size)
+        .and_then(|len| len.checked_add(header_size))
+        .ok_or(FontError::Invalid("psf2 glyph count is too large"))?;
+    let glyphs = bytes
+        .get(header_size..end)
+        .ok_or(FontError::UnexpectedEof)?;
+    // utf-8 strings, 0xfe starting the sequences and 0xff ending each glyph's entry
+    let mut table = bytes[end..].split(|&byte| byte == 0xff);
+
+    let mut font = new_font(height);
+    for (index, rows) in glyphs.chunks_exact(size.max(1)).enumerate() {
+        let mut chars = Vec::new();
+        if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+            let entry = table.next().unwrap_or_default();
+            let singles = entry.split(|&byte| byte == 0xfe).next().unwrap_or_default();
+            let singles = core::str::from_utf8(singles)
+                .map_err(|_| FontError::Invalid("psf2 unicode table isn't utf-8"))?;
+            chars.extend(singles.chars());
+        } else {
+            chars.extend(char::from_u32(index as u32));
+        }
+        font.push(chars, glyph(rows, width, height));
+    }
+    Ok(font)
+}
+
+/// console fonts have no baseline, so the whole cell sits on it
+fn new_font(height: u32) -> BitmapFont {
+    BitmapFont {
+        ascent: height,
+        descent: 0,
+        ..BitmapFont::default()
+    }
+}
+
+fn glyph(rows: &[u8], width: u32, height: u32) -> BitmapGlyph {
+    BitmapGlyph {
+        advance: width,
+        bitmap: GlyphBitmap {
+            width,
+            height,
+            left: 0,
+            top: -(height as i32),
+            coverage: unpack_rows(rows, width, height),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn both_versions() {
+        // two 2x2 glyphs, the first one for 'a' and 'b', the second for 'c'
+        let mut psf2 = Vec::new();
+        for word in [0x864ab572u32, 0, 32, 1, 2, 2, 2, 2] {
+            psf2.extend(word.to_le_bytes());
+        }
+        psf2.extend([0x80, 0x40, 0xc0, 0xc0]);
+        psf2.extend(b"ab\xfeab\xffc\xff");
+        let font = decode(&psf2).unwrap();
+        assert_eq!(font.glyph('b').unwrap().bitmap.coverage, [255, 0, 0, 255]);
+        assert_eq!(font.glyph('c').unwrap().advance, 2);
+        assert!(font.glyph('d').is_none());
+
+        let mut psf1 = vec![0x36, 0x04, 0, 1];
+        psf1.extend((0..=255).map(|index| index as u8));
+        let font = decode(&psf1).unwrap();
+        assert_eq!(font.line_height(), 1);
+        let a = &font.glyph('A').unwrap().bitmap;
+        assert_eq!((a.width, a.top), (8, -1));
+        assert_eq!(a.coverage, [0, 255, 0, 0, 0, 0, 0, 255]);
+    }
+}
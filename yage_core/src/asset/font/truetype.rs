@@ -0,0 +1,642 @@
+//! TrueType outline fonts: just enough of `cmap`, `glyf` and the metrics tables to turn
+//! characters into anti-aliased glyph bitmaps at any size. no hinting, no kerning
+
+use super::{FontError, GlyphBitmap};
+use crate::renderer::draw::{coverage_mask, FillRule, Point};
+use crate::renderer::math::{ceil, floor, sqrt};
+use alloc::vec::Vec;
+
+/// composite glyphs made of composite glyphs made of ... stop here
+const MAX_COMPONENT_DEPTH: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct TrueType {
+    data: Vec<u8>,
+    glyph_count: u16,
+    units_per_em: u16,
+    /// `loca` holds 32 bit offsets instead of halved 16 bit ones
+    long_offsets: bool,
+    ascender: i16,
+    descender: i16,
+    line_gap: i16,
+    long_metrics: u16,
+    cmap: Cmap,
+    loca: usize,
+    glyf: usize,
+    hmtx: usize,
+}
+
+/// where the best character map subtable is
+#[derive(Debug, Clone, Copy)]
+enum Cmap {
+    /// segments of 16 bit code points
+    Format4(usize),
+    /// groups of full unicode code points
+    Format12(usize),
+}
+
+/// a point of an outline in font units, `y` up. consecutive off-curve points have an
+/// on-curve one halfway between them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlinePoint {
+    pub x: f32,
+    pub y: f32,
+    pub on_curve: bool,
+}
+
+impl TrueType {
+    pub fn parse(data: Vec<u8>) -> Result<Self, FontError> {
+        let table = |tag: &[u8; 4]| -> Result<usize, FontError> {
+            let count = read_u16(&data, 4)? as usize;
+            for record in (0..count).map(|i| 12 + 16 * i) {
+                if data.get(record..record + 4) == Some(tag) {
+                    let offset = read_u32(&data, record + 8)? as usize;
+                    let length = read_u32(&data, record + 12)? as usize;
+                    if offset
+                        .checked_add(length)
+                        .is_none_or(|end| end > data.len())
+                    {
+                        return Err(FontError::UnexpectedEof);
+                    }
+                    return Ok(offset);
+                }
+            }
+            Err(FontError::Invalid("a required table is missing"))
+        };
+        let head = table(b"head")?;
+        let maxp = table(b"maxp")?;
+        let hhea = table(b"hhea")?;
+        let cmap = table(b"cmap")?;
+        let font = Self {
+            glyph_count: read_u16(&data, maxp + 4)?,
+            units_per_em: read_u16(&data, head + 18)?,
+            long_offsets: read_u16(&data, head + 50)? != 0,
+            ascender: read_u16(&data, hhea + 4)? as i16,
+            descender: read_u16(&data, hhea + 6)? as i16,
+            line_gap: read_u16(&data, hhea + 8)? as i16,
+            long_metrics: read_u16(&data, hhea + 34)?,
+            cmap: find_cmap(&data, cmap)?,
+            loca: table(b"loca")?,
+            glyf: table(b"glyf")?,
+            hmtx: table(b"hmtx")?,
+            data,
+        };
+        if font.units_per_em == 0 || font.long_metrics == 0 {
+            return Err(FontError::Invalid("bad font header"));
+        }
+        Ok(font)
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    /// from the baseline up, in font units
+    pub fn ascender(&self) -> i16 {
+        self.ascender
+    }
+
+    /// from the baseline up, so usually negative, in font units
+    pub fn descender(&self) -> i16 {
+        self.descender
+    }
+
+    pub fn line_gap(&self) -> i16 {
+        self.line_gap
+    }
+
+    /// how many pixels one font unit is at a size of `px` pixels per em
+    pub fn scale(&self, px: f32) -> f32 {
+        px / self.units_per_em as f32
+    }
+
+    /// the glyph for `c`, 0 being the font's "missing" glyph
+    pub fn glyph_index(&self, c: char) -> u16 {
+        let c = c as u32;
+        let data = &self.data;
+        let found = match self.cmap {
+            Cmap::Format4(at) => format4(data, at, c),
+            Cmap::Format12(at) => format12(data, at, c),
+        };
+        found.ok().flatten().unwrap_or(0)
+    }
+
+    /// in font units
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let metric = glyph.min(self.long_metrics - 1) as usize;
+        read_u16(&self.data, self.hmtx + 4 * metric).unwrap_or(0)
+    }
+
+    /// the contours of `glyph`, with composite glyphs put together
+    pub fn outline(&self, glyph: u16) -> Result<Vec<Vec<OutlinePoint>>, FontError> {
+        let mut contours = Vec::new();
+        self.add_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut contours)?;
+        Ok(contours)
+    }
+
+    /// `glyph` at `px` pixels per em, with the outline's origin on the pen
+    pub fn rasterize(&self, glyph: u16, px: f32) -> Result<GlyphBitmap, FontError> {
+        let scale = self.scale(px);
+        let contours: Vec<Vec<Point>> = self
+            .outline(glyph)?
+            .iter()
+            .map(|contour| flatten(contour, scale))
+            .filter(|contour| contour.len() > 2)
+            .collect();
+        let points = contours.iter().flatten();
+        let (mut min, mut max) = (
+            Point::new(f32::MAX, f32::MAX),
+            Point::new(f32::MIN, f32::MIN),
+        );
+        for point in points {
+            min = Point::new(min.x.min(point.x), min.y.min(point.y));
+            max = Point::new(max.x.max(point.x), max.y.max(point.y));
+        }
+        if contours.is_empty() || min.x >= max.x || min.y >= max.y {
+            return Ok(GlyphBitmap::default());
+        }
+
+        let (left, top) = (floor(min.x), floor(min.y));
+        let width = (ceil(max.x) - left) as u32;
+        let height = (ceil(max.y) - top) as u32;
+        let shifted: Vec<Vec<Point>> = contours
+            .into_iter()
+            .map(|contour| {
+                let shift = |p: Point| Point::new(p.x - left, p.y - top);
+                contour.into_iter().map(shift).collect()
+            })
+            .collect();
+        let shifted: Vec<&[Point]> = shifted.iter().map(Vec::as_slice).collect();
+        Ok(GlyphBitmap {
+            width,
+            height,
+            left: left as i32,
+            top: top as i32,
+            coverage: coverage_mask(width, height, &shifted, FillRule::NonZero),
+        })
+    }
+
+    fn glyph_data(&self, glyph: u16) -> Result<&[u8], FontError> {
+        if glyph >= self.glyph_count {
+            return Err(FontError::Invalid("glyph index out of range"));
+        }
+        let index = glyph as usize;
+        let (start, end) = match self.long_offsets {
+            true => (
+                read_u32(&self.data, self.loca + 4 * index)? as usize,
+                read_u32(&self.data, self.loca + 4 * index + 4)? as usize,
+            ),
+            false => (
+                read_u16(&self.data, self.loca + 2 * index)? as usize * 2,
+                read_u16(&self.data, self.loca + 2 * index + 2)? as usize * 2,
+            ),
+        };
+        let range = self.glyf + start..self.glyf + end.max(start);
+        self.data.get(range).ok_or(FontError::UnexpectedEof)
+    }
+
+    /// `transform` is `[xx, xy, yx, yy, dx, dy]`: `x' = xx * x + yx * y + dx` and so on
+    fn add_outline(
+        &self,
+        glyph: u16,
+        transform: [f32; 6],
+        depth: u32,
+        contours: &mut Vec<Vec<OutlinePoint>>,
+    ) -> Result<(), FontError> {
+        let data = self.glyph_data(glyph)?;
+        if data.is_empty() {
+            // nothing to draw, like a space
+            return Ok(());
+        }
+        let contour_count = read_u16(data, 0)? as i16;
+        if contour_count >= 0 {
+            for contour in simple_glyph(data, contour_count as usize)? {
+                contours.push(contour.into_iter().map(|p| apply(transform, p)).collect());
+            }
+            return Ok(());
+        }
+        if depth >= MAX_COMPONENT_DEPTH {
+            return Err(FontError::Invalid("composite glyphs nest too deep"));
+        }
+
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY: u16 = 0x0002;
+        const HAS_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAS_XY_SCALE: u16 = 0x0040;
+        const HAS_2X2: u16 = 0x0080;
+        let f2dot14 = |at: usize| Ok::<_, FontError>(read_u16(data, at)? as i16 as f32 / 16384.0);
+
+        let mut at = 10;
+        loop {
+            let flags = read_u16(data, at)?;
+            let component = read_u16(data, at + 2)?;
+            at += 4;
+            let (dx, dy) = match flags & ARGS_ARE_WORDS {
+                0 => {
+                    let args = data.get(at..at + 2).ok_or(FontError::UnexpectedEof)?;
+                    at += 2;
+                    (args[0] as i8 as f32, args[1] as i8 as f32)
+                }
+                _ => {
+                    let args = (read_u16(data, at)? as i16, read_u16(data, at + 2)? as i16);
+                    at += 4;
+                    (args.0 as f32, args.1 as f32)
+                }
+            };
+            // matching up points instead of offsetting is rare enough to leave out
+            let (dx, dy) = match flags & ARGS_ARE_XY {
+                0 => (0.0, 0.0),
+                _ => (dx, dy),
+            };
+            let [xx, xy, yx, yy] = if flags & HAS_SCALE != 0 {
+                at += 2;
+                let scale = f2dot14(at - 2)?;
+                [scale, 0.0, 0.0, scale]
+            } else if flags & HAS_XY_SCALE != 0 {
+                at += 4;
+                [f2dot14(at - 4)?, 0.0, 0.0, f2dot14(at - 2)?]
+            } else if flags & HAS_2X2 != 0 {
+                at += 8;
+                [
+                    f2dot14(at - 8)?,
+                    f2dot14(at - 6)?,
+                    f2dot14(at - 4)?,
+                    f2dot14(at - 2)?,
+                ]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            // the component's transform goes first, then ours
+            let [a, b, c, d, e, f] = transform;
+            let combined = [
+                a * xx + c * xy,
+                b * xx + d * xy,
+                a * yx + c * yy,
+                b * yx + d * yy,
+                a * dx + c * dy + e,
+                b * dx + d * dy + f,
+            ];
+            self.add_outline(component, combined, depth + 1, contours)?;
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn apply([xx, xy, yx, yy, dx, dy]: [f32; 6], point: OutlinePoint) -> OutlinePoint {
+    OutlinePoint {
+        x: xx * point.x + yx * point.y + dx,
+        y: xy * point.x + yy * point.y + dy,
+        on_curve: point.on_curve,
+    }
+}
+
+fn simple_glyph(data: &[u8], contour_count: usize) -> Result<Vec<Vec<OutlinePoint>>, FontError> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let mut ends = Vec::with_capacity(contour_count);
+    for i in 0..contour_count {
+        ends.push(read_u16(data, 10 + 2 * i)? as usize);
+    }
+    let point_count = match ends.last() {
+        Some(&last) => last + 1,
+        None => return Ok(Vec::new()),
+    };
+    let instructions = read_u16(data, 10 + 2 * contour_count)? as usize;
+    let mut at = 12 + 2 * contour_count + instructions;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = *data.get(at).ok_or(FontError::UnexpectedEof)?;
+        at += 1;
+        let repeats = match flag & REPEAT {
+            0 => 0,
+            _ => {
+                at += 1;
+                *data.get(at - 1).ok_or(FontError::UnexpectedEof)? as usize
+            }
+        };
+        flags.extend(core::iter::repeat_n(flag, repeats + 1));
+    }
+    flags.truncate(point_count);
+
+    let mut coordinates = |short: u8, same_or_positive: u8| {
+        let mut values = Vec::with_capacity(point_count);
+        let mut value = 0i32;
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = *data.get(at).ok_or(FontError::UnexpectedEof)? as i32;
+                at += 1;
+                value += match flag & same_or_positive {
+                    0 => -delta,
+                    _ => delta,
+                };
+            } else if flag & same_or_positive == 0 {
+                value += read_u16(data, at)? as i16 as i32;
+                at += 2;
+            }
+            values.push(value as f32);
+        }
+        Ok::<_, FontError>(values)
+    };
+    let xs = coordinates(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = coordinates(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut contours = Vec::with_capacity(contour_count);
+    let mut start = 0;
+    for end in ends {
+        if end < start || end >= point_count {
+            return Err(FontError::Invalid("glyph contours are out of order"));
+        }
+        let points = (start..=end).map(|i| OutlinePoint {
+            x: xs[i],
+            y: ys[i],
+            on_curve: flags[i] & ON_CURVE != 0,
+        });
+        contours.push(points.collect());
+        start = end + 1;
+    }
+    Ok(contours)
+}
+
+/// turns a contour of lines and quadratic curves into a polygon in pixels, `y` down
+fn flatten(contour: &[OutlinePoint], scale: f32) -> Vec<Point> {
+    let pixel = |p: &OutlinePoint| Point::new(p.x * scale, -p.y * scale);
+    let mid = |a: Point, b: Point| Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+    let (Some(first), Some(last)) = (contour.first(), contour.last()) else {
+        return Vec::new();
+    };
+    // start on the curve, making up a point if neither end is on it
+    let (start, rest) = match (first.on_curve, last.on_curve) {
+        (true, _) => (pixel(first), &contour[1..]),
+        (false, true) => (pixel(last), &contour[..contour.len() - 1]),
+        (false, false) => (mid(pixel(first), pixel(last)), contour),
+    };
+
+    let mut points = alloc::vec![start];
+    let mut control: Option<Point> = None;
+    let ends = rest.iter().map(|p| (pixel(p), p.on_curve));
+    for (point, on_curve) in ends.chain([(start, true)]) {
+        let from = *points.last().unwrap();
+        match (on_curve, control) {
+            (true, None) => points.push(point),
+            (true, Some(c)) => {
+                quadratic(&mut points, from, c, point);
+                control = None;
+            }
+            (false, None) => control = Some(point),
+            (false, Some(c)) => {
+                quadratic(&mut points, from, c, mid(c, point));
+                control = Some(point);
+            }
+        }
+    }
+    points.pop();
+    points
+}
+
+/// appends the curve from `from` (already in `points`) through `control` to `to`
+fn quadratic(points: &mut Vec<Point>, from: Point, control: Point, to: Point) {
+    // the curve strays at most a quarter of this from its chord
+    let dx = from.x - 2.0 * control.x + to.x;
+    let dy = from.y - 2.0 * control.y + to.y;
+    let steps = ceil(sqrt(sqrt(dx * dx + dy * dy) * 2.0)).clamp(1.0, 32.0) as u32;
+    for step in 1..=steps {
+        let t = step as f32 / steps as f32;
+        let u = 1.0 - t;
+        points.push(Point::new(
+            u * u * from.x + 2.0 * u * t * control.x + t * t * to.x,
+            u * u * from.y + 2.0 * u * t * control.y + t * t * to.y,
+        ));
+    }
+}
+
+fn find_cmap(data: &[u8], cmap: usize) -> Result<Cmap, FontError> {
+    let count = read_u16(data, cmap + 2)? as usize;
+    let mut best = None;
+    for record in (0..count).map(|i| cmap + 4 + 8 * i) {
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let at = cmap + read_u32(data, record + 4)? as usize;
+        let unicode = matches!((platform, encoding), (0, _) | (3, 1) | (3, 10));
+        if !unicode {
+            continue;
+        }
+        match read_u16(data, at)? {
+            12 => return Ok(Cmap::Format12(at)),
+            4 => best = Some(Cmap::Format4(at)),
+            _ => {}
+        }
+    }
+    best.ok_or(FontError::Unsupported("no unicode character map"))
+}
+
+fn format4(data: &[u8], at: usize, c: u32) -> Result<Option<u16>, FontError> {
+    let Ok(c) = u16::try_from(c) else {
+        return Ok(None);
+    };
+    let segments = read_u16(data, at + 6)? as usize / 2;
+    let ends = at + 14;
+    let starts = ends + 2 * segments + 2;
+    let deltas = starts + 2 * segments;
+    let range_offsets = deltas + 2 * segments;
+    for segment in 0..segments {
+        if read_u16(data, ends + 2 * segment)? < c {
+            continue;
+        }
+        let start = read_u16(data, starts + 2 * segment)?;
+        if start > c {
+            return Ok(None);
+        }
+        let delta = read_u16(data, deltas + 2 * segment)?;
+        let offset_at = range_offsets + 2 * segment;
+        let glyph = match read_u16(data, offset_at)? as usize {
+            0 => c,
+            // the offset is from where it is stored, into the glyph id array after it
+            offset => match read_u16(data, offset_at + offset + 2 * (c - start) as usize)? {
+                0 => return Ok(None),
+                glyph => glyph,
+            },
+        };
+        return Ok(Some(glyph.wrapping_add(delta)));
+    }
+    Ok(None)
+}
+
+fn format12(data: &[u8], at: usize, c: u32) -> Result<Option<u16>, FontError> {
+    let groups = read_u32(data, at + 12)? as usize;
+    for group in (0..groups).map(|i| at + 16 + 12 * i) {
+        let (start, end) = (read_u32(data, group)?, read_u32(data, group + 4)?);
+        if (start..=end).contains(&c) {
+            let glyph = read_u32(data, group + 8)? + (c - start);
+            return Ok(u16::try_from(glyph).ok());
+        }
+    }
+    Ok(None)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, FontError> {
+    let bytes = data.get(at..at + 2).ok_or(FontError::UnexpectedEof)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, FontError> {
+    let bytes = data.get(at..at + 4).ok_or(FontError::UnexpectedEof)?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// a font with a missing glyph, a square glyph for 'A' made of lines and a diamond for
+    /// 'B' made of off-curve points only, and 'C' as the square scaled down by half
+    fn tiny_font() -> Vec<u8> {
+        let be16 = |v: i32| (v as u16).to_be_bytes();
+        let simple = |points: &[(i32, i32, bool)]| {
+            let mut glyph = Vec::new();
+            for v in [1, 0, 0, 100, 100, points.len() as i32 - 1, 0] {
+                glyph.extend(be16(v));
+            }
+            glyph.extend(points.iter().map(|&(_, _, on)| on as u8));
+            let mut last = (0, 0);
+            for &(x, _, _) in points {
+                glyph.extend(be16(x - last.0));
+                last.0 = x;
+            }
+            for &(_, y, _) in points {
+                glyph.extend(be16(y - last.1));
+                last.1 = y;
+            }
+            glyph
+        };
+        let square = simple(&[
+            (0, 0, true),
+            (0, 100, true),
+            (100, 100, true),
+            (100, 0, true),
+        ]);
+        let diamond = simple(&[
+            (50, 0, false),
+            (0, 50, false),
+            (50, 100, false),
+            (100, 50, false),
+        ]);
+        let mut half = Vec::new();
+        // one component, word offsets of (10, 0), scaled by 0.5
+        for v in [-1, 0, 0, 50, 50, 0x0001 | 0x0002 | 0x0008, 1, 10, 0, 0x2000] {
+            half.extend(be16(v));
+        }
+        let glyphs = [Vec::new(), square, diamond, half];
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in &glyphs {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            glyf.extend(glyph);
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&be16(100));
+        head[50..52].copy_from_slice(&be16(1));
+        let mut maxp = vec![0; 6];
+        maxp[4..6].copy_from_slice(&be16(glyphs.len() as i32));
+        let mut hhea = vec![0; 36];
+        for (at, v) in [(4, 80), (6, -20), (8, 10), (34, 1)] {
+            hhea[at..at + 2].copy_from_slice(&be16(v));
+        }
+        let hmtx = [be16(120), be16(0)].concat();
+        // format 4 with a segment for 'A'..='C' and the final 0xffff one
+        let mut cmap = Vec::new();
+        for v in [0, 1, 3, 1, 0, 12] {
+            cmap.extend(be16(v));
+        }
+        for v in [
+            4,
+            32,
+            0,
+            4,
+            0,
+            0,
+            0,
+            0x43,
+            0xffff,
+            0,
+            0x41,
+            0xffff,
+            1 - 0x41,
+            1,
+            0,
+            0,
+        ] {
+            cmap.extend(be16(v));
+        }
+
+        let tables: [(&[u8; 4], &[u8]); 7] = [
+            (b"cmap", &cmap),
+            (b"glyf", &glyf),
+            (b"head", &head),
+            (b"hhea", &hhea),
+            (b"hmtx", &hmtx),
+            (b"loca", &loca),
+            (b"maxp", &maxp),
+        ];
+        let mut font = vec![0, 1, 0, 0, 0, tables.len() as u8, 0, 0, 0, 0, 0, 0];
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables {
+            font.extend(tag);
+            font.extend([0; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            font.extend(table);
+        }
+        font
+    }
+
+    #[test]
+    fn glyphs_and_outlines() {
+        let font = TrueType::parse(tiny_font()).unwrap();
+        assert_eq!((font.ascender(), font.descender()), (80, -20));
+        assert_eq!(font.glyph_index('A'), 1);
+        assert_eq!(font.glyph_index('C'), 3);
+        assert_eq!(font.glyph_index('Z'), 0);
+        assert_eq!(font.advance(2), 120);
+
+        let square = font.rasterize(1, 10.0).unwrap();
+        assert_eq!((square.width, square.height), (10, 10));
+        assert_eq!((square.left, square.top), (0, -10));
+        assert!(square.coverage.iter().all(|&c| c == 255));
+
+        let half = font.rasterize(3, 10.0).unwrap();
+        assert_eq!(
+            (half.left, half.top, half.width, half.height),
+            (1, -5, 5, 5)
+        );
+
+        // a circle-ish shape, the curves only reaching halfway to the off-curve corners
+        let diamond = font.rasterize(2, 20.0).unwrap();
+        assert_eq!((diamond.left, diamond.top), (2, -18));
+        assert_eq!((diamond.width, diamond.height), (16, 16));
+        let at = |x: usize, y: usize| diamond.coverage[y * 16 + x];
+        assert_eq!((at(8, 8), at(0, 0), at(15, 15)), (255, 0, 0));
+        assert!(at(0, 7) > 0 && at(0, 7) < 255);
+
+        assert_eq!(font.rasterize(0, 10.0).unwrap(), GlyphBitmap::default());
+    }
+}
@@ -0,0 +1,309 @@
+//! decoded images for `AssetKind::Image`: PNG, QOI and binary PPM/PGM
+
+use super::deflate::InflateError;
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use crate::renderer::{Blend, FromRgba, PixelFormat, Renderer, Rgba};
+use alloc::vec::Vec;
+use core::fmt;
+
+mod png;
+mod ppm;
+mod qoi;
+
+/// anything bigger than this is treated as a corrupt or malicious header
+pub const DEFAULT_MAX_PIXELS: u64 = 1 << 26;
+
+/// row-major straight RGBA pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgba>,
+}
+
+impl Image {
+    /// `None` if `pixels` does not hold exactly `width * height` pixels
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Rgba>) -> Option<Self> {
+        (pixels.len() as u64 == width as u64 * height as u64).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn filled(width: u32, height: u32, color: Rgba) -> Self {
+        Self {
+            width,
+            height,
+            pixels: alloc::vec![color; width as usize * height as usize],
+        }
+    }
+
+    /// decodes any supported format, going by the magic bytes at the start
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        Self::decode_with_limit(bytes, DEFAULT_MAX_PIXELS)
+    }
+
+    pub fn decode_with_limit(bytes: &[u8], max_pixels: u64) -> Result<Self, ImageError> {
+        match ImageFormat::sniff(bytes).ok_or(ImageError::UnknownFormat)? {
+            ImageFormat::Png => png::decode(bytes, max_pixels),
+            ImageFormat::Qoi => qoi::decode(bytes, max_pixels),
+            ImageFormat::Ppm => ppm::decode(bytes, max_pixels),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Rgba] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Rgba] {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<Rgba> {
+        self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[y as usize * self.width as usize + x as usize])
+    }
+
+    /// converts every pixel into a renderer's pixel type
+    pub fn to_pixels<P: FromRgba>(&self) -> Vec<P> {
+        self.pixels
+            .iter()
+            .map(|&color| P::from_rgba(color))
+            .collect()
+    }
+
+    /// a copy of everything `renderer` shows, e.g. for a screenshot
+    pub fn capture<R>(renderer: &R) -> Self
+    where
+        R: Renderer,
+        R::Pixel: PixelFormat,
+    {
+        Self {
+            width: renderer.width(),
+            height: renderer.height(),
+            pixels: renderer.data().iter().map(|p| p.to_rgba()).collect(),
+        }
+    }
+
+    /// as an 8-bit RGBA PNG
+    pub fn encode_png(&self) -> Vec<u8> {
+        png::encode(self)
+    }
+
+    /// as a binary PPM, without the alpha channel
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        ppm::encode(self)
+    }
+
+    /// copies the image onto `renderer` with its top left corner at `(x, y)`
+    pub fn blit<R>(&self, renderer: &mut R, x: u32, y: u32) -> Result<(), R::Error>
+    where
+        R: Renderer,
+        R::Pixel: FromRgba,
+    {
+        let pixels = self.to_pixels::<R::Pixel>();
+        renderer.overwrite_with(x, y, self.width, self.height, &pixels)
+    }
+
+    /// like `blit`, but blends with what is already there instead of overwriting it
+    pub fn composite<R>(&self, renderer: &mut R, x: u32, y: u32, blend: Blend)
+    where
+        R: Renderer,
+        R::Pixel: PixelFormat,
+    {
+        let (width, height) = (self.width, self.height);
+        crate::renderer::blend::composite(renderer, x, y, width, height, &self.pixels, blend);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Qoi,
+    /// binary `P6` pixmaps and `P5` greymaps
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => Some(Self::Png),
+            [b'q', b'o', b'i', b'f', ..] => Some(Self::Qoi),
+            [b'P', b'5' | b'6', ..] => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    UnknownFormat,
+    UnexpectedEof,
+    /// the header asks for more pixels than the decoder allows
+    TooLarge,
+    Invalid(&'static str),
+    Unsupported(&'static str),
+    ChecksumMismatch,
+    Inflate(InflateError),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => f.write_str("unknown image format"),
+            Self::UnexpectedEof => f.write_str("image data ended early"),
+            Self::TooLarge => f.write_str("image is too large"),
+            Self::Invalid(what) => write!(f, "invalid image: {what}"),
+            Self::Unsupported(what) => write!(f, "unsupported image: {what}"),
+            Self::ChecksumMismatch => f.write_str("image checksum mismatch"),
+            Self::Inflate(error) => write!(f, "bad compressed image data: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for ImageError {}
+
+impl From<InflateError> for ImageError {
+    fn from(error: InflateError) -> Self {
+        Self::Inflate(error)
+    }
+}
+
+fn check_size(width: u32, height: u32, max_pixels: u64) -> Result<usize, ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::Invalid("zero-sized image"));
+    }
+    let pixels = width as u64 * height as u64;
+    if pixels > max_pixels {
+        return Err(ImageError::TooLarge);
+    }
+    usize::try_from(pixels).map_err(|_| ImageError::TooLarge)
+}
+
+pub struct ImageDecoder {
+    pub max_pixels: u64,
+}
+
+impl Default for ImageDecoder {
+    fn default() -> Self {
+        Self {
+            max_pixels: DEFAULT_MAX_PIXELS,
+        }
+    }
+}
+
+impl AssetDecoder for ImageDecoder {
+    type Output = Image;
+    type Error = ImageError;
+
+    fn decode(&self, _: &DecodeContext<'_>, bytes: &[u8]) -> Result<Image, ImageError> {
+        Image::decode_with_limit(bytes, self.max_pixels)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.png`, `.qoi`, `.ppm` and `.pgm` files into an `Image`
+    pub fn with_images(mut self) -> Self {
+        self.register(
+            AssetKind::Image,
+            &["png", "qoi", "ppm", "pgm"],
+            ImageDecoder::default(),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 RGBA, second row up-filtered, written by python's zlib
+    const PNG: [u8; 88] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x9d,
+        0x74, 0x66, 0x1a, 0x00, 0x00, 0x00, 0x1f, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8,
+        0xcf, 0xc0, 0xf0, 0x1f, 0x0c, 0x19, 0xfe, 0x37, 0x30, 0x71, 0x8b, 0xc8, 0x69, 0x1a, 0xd9,
+        0xba, 0x05, 0x46, 0xa5, 0xe4, 0xff, 0x00, 0x00, 0x79, 0xab, 0x09, 0x0f, 0xe2, 0x17, 0xbe,
+        0xac, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    fn png() {
+        let image = Image::decode(&PNG).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.get(0, 0), Some(Rgba::opaque(255, 0, 0)));
+        assert_eq!(image.get(2, 0), Some(Rgba::new(0, 0, 255, 128)));
+        assert_eq!(image.get(1, 1), Some(Rgba::new(50, 60, 70, 80)));
+
+        let mut corrupt = PNG;
+        corrupt[20] ^= 1;
+        assert_eq!(Image::decode(&corrupt), Err(ImageError::ChecksumMismatch));
+
+        let pixels = (0..48u8)
+            .map(|i| Rgba::new(i * 5, 255 - i, i / 2, 255 - i * 3))
+            .collect();
+        let image = Image::from_pixels(8, 6, pixels).unwrap();
+        assert_eq!(Image::decode(&image.encode_png()).unwrap(), image);
+    }
+
+    // 4x2, one pixel or run per op: rgb, diff, luma, run of 2, index, rgba, a run clipped to 1
+    const QOI: [u8; 37] = [
+        b'q', b'o', b'i', b'f', 0, 0, 0, 4, 0, 0, 0, 2, 4, 0, 0xfe, 10, 20, 30, 0x76, 0xa5, 0x6b,
+        0xc1, 0x09, 0xff, 1, 2, 3, 4, 0xc4, 0, 0, 0, 0, 0, 0, 0, 1,
+    ];
+
+    #[test]
+    fn qoi() {
+        let image = Image::decode(&QOI).unwrap();
+        let luma = Rgba::opaque(14, 24, 38);
+        assert_eq!(
+            image.pixels(),
+            &[
+                Rgba::opaque(10, 20, 30),
+                Rgba::opaque(11, 19, 30),
+                luma,
+                luma,
+                luma,
+                Rgba::opaque(10, 20, 30),
+                Rgba::new(1, 2, 3, 4),
+                Rgba::new(1, 2, 3, 4),
+            ]
+        );
+
+        assert_eq!(Image::decode(&QOI[..20]), Err(ImageError::UnexpectedEof));
+        assert_eq!(Image::decode(&QOI[..26]), Err(ImageError::UnexpectedEof));
+        assert!(matches!(
+            Image::decode(&QOI[..33]),
+            Err(ImageError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn ppm_and_limits() {
+        let image = Image::decode(b"P5\n# grey\n2 1\n255\n\x00\xff").unwrap();
+        assert_eq!(image.pixels(), &[Rgba::BLACK, Rgba::WHITE]);
+        assert_eq!(
+            image.encode_ppm(),
+            b"P6\n2 1\n255\n\x00\x00\x00\xff\xff\xff"
+        );
+        assert_eq!(
+            Image::decode_with_limit(b"P6 100 100 255 ", 64),
+            Err(ImageError::TooLarge)
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use super::{check_size, Image, ImageError};
+use crate::asset::checksum::Crc32;
+use crate::asset::deflate::{deflate_zlib, inflate_zlib};
+use crate::renderer::Rgba;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub(super) const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// (x offset, y offset, x step, y step) for each adam7 pass
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Gray,
+    Rgb,
+    Indexed,
+    GrayAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn channels(self) -> u32 {
+        match self {
+            Self::Gray | Self::Indexed => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u8,
+    color: ColorType,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        let [w0, w1, w2, w3, h0, h1, h2, h3, depth, color, compression, filter, interlace] = *data
+        else {
+            return Err(ImageError::Invalid("bad IHDR length"));
+        };
+        let color = match (color, depth) {
+            (0, 1 | 2 | 4 | 8 | 16) => ColorType::Gray,
+            (2, 8 | 16) => ColorType::Rgb,
+            (3, 1 | 2 | 4 | 8) => ColorType::Indexed,
+            (4, 8 | 16) => ColorType::GrayAlpha,
+            (6, 8 | 16) => ColorType::Rgba,
+            _ => return Err(ImageError::Invalid("bad color type and bit depth")),
+        };
+        if compression != 0 || filter != 0 || interlace > 1 {
+            return Err(ImageError::Unsupported(
+                "unknown png compression, filter or interlace method",
+            ));
+        }
+        Ok(Self {
+            width: u32::from_be_bytes([w0, w1, w2, w3]),
+            height: u32::from_be_bytes([h0, h1, h2, h3]),
+            depth,
+            color,
+            interlaced: interlace == 1,
+        })
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.color.channels() as usize * self.depth as usize
+    }
+
+    /// bytes in one scanline of a `width` pixel wide (sub)image, without the filter byte
+    fn stride(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    fn passes(&self) -> impl Iterator<Item = (u32, u32, u32, u32, u32, u32)> + '_ {
+        let passes: &'static [(u32, u32, u32, u32)] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        passes.iter().map(|&(x0, y0, dx, dy)| {
+            let width = self.width.saturating_sub(x0).div_ceil(dx);
+            let height = self.height.saturating_sub(y0).div_ceil(dy);
+            (x0, y0, dx, dy, width, height)
+        })
+    }
+
+    fn raw_size(&self) -> usize {
+        self.passes()
+            .filter(|&(.., w, h)| w != 0 && h != 0)
+            .map(|(.., w, h)| h as usize * (1 + self.stride(w)))
+            .sum()
+    }
+}
+
+/// how transparency is represented, from the `tRNS` chunk
+enum Transparency {
+    None,
+    /// this exact gray or rgb sample value (at the image's bit depth) is fully transparent
+    Key([u16; 3]),
+}
+
+struct Chunks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<([u8; 4], &'a [u8]), ImageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(self.chunk())
+    }
+}
+
+impl<'a> Chunks<'a> {
+    fn chunk(&mut self) -> Result<([u8; 4], &'a [u8]), ImageError> {
+        let header = self.data.get(..8).ok_or(ImageError::UnexpectedEof)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let body = self.data.get(8..8 + len).ok_or(ImageError::UnexpectedEof)?;
+        let crc = self
+            .data
+            .get(8 + len..12 + len)
+            .ok_or(ImageError::UnexpectedEof)?;
+
+        let mut expected = Crc32::new();
+        expected.update(&kind);
+        expected.update(body);
+        if expected.finish() != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(ImageError::ChecksumMismatch);
+        }
+        self.data = &self.data[12 + len..];
+        Ok((kind, body))
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// undoes one scanline's filter in place. `prev` is the unfiltered line above (zeroes for the first)
+fn unfilter(filter: u8, line: &mut [u8], prev: &[u8], bpp: usize) -> Result<(), ImageError> {
+    match filter {
+        0 => {}
+        1 => {
+            for i in bpp..line.len() {
+                line[i] = line[i].wrapping_add(line[i - bpp]);
+            }
+        }
+        2 => {
+            for (byte, up) in line.iter_mut().zip(prev) {
+                *byte = byte.wrapping_add(*up);
+            }
+        }
+        3 => {
+            for i in 0..line.len() {
+                let left = if i >= bpp { line[i - bpp] as u16 } else { 0 };
+                line[i] = line[i].wrapping_add(((left + prev[i] as u16) / 2) as u8);
+            }
+        }
+        4 => {
+            for i in 0..line.len() {
+                let (left, up_left) = if i >= bpp {
+                    (line[i - bpp], prev[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                line[i] = line[i].wrapping_add(paeth(left, prev[i], up_left));
+            }
+        }
+        _ => return Err(ImageError::Invalid("unknown png filter type")),
+    }
+    Ok(())
+}
+
+pub(super) fn decode(bytes: &[u8], max_pixels: u64) -> Result<Image, ImageError> {
+    let rest = bytes
+        .strip_prefix(&SIGNATURE)
+        .ok_or(ImageError::Invalid("bad png signature"))?;
+    let mut chunks = Chunks { data: rest };
+
+    let (kind, data) = chunks.next().ok_or(ImageError::UnexpectedEof)??;
+    if &kind != b"IHDR" {
+        return Err(ImageError::Invalid("png does not start with IHDR"));
+    }
+    let header = Header::parse(data)?;
+    let count = check_size(header.width, header.height, max_pixels)?;
+
+    let mut palette: Vec<Rgba> = Vec::new();
+    let mut transparency = Transparency::None;
+    let mut compressed = Vec::new();
+    let mut ended = false;
+
+    for chunk in chunks.by_ref() {
+        let (kind, data) = chunk?;
+        match &kind {
+            b"PLTE" => {
+                if data.len() % 3 != 0 || data.len() > 256 * 3 {
+                    return Err(ImageError::Invalid("bad PLTE length"));
+                }
+                palette = data
+                    .chunks_exact(3)
+                    .map(|rgb| Rgba::opaque(rgb[0], rgb[1], rgb[2]))
+                    .collect();
+            }
+            b"tRNS" => match header.color {
+                ColorType::Indexed => {
+                    for (entry, &alpha) in palette.iter_mut().zip(data) {
+                        entry.a = alpha;
+                    }
+                }
+                ColorType::Gray if data.len() == 2 => {
+                    let gray = u16::from_be_bytes([data[0], data[1]]);
+                    transparency = Transparency::Key([gray; 3]);
+                }
+                ColorType::Rgb if data.len() == 6 => {
+                    transparency = Transparency::Key([
+                        u16::from_be_bytes([data[0], data[1]]),
+                        u16::from_be_bytes([data[2], data[3]]),
+                        u16::from_be_bytes([data[4], data[5]]),
+                    ]);
+                }
+                _ => return Err(ImageError::Invalid("bad tRNS chunk")),
+            },
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => {
+                ended = true;
+                break;
+            }
+            // an uppercase first letter marks a chunk we are not allowed to skip
+            [first, ..] if first.is_ascii_uppercase() => {
+                return Err(ImageError::Unsupported("unknown critical png chunk"));
+            }
+            _ => {}
+        }
+    }
+    if !ended {
+        return Err(ImageError::UnexpectedEof);
+    }
+    if header.color == ColorType::Indexed && palette.is_empty() {
+        return Err(ImageError::Invalid("indexed png without a palette"));
+    }
+
+    let raw_size = header.raw_size();
+    let mut raw = inflate_zlib(&compressed, raw_size)?;
+    if raw.len() != raw_size {
+        return Err(ImageError::UnexpectedEof);
+    }
+
+    let mut pixels = vec![Rgba::TRANSPARENT; count];
+    let bpp = header.bits_per_pixel().div_ceil(8);
+    let mut offset = 0;
+
+    for (x0, y0, dx, dy, width, height) in header.passes() {
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let stride = header.stride(width);
+        let mut prev = vec![0u8; stride];
+        for row in 0..height {
+            let (filter, line) = raw[offset..offset + 1 + stride]
+                .split_first_mut()
+                .expect("raw_size accounts for the filter byte");
+            unfilter(*filter, line, &prev, bpp)?;
+
+            let y = y0 + row * dy;
+            for col in 0..width {
+                let x = x0 + col * dx;
+                pixels[y as usize * header.width as usize + x as usize] =
+                    pixel(&header, line, col as usize, &palette, &transparency)?;
+            }
+            prev.copy_from_slice(line);
+            offset += 1 + stride;
+        }
+    }
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+/// reads sample `index` of a scanline at the header's bit depth
+fn sample(line: &[u8], depth: u8, index: usize) -> u16 {
+    match depth {
+        8 => line[index] as u16,
+        16 => u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]),
+        _ => {
+            let bit = index * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            ((line[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+        }
+    }
+}
+
+fn pixel(
+    header: &Header,
+    line: &[u8],
+    x: usize,
+    palette: &[Rgba],
+    transparency: &Transparency,
+) -> Result<Rgba, ImageError> {
+    let channels = header.color.channels() as usize;
+    let depth = header.depth;
+    let max = (1u32 << depth) - 1;
+    let to8 = |value: u16| ((value as u32 * 255 + max / 2) / max) as u8;
+    let get = |channel: usize| sample(line, depth, x * channels + channel);
+
+    let keyed = |samples: [u16; 3]| match transparency {
+        Transparency::Key(key) => *key == samples,
+        Transparency::None => false,
+    };
+
+    Ok(match header.color {
+        ColorType::Indexed => *palette
+            .get(get(0) as usize)
+            .ok_or(ImageError::Invalid("palette index out of range"))?,
+        ColorType::Gray => {
+            let gray = get(0);
+            let v = to8(gray);
+            let a = if keyed([gray; 3]) { 0 } else { 255 };
+            Rgba::new(v, v, v, a)
+        }
+        ColorType::GrayAlpha => {
+            let v = to8(get(0));
+            Rgba::new(v, v, v, to8(get(1)))
+        }
+        ColorType::Rgb => {
+            let rgb = [get(0), get(1), get(2)];
+            let a = if keyed(rgb) { 0 } else { 255 };
+            Rgba::new(to8(rgb[0]), to8(rgb[1]), to8(rgb[2]), a)
+        }
+        ColorType::Rgba => Rgba::new(to8(get(0)), to8(get(1)), to8(get(2)), to8(get(3))),
+    })
+}
+
+/// one chunk with its length and checksum
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(body);
+    out.extend_from_slice(&crc.finish().to_be_bytes());
+}
+
+/// 8-bit RGBA, non-interlaced. each scanline gets whichever filter leaves the smallest
+/// bytes, which is what libpng guesses with too
+pub(super) fn encode(image: &Image) -> Vec<u8> {
+    let stride = image.width as usize * 4;
+    let raw: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|p| [p.r, p.g, p.b, p.a])
+        .collect();
+    let mut filtered = Vec::with_capacity((stride + 1) * image.height as usize);
+    let zeroes = vec![0; stride];
+    let (mut line, mut best_line) = (vec![0; stride], vec![0; stride]);
+    for (row, current) in raw.chunks_exact(stride.max(1)).enumerate() {
+        let prev = match row {
+            0 => &zeroes[..],
+            _ => &raw[(row - 1) * stride..row * stride],
+        };
+        let mut best = (u64::MAX, 0);
+        for filter in 0..5 {
+            for i in 0..stride {
+                let (left, up_left) = match i >= 4 {
+                    true => (current[i - 4], prev[i - 4]),
+                    false => (0, 0),
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => prev[i],
+                    3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+                    _ => paeth(left, prev[i], up_left),
+                };
+                line[i] = current[i].wrapping_sub(predicted);
+            }
+            // as signed bytes, so small steps either way count as small
+            let cost = line.iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+            if cost < best.0 {
+                best = (cost, filter);
+                best_line.copy_from_slice(&line);
+            }
+        }
+        filtered.push(best.1);
+        filtered.extend_from_slice(&best_line);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &deflate_zlib(&filtered));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
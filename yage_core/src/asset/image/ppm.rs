@@ -0,0 +1,82 @@
+use super::{check_size, Image, ImageError};
+use crate::renderer::Rgba;
+use alloc::vec::Vec;
+
+/// reads one whitespace separated header number, skipping `#` comments
+fn number(bytes: &[u8], pos: &mut usize) -> Result<u32, ImageError> {
+    loop {
+        match bytes.get(*pos) {
+            Some(b' ' | b'\t' | b'\n' | b'\r') => *pos += 1,
+            Some(b'#') => {
+                while !matches!(bytes.get(*pos), Some(b'\n' | b'\r') | None) {
+                    *pos += 1;
+                }
+            }
+            Some(b'0'..=b'9') => break,
+            Some(_) => return Err(ImageError::Invalid("bad ppm header")),
+            None => return Err(ImageError::UnexpectedEof),
+        }
+    }
+    let mut value: u32 = 0;
+    while let Some(&digit @ b'0'..=b'9') = bytes.get(*pos) {
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add((digit - b'0') as u32))
+            .ok_or(ImageError::Invalid("bad ppm header"))?;
+        *pos += 1;
+    }
+    Ok(value)
+}
+
+pub(super) fn decode(bytes: &[u8], max_pixels: u64) -> Result<Image, ImageError> {
+    let channels = match bytes[1] {
+        b'5' => 1,
+        _ => 3,
+    };
+    let mut pos = 2;
+    let width = number(bytes, &mut pos)?;
+    let height = number(bytes, &mut pos)?;
+    let max = number(bytes, &mut pos)?;
+    if max == 0 || max > 65535 {
+        return Err(ImageError::Invalid("bad ppm maximum value"));
+    }
+    // exactly one whitespace byte separates the header from the raster
+    if !matches!(bytes.get(pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+        return Err(ImageError::Invalid("bad ppm header"));
+    }
+    pos += 1;
+
+    let count = check_size(width, height, max_pixels)?;
+    let sample_size = if max > 255 { 2 } else { 1 };
+    let raster = bytes
+        .get(pos..pos + count * channels * sample_size)
+        .ok_or(ImageError::UnexpectedEof)?;
+
+    let scale = |sample: u32| (sample.min(max) * 255 + max / 2) / max;
+    let samples: Vec<u8> = match sample_size {
+        1 => raster.iter().map(|&s| scale(s as u32) as u8).collect(),
+        _ => raster
+            .chunks_exact(2)
+            .map(|s| scale(u16::from_be_bytes([s[0], s[1]]) as u32) as u8)
+            .collect(),
+    };
+    let pixels = match channels {
+        1 => samples.iter().map(|&v| Rgba::opaque(v, v, v)).collect(),
+        _ => samples
+            .chunks_exact(3)
+            .map(|rgb| Rgba::opaque(rgb[0], rgb[1], rgb[2]))
+            .collect(),
+    };
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// a binary `P6` pixmap. PPM has no alpha, so it is dropped
+pub(super) fn encode(image: &Image) -> Vec<u8> {
+    let mut out = alloc::format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    out.extend(image.pixels.iter().flat_map(|p| [p.r, p.g, p.b]));
+    out
+}
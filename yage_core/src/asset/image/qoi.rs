@@ -0,0 +1,88 @@
+use super::{check_size, Image, ImageError};
+use crate::renderer::Rgba;
+use alloc::vec::Vec;
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+fn hash(Rgba { r, g, b, a }: Rgba) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+pub(super) fn decode(bytes: &[u8], max_pixels: u64) -> Result<Image, ImageError> {
+    let header = bytes.get(..14).ok_or(ImageError::UnexpectedEof)?;
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    if !matches!(header[12], 3 | 4) || header[13] > 1 {
+        return Err(ImageError::Invalid("bad qoi channels or colorspace"));
+    }
+    let count = check_size(width, height, max_pixels)?;
+
+    let mut pixels = Vec::with_capacity(count);
+    let mut seen = [Rgba::TRANSPARENT; 64];
+    let mut px = Rgba::BLACK;
+    let mut data = &bytes[14..];
+
+    let mut next = || -> Result<u8, ImageError> {
+        let (&byte, rest) = data.split_first().ok_or(ImageError::UnexpectedEof)?;
+        data = rest;
+        Ok(byte)
+    };
+
+    while pixels.len() < count {
+        let op = next()?;
+        match op {
+            OP_RGB => {
+                px.r = next()?;
+                px.g = next()?;
+                px.b = next()?;
+            }
+            OP_RGBA => {
+                px.r = next()?;
+                px.g = next()?;
+                px.b = next()?;
+                px.a = next()?;
+            }
+            _ => match op & 0xc0 {
+                OP_INDEX => px = seen[op as usize],
+                OP_DIFF => {
+                    px.r = px.r.wrapping_add((op >> 4) & 3).wrapping_sub(2);
+                    px.g = px.g.wrapping_add((op >> 2) & 3).wrapping_sub(2);
+                    px.b = px.b.wrapping_add(op & 3).wrapping_sub(2);
+                }
+                OP_LUMA => {
+                    let second = next()?;
+                    let dg = (op & 0x3f).wrapping_sub(32);
+                    px.r =
+                        px.r.wrapping_add(dg.wrapping_sub(8).wrapping_add(second >> 4));
+                    px.g = px.g.wrapping_add(dg);
+                    px.b =
+                        px.b.wrapping_add(dg.wrapping_sub(8).wrapping_add(second & 0xf));
+                }
+                OP_RUN => {
+                    // stored with a bias of one. the run can't go past the end of the image
+                    let run = ((op & 0x3f) as usize + 1).min(count - pixels.len());
+                    pixels.extend(core::iter::repeat_n(px, run));
+                    continue;
+                }
+                _ => unreachable!(),
+            },
+        }
+        seen[hash(px)] = px;
+        pixels.push(px);
+    }
+
+    if !data.starts_with(&END_MARKER) {
+        return Err(ImageError::Invalid("missing qoi end marker"));
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
@@ -0,0 +1,270 @@
+use super::{Map, Value};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// builds a typed value out of a `Value`. see `impl_from_json!` for structs
+pub trait FromJson: Sized {
+    fn from_json(value: &Value) -> Result<Self, ExtractError>;
+
+    /// what a missing object field turns into, if anything. only `Option` has an answer
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractErrorKind {
+    Missing,
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    OutOfRange,
+    Invalid(String),
+}
+
+/// what went wrong and where, as a JSON pointer to the offending value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractError {
+    path: String,
+    kind: ExtractErrorKind,
+}
+
+impl ExtractError {
+    pub fn new(kind: ExtractErrorKind) -> Self {
+        Self {
+            path: String::new(),
+            kind,
+        }
+    }
+
+    pub fn wrong_type(expected: &'static str, found: &Value) -> Self {
+        Self::new(ExtractErrorKind::WrongType {
+            expected,
+            found: found.type_name(),
+        })
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ExtractErrorKind::Invalid(message.into()))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &ExtractErrorKind {
+        &self.kind
+    }
+
+    /// marks the error as having happened inside `segment` of the parent value
+    pub fn within(mut self, segment: &str) -> Self {
+        let mut path = String::with_capacity(segment.len() + self.path.len() + 1);
+        path.push('/');
+        for c in segment.chars() {
+            match c {
+                '~' => path.push_str("~0"),
+                '/' => path.push_str("~1"),
+                c => path.push(c),
+            }
+        }
+        path.push_str(&self.path);
+        self.path = path;
+        self
+    }
+
+    fn within_index(self, index: usize) -> Self {
+        self.within(&alloc::format!("{index}"))
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        match &self.kind {
+            ExtractErrorKind::Missing => write!(f, "missing value at `{path}`"),
+            ExtractErrorKind::WrongType { expected, found } => {
+                write!(f, "expected {expected} at `{path}`, found {found}")
+            }
+            ExtractErrorKind::OutOfRange => write!(f, "number out of range at `{path}`"),
+            ExtractErrorKind::Invalid(message) => write!(f, "{message} at `{path}`"),
+        }
+    }
+}
+
+impl core::error::Error for ExtractError {}
+
+impl Value {
+    /// extracts the field `key` of an object, keeping track of the path on failure
+    pub fn field<T: FromJson>(&self, key: &str) -> Result<T, ExtractError> {
+        let map = self
+            .as_object()
+            .ok_or_else(|| ExtractError::wrong_type("an object", self))?;
+        match map.get(key) {
+            Some(value) => T::from_json(value).map_err(|e| e.within(key)),
+            None => T::from_missing()
+                .ok_or_else(|| ExtractError::new(ExtractErrorKind::Missing).within(key)),
+        }
+    }
+
+    /// like `field`, but a missing field is `default` instead of an error
+    pub fn field_or<T: FromJson>(&self, key: &str, default: T) -> Result<T, ExtractError> {
+        match self.get(key) {
+            Some(value) => T::from_json(value).map_err(|e| e.within(key)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn extract<T: FromJson>(&self) -> Result<T, ExtractError> {
+        T::from_json(self)
+    }
+}
+
+/// implements `FromJson` for a struct by extracting every listed field under its own name
+#[macro_export]
+macro_rules! impl_from_json {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::asset::json::FromJson for $ty {
+            fn from_json(
+                value: &$crate::asset::json::Value,
+            ) -> ::core::result::Result<Self, $crate::asset::json::ExtractError> {
+                ::core::result::Result::Ok(Self {
+                    $($field: value.field(::core::stringify!($field))?,)*
+                })
+            }
+        }
+    };
+}
+
+impl FromJson for Value {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJson for Map {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        value
+            .as_object()
+            .cloned()
+            .ok_or_else(|| ExtractError::wrong_type("an object", value))
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        value
+            .as_bool()
+            .ok_or_else(|| ExtractError::wrong_type("a boolean", value))
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        value
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| ExtractError::wrong_type("a string", value))
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        value
+            .as_f64()
+            .ok_or_else(|| ExtractError::wrong_type("a number", value))
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        f64::from_json(value).map(|float| float as f32)
+    }
+}
+
+macro_rules! int_from_json {
+    ($($int:ty)*) => {
+        $(
+            impl FromJson for $int {
+                fn from_json(value: &Value) -> Result<Self, ExtractError> {
+                    let number = value
+                        .as_number()
+                        .ok_or_else(|| ExtractError::wrong_type("an integer", value))?;
+                    let int = number
+                        .as_i64()
+                        .ok_or_else(|| ExtractError::wrong_type("an integer", value))?;
+                    int.try_into()
+                        .map_err(|_| ExtractError::new(ExtractErrorKind::OutOfRange))
+                }
+            }
+        )*
+    };
+}
+
+int_from_json!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json(value).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromJson> FromJson for Box<T> {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        T::from_json(value).map(Box::new)
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        let array = value
+            .as_array()
+            .ok_or_else(|| ExtractError::wrong_type("an array", value))?;
+        array
+            .iter()
+            .enumerate()
+            .map(|(i, value)| T::from_json(value).map_err(|e| e.within_index(i)))
+            .collect()
+    }
+}
+
+impl<T: FromJson, const N: usize> FromJson for [T; N] {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        let items: Vec<T> = Vec::from_json(value)?;
+        items.try_into().map_err(|items: Vec<T>| {
+            ExtractError::invalid(alloc::format!(
+                "expected {N} elements, found {}",
+                items.len()
+            ))
+        })
+    }
+}
+
+impl<T: FromJson> FromJson for BTreeMap<String, T> {
+    fn from_json(value: &Value) -> Result<Self, ExtractError> {
+        let map = value
+            .as_object()
+            .ok_or_else(|| ExtractError::wrong_type("an object", value))?;
+        map.iter()
+            .map(|(key, value)| {
+                T::from_json(value)
+                    .map(|value| (key.into(), value))
+                    .map_err(|e| e.within(key))
+            })
+            .collect()
+    }
+}
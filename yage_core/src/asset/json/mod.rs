@@ -0,0 +1,593 @@
+//! `no_std` JSON for `AssetKind::JsonBody`.
+//!
+//! `Parser` streams `Event`s out of a document, `Value` is the tree built from them, and
+//! `FromJson` pulls typed data back out of a `Value`
+
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+mod extract;
+mod parse;
+
+pub use extract::{ExtractError, ExtractErrorKind, FromJson};
+pub use parse::{Event, ParseError, ParseErrorKind, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    /// anything written without a fraction or exponent that fits
+    Int(i64),
+    /// finite, the parser rejects numbers too big for an `f64`
+    Float(f64),
+}
+
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(int) => int as f64,
+            Self::Float(float) => float,
+        }
+    }
+
+    /// `None` for floats that are not whole or do not fit
+    pub fn as_i64(self) -> Option<i64> {
+        match self {
+            Self::Int(int) => Some(int),
+            Self::Float(float) => {
+                let int = float as i64;
+                (int as f64 == float).then_some(int)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(int) => write!(f, "{int}"),
+            // JSON has no way to spell these
+            Self::Float(float) if !float.is_finite() => f.write_str("null"),
+            Self::Float(float) => write!(f, "{float:?}"),
+        }
+    }
+}
+
+/// an object that keeps its keys in document order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+    entries: Vec<(String, Value)>,
+    /// where each key is in `entries`, so big objects don't make every lookup a scan
+    index: BTreeMap<String, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let &i = self.index.get(key)?;
+        Some(&self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        let &i = self.index.get(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    /// replaces (and returns) the old value if `key` was already present, keeping its position
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        match self.get_mut(&key) {
+            Some(slot) => Some(core::mem::replace(slot, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _)| k.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Map),
+}
+
+impl Value {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        Self::from_parser(Parser::new(src))
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ParseError> {
+        Self::from_parser(Parser::from_slice(bytes)?)
+    }
+
+    /// drains `parser` into a tree. the parser already caps how deep this can go
+    pub fn from_parser(mut parser: Parser<'_>) -> Result<Self, ParseError> {
+        // containers that are still open, with the key their value will be stored under
+        let mut stack: Vec<(Value, Option<String>)> = Vec::new();
+        let mut key = None;
+        let mut root = None;
+
+        while let Some(event) = parser.next_event()? {
+            let value = match event {
+                Event::Key(k) => {
+                    key = Some(k.into_owned());
+                    continue;
+                }
+                Event::BeginObject | Event::BeginArray => {
+                    let container = match event {
+                        Event::BeginObject => Value::Object(Map::new()),
+                        _ => Value::Array(Vec::new()),
+                    };
+                    stack.push((container, key.take()));
+                    continue;
+                }
+                Event::EndObject | Event::EndArray => {
+                    let (container, k) = stack.pop().expect("the parser balances containers");
+                    key = k;
+                    container
+                }
+                Event::String(s) => Value::String(s.into_owned()),
+                Event::Number(n) => Value::Number(n),
+                Event::Bool(b) => Value::Bool(b),
+                Event::Null => Value::Null,
+            };
+            match stack.last_mut() {
+                Some((Value::Object(map), _)) => {
+                    map.insert(key.take().expect("the parser emits a key first"), value);
+                }
+                Some((Value::Array(array), _)) => array.push(value),
+                Some(_) => unreachable!(),
+                None => root = Some(value),
+            }
+        }
+        Ok(root.expect("the parser rejects empty documents"))
+    }
+
+    /// looks up a JSON pointer (RFC 6901), e.g. `/enemies/0/name`. the empty pointer is `self`
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let mut current = self;
+        for token in pointer.strip_prefix('/')?.split('/') {
+            current = match current {
+                Value::Object(map) => {
+                    if token.contains('~') {
+                        map.get(&unescape_token(token)?)?
+                    } else {
+                        map.get(token)?
+                    }
+                }
+                Value::Array(array) => array.get(parse_index(token)?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let mut current = self;
+        for token in pointer.strip_prefix('/')?.split('/') {
+            current = match current {
+                Value::Object(map) => {
+                    if token.contains('~') {
+                        map.get_mut(&unescape_token(token)?)?
+                    } else {
+                        map.get_mut(token)?
+                    }
+                }
+                Value::Array(array) => array.get_mut(parse_index(token)?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object()?.get(key)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number()?.as_i64()
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(Number::as_f64)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Map> {
+        match self {
+            Self::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "a boolean",
+            Self::Number(_) => "a number",
+            Self::String(_) => "a string",
+            Self::Array(_) => "an array",
+            Self::Object(_) => "an object",
+        }
+    }
+}
+
+fn unescape_token(token: &str) -> Option<String> {
+    let mut out = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' => match chars.next()? {
+                '0' => out.push('~'),
+                '1' => out.push('/'),
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn parse_index(token: &str) -> Option<usize> {
+    // leading zeros are not valid array indices
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// writes compact JSON
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::String(s) => write_string(f, s),
+            Self::Array(array) => {
+                f.write_str("[")?;
+                for (i, value) in array.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(map) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    Extract(ExtractError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => write!(f, "{error}"),
+            Self::Extract(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<ExtractError> for Error {
+    fn from(error: ExtractError) -> Self {
+        Self::Extract(error)
+    }
+}
+
+/// decodes JSON assets into a `T`. the default leaves them as a `Value`
+pub struct JsonDecoder<T = Value> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonDecoder<T> {
+    pub const fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for JsonDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> AssetDecoder for JsonDecoder<T>
+where
+    T: FromJson + Send + Sync + 'static,
+{
+    type Output = T;
+    type Error = Error;
+
+    fn decode(&self, _: &DecodeContext<'_>, bytes: &[u8]) -> Result<T, Error> {
+        Ok(T::from_json(&Value::from_slice(bytes)?)?)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.json` files and anything else tagged `AssetKind::JsonBody` into a `Value`
+    pub fn with_json(mut self) -> Self {
+        self.register(AssetKind::JsonBody, &["json"], JsonDecoder::<Value>::new());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parses_nested_documents() {
+        let value = Value::parse(
+            r#"{ "name": "slime", "hp": 12, "speed": 1.5, "tags": ["small", "green"],
+                "loot": { "gold": -3, "drop": null, "rare": false } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.pointer("/name").and_then(Value::as_str),
+            Some("slime")
+        );
+        assert_eq!(value.pointer("/hp").and_then(Value::as_i64), Some(12));
+        assert_eq!(value.pointer("/speed").and_then(Value::as_f64), Some(1.5));
+        assert_eq!(
+            value.pointer("/tags/1").and_then(Value::as_str),
+            Some("green")
+        );
+        assert_eq!(
+            value.pointer("/loot/gold").and_then(Value::as_i64),
+            Some(-3)
+        );
+        assert!(value.pointer("/loot/drop").unwrap().is_null());
+        assert_eq!(
+            value.pointer("/loot/rare").and_then(Value::as_bool),
+            Some(false)
+        );
+        assert!(value.pointer("/tags/01").is_none());
+        assert!(value.pointer("/missing").is_none());
+        assert_eq!(value.pointer(""), Some(&value));
+    }
+
+    #[test]
+    fn pointer_escapes() {
+        let value = Value::parse(r#"{ "a/b": 1, "m~n": 2 }"#).unwrap();
+        assert_eq!(value.pointer("/a~1b").and_then(Value::as_i64), Some(1));
+        assert_eq!(value.pointer("/m~0n").and_then(Value::as_i64), Some(2));
+    }
+
+    #[test]
+    fn string_escapes() {
+        let value = Value::parse(r#"["a\"b\\c\/d\n", "é😀", "plain"]"#).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::String("a\"b\\c/d\n".into()),
+                Value::String("é😀".into()),
+                Value::String("plain".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn streams_events() {
+        let mut parser = Parser::new(r#"{"a": [1, true]}"#);
+        let mut events = Vec::new();
+        while let Some(event) = parser.next_event().unwrap() {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            vec![
+                Event::BeginObject,
+                Event::Key("a".into()),
+                Event::BeginArray,
+                Event::Number(Number::Int(1)),
+                Event::Bool(true),
+                Event::EndArray,
+                Event::EndObject,
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let error = Value::parse("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::ExpectedColon);
+        assert_eq!((error.line, error.column), (3, 7));
+
+        let error = Value::parse("[1, 2").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEof);
+
+        let error = Value::parse("[01]").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::ExpectedCommaOrEnd);
+
+        let error = Value::parse("{} {}").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::TrailingCharacters);
+        assert_eq!((error.line, error.column), (1, 4));
+
+        let error = Value::from_slice(b"[\"\xff\"]").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidUtf8);
+        assert_eq!((error.line, error.column), (1, 3));
+
+        let error = Value::parse("[1, -1e999]").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidNumber);
+        assert_eq!((error.line, error.column), (1, 5));
+    }
+
+    #[test]
+    fn big_objects_and_repeated_keys() {
+        let mut src = String::from("{");
+        for i in 0..20_000 {
+            src.push_str(&alloc::format!("\"k{i}\": {i}, "));
+        }
+        src.push_str("\"k7\": -1}");
+        let value = Value::parse(&src).unwrap();
+        let Value::Object(map) = &value else {
+            panic!("{value:?}")
+        };
+        assert_eq!(map.len(), 20_000);
+        // the last value wins, the first position stays
+        assert_eq!(map.get("k7").and_then(Value::as_i64), Some(-1));
+        assert_eq!(map.keys().nth(7), Some("k7"));
+        assert_eq!(map.get("k19999").and_then(Value::as_i64), Some(19_999));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let src = r#"{"a":[1,2.5,"x\ny"],"b":{"c":null,"d":true}}"#;
+        let value = Value::parse(src).unwrap();
+        assert_eq!(alloc::format!("{value}"), src);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Enemy {
+        name: String,
+        hp: u32,
+        speed: f32,
+        tags: Vec<String>,
+        boss: Option<bool>,
+    }
+
+    crate::impl_from_json!(Enemy {
+        name,
+        hp,
+        speed,
+        tags,
+        boss
+    });
+
+    #[test]
+    fn extracts_structs() {
+        let value =
+            Value::parse(r#"{"name": "bat", "hp": 3, "speed": 4, "tags": ["flying"]}"#).unwrap();
+        let enemy = Enemy::from_json(&value).unwrap();
+        assert_eq!(
+            enemy,
+            Enemy {
+                name: "bat".into(),
+                hp: 3,
+                speed: 4.0,
+                tags: vec!["flying".into()],
+                boss: None,
+            }
+        );
+
+        let value = Value::parse(r#"{"name": "bat", "hp": -1, "speed": 4, "tags": [1]}"#).unwrap();
+        let error = Enemy::from_json(&value).unwrap_err();
+        assert_eq!(error.path(), "/hp");
+
+        let value = Value::parse(r#"{"name": "bat", "hp": 1, "speed": 4, "tags": [1]}"#).unwrap();
+        let error = Enemy::from_json(&value).unwrap_err();
+        assert_eq!(error.path(), "/tags/0");
+    }
+}
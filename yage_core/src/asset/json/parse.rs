@@ -0,0 +1,461 @@
+use super::Number;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// a single step through a JSON document, in document order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    BeginObject,
+    EndObject,
+    BeginArray,
+    EndArray,
+    /// an object key, always followed by the events of its value
+    Key(Cow<'a, str>),
+    String(Cow<'a, str>),
+    Number(Number),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidUtf8,
+    UnexpectedEof,
+    UnexpectedCharacter(char),
+    ExpectedColon,
+    ExpectedCommaOrEnd,
+    ExpectedKey,
+    InvalidLiteral,
+    InvalidNumber,
+    InvalidEscape,
+    InvalidUnicodeEscape,
+    ControlCharacterInString,
+    TrailingCharacters,
+    TooDeep,
+}
+
+/// where and why a document failed to parse. lines and columns start at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUtf8 => f.write_str("invalid utf-8"),
+            Self::UnexpectedEof => f.write_str("unexpected end of input"),
+            Self::UnexpectedCharacter(c) => write!(f, "unexpected character {c:?}"),
+            Self::ExpectedColon => f.write_str("expected `:`"),
+            Self::ExpectedCommaOrEnd => f.write_str("expected `,` or the end of the container"),
+            Self::ExpectedKey => f.write_str("expected a string key"),
+            Self::InvalidLiteral => f.write_str("invalid literal"),
+            Self::InvalidNumber => f.write_str("invalid number"),
+            Self::InvalidEscape => f.write_str("invalid escape sequence"),
+            Self::InvalidUnicodeEscape => f.write_str("invalid unicode escape"),
+            Self::ControlCharacterInString => f.write_str("control character in string"),
+            Self::TrailingCharacters => f.write_str("trailing characters after the document"),
+            Self::TooDeep => f.write_str("the document is nested too deeply"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Object,
+    Array,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Value,
+    FirstValueOrEnd,
+    FirstKeyOrEnd,
+    Key,
+    AfterValue,
+    Done,
+}
+
+/// pull parser that hands out `Event`s without building a tree.
+/// strings without escapes are borrowed straight out of the source
+pub struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+    stack: Vec<Frame>,
+    state: State,
+    max_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            line: 1,
+            column: 1,
+            stack: Vec::new(),
+            state: State::Value,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// validates the bytes as utf-8 first, reporting where the first bad sequence is
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        match core::str::from_utf8(bytes) {
+            Ok(src) => Ok(Self::new(src)),
+            Err(error) => {
+                let mut parser = Self::new(
+                    // SAFETY: everything up to `valid_up_to` has been checked
+                    unsafe { core::str::from_utf8_unchecked(&bytes[..error.valid_up_to()]) },
+                );
+                while parser.bump().is_some() {}
+                Err(parser.error(ParseErrorKind::InvalidUtf8))
+            }
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// how many containers the parser is currently inside of
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// `Ok(None)` once the whole document (and nothing after it) has been read
+    pub fn next_event(&mut self) -> Result<Option<Event<'a>>, ParseError> {
+        loop {
+            self.skip_whitespace();
+            match self.state {
+                State::Done => {
+                    return match self.peek() {
+                        None => Ok(None),
+                        Some(_) => Err(self.error(ParseErrorKind::TrailingCharacters)),
+                    };
+                }
+                State::AfterValue => {
+                    let Some(&frame) = self.stack.last() else {
+                        self.state = State::Done;
+                        continue;
+                    };
+                    match (self.peek(), frame) {
+                        (Some(b','), Frame::Object) => {
+                            self.bump();
+                            self.state = State::Key;
+                        }
+                        (Some(b','), Frame::Array) => {
+                            self.bump();
+                            self.state = State::Value;
+                        }
+                        (Some(b'}'), Frame::Object) => return Ok(Some(self.close())),
+                        (Some(b']'), Frame::Array) => return Ok(Some(self.close())),
+                        (None, _) => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+                        _ => return Err(self.error(ParseErrorKind::ExpectedCommaOrEnd)),
+                    }
+                }
+                State::FirstKeyOrEnd if self.peek() == Some(b'}') => {
+                    return Ok(Some(self.close()));
+                }
+                State::FirstKeyOrEnd | State::Key => {
+                    match self.peek() {
+                        Some(b'"') => {}
+                        None => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+                        _ => return Err(self.error(ParseErrorKind::ExpectedKey)),
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b':') => self.bump(),
+                        None => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+                        _ => return Err(self.error(ParseErrorKind::ExpectedColon)),
+                    };
+                    self.state = State::Value;
+                    return Ok(Some(Event::Key(key)));
+                }
+                State::FirstValueOrEnd if self.peek() == Some(b']') => {
+                    return Ok(Some(self.close()));
+                }
+                State::FirstValueOrEnd | State::Value => return self.value().map(Some),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Event<'a>, ParseError> {
+        let event = match self.peek() {
+            None => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+            Some(b'{') => return self.open(Frame::Object),
+            Some(b'[') => return self.open(Frame::Array),
+            Some(b'"') => Event::String(self.string()?),
+            Some(b't') => {
+                self.literal("true")?;
+                Event::Bool(true)
+            }
+            Some(b'f') => {
+                self.literal("false")?;
+                Event::Bool(false)
+            }
+            Some(b'n') => {
+                self.literal("null")?;
+                Event::Null
+            }
+            Some(b'-' | b'0'..=b'9') => Event::Number(self.number()?),
+            Some(_) => {
+                let c = self.src[self.pos..].chars().next().unwrap_or_default();
+                return Err(self.error(ParseErrorKind::UnexpectedCharacter(c)));
+            }
+        };
+        self.state = State::AfterValue;
+        Ok(event)
+    }
+
+    fn open(&mut self, frame: Frame) -> Result<Event<'a>, ParseError> {
+        if self.stack.len() >= self.max_depth {
+            return Err(self.error(ParseErrorKind::TooDeep));
+        }
+        self.bump();
+        self.stack.push(frame);
+        Ok(match frame {
+            Frame::Object => {
+                self.state = State::FirstKeyOrEnd;
+                Event::BeginObject
+            }
+            Frame::Array => {
+                self.state = State::FirstValueOrEnd;
+                Event::BeginArray
+            }
+        })
+    }
+
+    fn close(&mut self) -> Event<'a> {
+        self.bump();
+        self.state = State::AfterValue;
+        match self.stack.pop() {
+            Some(Frame::Object) => Event::EndObject,
+            _ => Event::EndArray,
+        }
+    }
+
+    fn literal(&mut self, word: &str) -> Result<(), ParseError> {
+        for expected in word.bytes() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(ParseErrorKind::InvalidLiteral));
+            }
+            self.bump();
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<Number, ParseError> {
+        let start = self.pos;
+        let (line, column) = (self.line, self.column);
+        let invalid = ParseError {
+            kind: ParseErrorKind::InvalidNumber,
+            line,
+            column,
+        };
+        let mut integral = true;
+
+        if self.peek() == Some(b'-') {
+            self.bump();
+        }
+        match self.peek() {
+            Some(b'0') => {
+                self.bump();
+            }
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(invalid),
+        }
+        if self.peek() == Some(b'.') {
+            integral = false;
+            self.bump();
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(invalid);
+            }
+            self.digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            integral = false;
+            self.bump();
+            if let Some(b'+' | b'-') = self.peek() {
+                self.bump();
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(invalid);
+            }
+            self.digits();
+        }
+
+        let text = &self.src[start..self.pos];
+        if integral {
+            // too big for an `i64` still parses, it just loses precision
+            if let Ok(int) = text.parse() {
+                return Ok(Number::Int(int));
+            }
+        }
+        // `1e999` would be infinity, which no JSON writer could give back
+        match text.parse::<f64>() {
+            Ok(float) if float.is_finite() => Ok(Number::Float(float)),
+            _ => Err(invalid),
+        }
+    }
+
+    fn digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Result<Cow<'a, str>, ParseError> {
+        // opening quote
+        self.bump();
+        let start = self.pos;
+        let mut owned: Option<String> = None;
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error(ParseErrorKind::UnexpectedEof));
+            };
+            match byte {
+                b'"' => {
+                    let end = self.pos;
+                    self.bump();
+                    return Ok(match owned {
+                        Some(owned) => Cow::Owned(owned),
+                        None => Cow::Borrowed(&self.src[start..end]),
+                    });
+                }
+                b'\\' => {
+                    let owned =
+                        owned.get_or_insert_with(|| String::from(&self.src[start..self.pos]));
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    let escaped = match self.bump() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape(line, column)?,
+                        None => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+                        Some(_) => {
+                            return Err(ParseError {
+                                kind: ParseErrorKind::InvalidEscape,
+                                line,
+                                column,
+                            })
+                        }
+                    };
+                    owned.push(escaped);
+                }
+                0..=0x1f => return Err(self.error(ParseErrorKind::ControlCharacterInString)),
+                _ => {
+                    let c = self.src[self.pos..].chars().next().unwrap_or_default();
+                    if let Some(owned) = &mut owned {
+                        owned.push(c);
+                    }
+                    for _ in 0..c.len_utf8() {
+                        self.bump();
+                    }
+                }
+            }
+        }
+    }
+
+    // the `\u` has already been consumed
+    fn unicode_escape(&mut self, line: usize, column: usize) -> Result<char, ParseError> {
+        let invalid = ParseError {
+            kind: ParseErrorKind::InvalidUnicodeEscape,
+            line,
+            column,
+        };
+        let high = self.hex4().ok_or(invalid)?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                // a high surrogate has to be followed by an escaped low surrogate
+                if self.bump() != Some(b'\\') || self.bump() != Some(b'u') {
+                    return Err(invalid);
+                }
+                let low = self.hex4().ok_or(invalid)?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(invalid);
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            }
+            0xdc00..=0xdfff => return Err(invalid),
+            code => code,
+        };
+        char::from_u32(code).ok_or(invalid)
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = (self.bump()? as char).to_digit(16)?;
+            code = code * 16 + digit;
+        }
+        Some(code)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if byte & 0xc0 != 0x80 {
+            // continuation bytes don't start a new column
+            self.column += 1;
+        }
+        Some(byte)
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! the LZ4 block format: literal runs and back references, no entropy coding, so it decompresses
+//! about as fast as memory can be copied. only single blocks, the frame format is not supported
+
+use alloc::vec::Vec;
+use core::fmt;
+
+const MIN_MATCH: usize = 4;
+/// the last match has to start at least this far from the end of the block
+const MF_LIMIT: usize = 12;
+/// and the last this many bytes are always literals
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 65535;
+const HASH_LOG: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lz4Error {
+    UnexpectedEof,
+    /// a back reference points before the start of the output, or is zero
+    InvalidOffset,
+    /// the output grew past the limit the caller allowed
+    TooLarge,
+}
+
+impl fmt::Display for Lz4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnexpectedEof => "compressed data ended early",
+            Self::InvalidOffset => "back reference points before the start of the output",
+            Self::TooLarge => "decompressed data is larger than allowed",
+        })
+    }
+}
+
+impl core::error::Error for Lz4Error {}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// the 255, 255, ..., rest encoding lengths of 15 and up continue in
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], offset: usize, match_len: usize) {
+    let extra = match_len - MIN_MATCH;
+    out.push(((literals.len().min(15) as u8) << 4) | extra.min(15) as u8);
+    if literals.len() >= 15 {
+        write_len(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    out.extend_from_slice(&(offset as u16).to_le_bytes());
+    if extra >= 15 {
+        write_len(out, extra - 15);
+    }
+}
+
+/// compresses `data` into one block, greedily taking the first match a small hash table finds
+pub fn compress_block(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    // positions plus one, so zero is empty
+    let mut table = alloc::vec![0u32; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + MF_LIMIT < data.len() {
+        let sequence = read_u32(data, i);
+        let slot = &mut table[hash(sequence)];
+        let candidate = *slot as usize;
+        *slot = i as u32 + 1;
+        if candidate != 0 {
+            let candidate = candidate - 1;
+            if i - candidate <= MAX_OFFSET && read_u32(data, candidate) == sequence {
+                let end = data.len() - LAST_LITERALS;
+                let mut len = MIN_MATCH;
+                while i + len < end && data[candidate + len] == data[i + len] {
+                    len += 1;
+                }
+                write_sequence(&mut out, &data[anchor..i], i - candidate, len);
+                i += len;
+                anchor = i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    let literals = &data[anchor..];
+    out.push((literals.len().min(15) as u8) << 4);
+    if literals.len() >= 15 {
+        write_len(&mut out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    out
+}
+
+fn read_len(data: &[u8], at: &mut usize) -> Result<usize, Lz4Error> {
+    let mut len = 0usize;
+    loop {
+        let byte = *data.get(*at).ok_or(Lz4Error::UnexpectedEof)?;
+        *at += 1;
+        len = len.checked_add(byte as usize).ok_or(Lz4Error::TooLarge)?;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// decompresses one block, failing once the output would grow past `limit` bytes
+pub fn decompress_block(data: &[u8], limit: usize) -> Result<Vec<u8>, Lz4Error> {
+    let mut out = Vec::new();
+    let mut at = 0;
+    loop {
+        let token = *data.get(at).ok_or(Lz4Error::UnexpectedEof)?;
+        at += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_len(data, &mut at)?;
+        }
+        let end = at.checked_add(literals).ok_or(Lz4Error::UnexpectedEof)?;
+        let literals = data.get(at..end).ok_or(Lz4Error::UnexpectedEof)?;
+        if out.len() + literals.len() > limit {
+            return Err(Lz4Error::TooLarge);
+        }
+        out.extend_from_slice(literals);
+        at = end;
+        if at == data.len() {
+            return Ok(out);
+        }
+
+        let offset = data.get(at..at + 2).ok_or(Lz4Error::UnexpectedEof)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        at += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(Lz4Error::InvalidOffset);
+        }
+        let mut len = (token & 15) as usize + MIN_MATCH;
+        if token & 15 == 15 {
+            len += read_len(data, &mut at)?;
+        }
+        if out.len() + len > limit {
+            return Err(Lz4Error::TooLarge);
+        }
+        // the match may overlap what it is copying, so byte by byte
+        let start = out.len() - offset;
+        for i in start..start + len {
+            out.push(out[i]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = b"hello hello hello hello, yage! hello hello hello hello, yage!";
+        let mut inputs: Vec<Vec<u8>> = alloc::vec![Vec::new(), b"tiny".to_vec(), text.to_vec()];
+        inputs.push((0..100_000u32).map(|i| (i % 251) as u8).collect());
+        inputs.push(alloc::vec![0; 70_000]);
+        for input in &inputs {
+            let compressed = compress_block(input);
+            assert_eq!(&decompress_block(&compressed, usize::MAX).unwrap(), input);
+        }
+        assert!(compress_block(&inputs[4]).len() < 500);
+        assert_eq!(
+            decompress_block(&compress_block(&inputs[4]), 1000),
+            Err(Lz4Error::TooLarge)
+        );
+    }
+
+    #[test]
+    fn known_block() {
+        // three literals, a 19 byte match that overlaps itself, then the last five literals
+        let block = [
+            0x3f, b'a', b'b', b'c', 3, 0, 0, 0x50, b'b', b'c', b'a', b'b', b'c',
+        ];
+        assert_eq!(
+            decompress_block(&block, usize::MAX).unwrap(),
+            b"abc".repeat(9)
+        );
+        assert_eq!(
+            decompress_block(&[0x1f, b'a', 2, 0], usize::MAX),
+            Err(Lz4Error::InvalidOffset)
+        );
+        assert_eq!(
+            decompress_block(&[0x50, b'a'], usize::MAX),
+            Err(Lz4Error::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! triangle meshes for `AssetKind::Mesh`
+
+use super::{AssetKind, DecoderRegistry};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+mod obj;
+
+pub use obj::{parse_mtl, MtlDecoder, ObjDecoder, ObjError, ObjErrorKind};
+
+/// an indexed triangle list. `normals` and `uvs` are either empty or exactly as long as
+/// `positions`; every three entries of `indices` make one triangle
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    /// consecutive runs of `indices` that share a material
+    pub groups: Vec<MaterialGroup>,
+    pub materials: Vec<Material>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialGroup {
+    /// index into `Mesh::materials`, `None` for faces before any material was selected
+    pub material: Option<usize>,
+    /// offset into `Mesh::indices`
+    pub start: u32,
+    /// number of indices, always a multiple of three
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl Mesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    pub fn has_uvs(&self) -> bool {
+        !self.uvs.is_empty()
+    }
+
+    /// interleaves one vertex, with zeroes for anything the mesh does not have
+    pub fn vertex(&self, index: usize) -> Vertex {
+        Vertex {
+            position: self.positions[index],
+            normal: self.normals.get(index).copied().unwrap_or_default(),
+            uv: self.uvs.get(index).copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0], tri[1], tri[2]])
+    }
+
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|m| m.name == name)
+    }
+
+    /// the smallest axis-aligned box around every position, `None` for an empty mesh
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let (first, rest) = self.positions.split_first()?;
+        Some(rest.iter().fold((*first, *first), |(mut min, mut max), p| {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+            (min, max)
+        }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    /// 1.0 is fully opaque
+    pub opacity: f32,
+    pub illumination: u32,
+    /// texture names, already resolved to asset names relative to the search paths
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub opacity_map: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            opacity_map: None,
+        }
+    }
+
+    /// every texture this material refers to
+    pub fn textures(&self) -> impl Iterator<Item = &str> {
+        [
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.normal_map,
+            &self.opacity_map,
+        ]
+        .into_iter()
+        .filter_map(Option::as_deref)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.obj` files (and their `.mtl` libraries) into a `Mesh`, and `.mtl` files on
+    /// their own into a `Vec<Material>`
+    pub fn with_meshes(mut self) -> Self {
+        self.register(AssetKind::Other, &["mtl"], MtlDecoder);
+        self.register(AssetKind::Mesh, &["obj"], ObjDecoder);
+        self
+    }
+}
@@ -0,0 +1,597 @@
+use super::{Material, MaterialGroup, Mesh};
+use crate::asset::{AssetDecoder, AssetKind, DecodeContext};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// decodes Wavefront OBJ. `mtllib` files are fetched relative to the OBJ itself
+pub struct ObjDecoder;
+
+impl AssetDecoder for ObjDecoder {
+    type Output = Mesh;
+    type Error = ObjError;
+
+    fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Result<Mesh, ObjError> {
+        let src = core::str::from_utf8(bytes)
+            .map_err(|_| ObjError::new(cx.name, 0, ObjErrorKind::InvalidUtf8))?;
+        parse_obj(cx, src)
+    }
+
+    /// every `mtllib`
+    fn dependencies(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+        let Ok(src) = core::str::from_utf8(bytes) else {
+            return Vec::new();
+        };
+        statements(src)
+            .filter_map(|(_, statement)| {
+                let mut args = statement.split_whitespace();
+                (args.next() == Some("mtllib"))
+                    .then(|| args.map(|library| cx.relative(library)).collect::<Vec<_>>())
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+/// decodes a material library on its own
+pub struct MtlDecoder;
+
+impl AssetDecoder for MtlDecoder {
+    type Output = Vec<Material>;
+    type Error = ObjError;
+
+    fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Result<Vec<Material>, ObjError> {
+        let src = core::str::from_utf8(bytes)
+            .map_err(|_| ObjError::new(cx.name, 0, ObjErrorKind::InvalidUtf8))?;
+        parse_mtl(src, cx.name)
+    }
+
+    /// every texture the materials use
+    fn dependencies(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+        let mut textures: Vec<String> = self
+            .decode(cx, bytes)
+            .iter()
+            .flatten()
+            .flat_map(|material| material.textures().map(String::from))
+            .collect();
+        textures.sort();
+        textures.dedup();
+        textures
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjErrorKind {
+    InvalidUtf8,
+    InvalidNumber,
+    /// a statement is missing one of its arguments
+    MissingArgument,
+    IndexOutOfRange,
+    /// faces need at least three corners
+    DegenerateFace,
+    MissingFile(String),
+    UnknownMaterial(String),
+}
+
+/// `line` is 1-based, 0 when the error is not about a particular line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub kind: ObjErrorKind,
+}
+
+impl ObjError {
+    fn new(file: &str, line: usize, kind: ObjErrorKind) -> Self {
+        Self {
+            file: file.into(),
+            line,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.line != 0 {
+            write!(f, ":{}", self.line)?;
+        }
+        match &self.kind {
+            ObjErrorKind::InvalidUtf8 => f.write_str(": invalid utf-8"),
+            ObjErrorKind::InvalidNumber => f.write_str(": invalid number"),
+            ObjErrorKind::MissingArgument => f.write_str(": missing argument"),
+            ObjErrorKind::IndexOutOfRange => f.write_str(": vertex index out of range"),
+            ObjErrorKind::DegenerateFace => f.write_str(": face has fewer than three vertices"),
+            ObjErrorKind::MissingFile(name) => write!(f, ": could not find `{name}`"),
+            ObjErrorKind::UnknownMaterial(name) => write!(f, ": unknown material `{name}`"),
+        }
+    }
+}
+
+impl core::error::Error for ObjError {}
+
+/// yields `(line number, statement)`, with comments stripped and `\` continuations joined
+fn statements(src: &str) -> impl Iterator<Item = (usize, Cow<'_, str>)> {
+    let mut lines = src.lines().enumerate();
+    core::iter::from_fn(move || loop {
+        let (index, line) = lines.next()?;
+        let mut statement = Cow::Borrowed(line);
+        while let Some(head) = statement.strip_suffix('\\') {
+            let mut joined = String::from(head);
+            if let Some((_, next)) = lines.next() {
+                joined.push_str(next);
+            }
+            statement = Cow::Owned(joined);
+        }
+        let statement = match statement.find('#') {
+            Some(comment) => match statement {
+                Cow::Borrowed(s) => Cow::Borrowed(&s[..comment]),
+                Cow::Owned(mut s) => {
+                    s.truncate(comment);
+                    Cow::Owned(s)
+                }
+            },
+            None => statement,
+        };
+        if !statement.trim().is_empty() {
+            return Some((index + 1, statement));
+        }
+    })
+}
+
+fn floats<const N: usize>(
+    args: &mut core::str::SplitWhitespace<'_>,
+    required: usize,
+    default: f32,
+) -> Result<[f32; N], ObjErrorKind> {
+    let mut out = [default; N];
+    for (i, slot) in out.iter_mut().enumerate() {
+        match args.next() {
+            Some(arg) => *slot = arg.parse().map_err(|_| ObjErrorKind::InvalidNumber)?,
+            None if i < required => return Err(ObjErrorKind::MissingArgument),
+            None => break,
+        }
+    }
+    Ok(out)
+}
+
+const NONE: u32 = u32::MAX;
+
+struct Builder {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    mesh: Mesh,
+    // (position, uv, normal) -> index into the output buffers
+    dedup: BTreeMap<(u32, u32, u32), u32>,
+    material: Option<usize>,
+    any_uv: bool,
+    any_normal: bool,
+}
+
+impl Builder {
+    /// turns one `v/vt/vn` reference (1-based, or negative from the end) into an output index
+    fn corner(&mut self, reference: &str) -> Result<u32, ObjErrorKind> {
+        let mut parts = reference.split('/');
+        let position = resolve(parts.next(), self.positions.len())?;
+        if position == NONE {
+            return Err(ObjErrorKind::MissingArgument);
+        }
+        let uv = resolve(parts.next(), self.uvs.len())?;
+        let normal = resolve(parts.next(), self.normals.len())?;
+
+        let key = (position, uv, normal);
+        if let Some(&index) = self.dedup.get(&key) {
+            return Ok(index);
+        }
+        let index =
+            u32::try_from(self.mesh.positions.len()).map_err(|_| ObjErrorKind::IndexOutOfRange)?;
+        self.mesh.positions.push(self.positions[position as usize]);
+        self.mesh.uvs.push(match uv {
+            NONE => [0.0; 2],
+            uv => {
+                self.any_uv = true;
+                self.uvs[uv as usize]
+            }
+        });
+        self.mesh.normals.push(match normal {
+            NONE => [0.0; 3],
+            normal => {
+                self.any_normal = true;
+                self.normals[normal as usize]
+            }
+        });
+        self.dedup.insert(key, index);
+        Ok(index)
+    }
+
+    fn face(&mut self, corners: &[u32]) {
+        let start = self.mesh.indices.len();
+        if corners.len() == 3 {
+            self.mesh.indices.extend_from_slice(corners);
+        } else {
+            let points: Vec<[f32; 3]> = corners
+                .iter()
+                .map(|&i| self.mesh.positions[i as usize])
+                .collect();
+            let mut triangles = Vec::new();
+            triangulate(&points, &mut triangles);
+            for triangle in triangles {
+                self.mesh
+                    .indices
+                    .extend(triangle.iter().map(|&corner| corners[corner]));
+            }
+        }
+        let added = (self.mesh.indices.len() - start) as u32;
+
+        match self.mesh.groups.last_mut() {
+            Some(group) if group.material == self.material => group.count += added,
+            _ => self.mesh.groups.push(MaterialGroup {
+                material: self.material,
+                start: start as u32,
+                count: added,
+            }),
+        }
+    }
+
+    fn finish(mut self) -> Mesh {
+        if !self.any_uv {
+            self.mesh.uvs.clear();
+        }
+        if !self.any_normal {
+            self.mesh.normals.clear();
+        }
+        self.mesh
+    }
+}
+
+fn resolve(part: Option<&str>, len: usize) -> Result<u32, ObjErrorKind> {
+    let part = match part {
+        None | Some("") => return Ok(NONE),
+        Some(part) => part,
+    };
+    let index: i64 = part.parse().map_err(|_| ObjErrorKind::InvalidNumber)?;
+    let resolved = match index {
+        0 => return Err(ObjErrorKind::IndexOutOfRange),
+        i if i > 0 => i - 1,
+        i => len as i64 + i,
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange);
+    }
+    Ok(resolved as u32)
+}
+
+fn parse_obj(cx: &DecodeContext<'_>, src: &str) -> Result<Mesh, ObjError> {
+    let mut builder = Builder {
+        positions: Vec::new(),
+        uvs: Vec::new(),
+        normals: Vec::new(),
+        mesh: Mesh::default(),
+        dedup: BTreeMap::new(),
+        material: None,
+        any_uv: false,
+        any_normal: false,
+    };
+    let mut corners = Vec::new();
+
+    for (line, statement) in statements(src) {
+        let error = |kind| ObjError::new(cx.name, line, kind);
+        let mut args = statement.split_whitespace();
+        match args.next() {
+            Some("v") => {
+                let [x, y, z, w] = floats::<4>(&mut args, 3, 1.0).map_err(error)?;
+                // rational vertices are rare, but dividing them out is cheap
+                let w = if w == 0.0 { 1.0 } else { w };
+                builder.positions.push([x / w, y / w, z / w]);
+            }
+            Some("vt") => {
+                let [u, v] = floats::<2>(&mut args, 1, 0.0).map_err(error)?;
+                builder.uvs.push([u, v]);
+            }
+            Some("vn") => {
+                let normal = floats::<3>(&mut args, 3, 0.0).map_err(error)?;
+                builder.normals.push(normal);
+            }
+            Some("f") => {
+                corners.clear();
+                for reference in args {
+                    corners.push(builder.corner(reference).map_err(error)?);
+                }
+                if corners.len() < 3 {
+                    return Err(error(ObjErrorKind::DegenerateFace));
+                }
+                builder.face(&corners);
+            }
+            Some("usemtl") => {
+                let name = statement.trim()["usemtl".len()..].trim();
+                if name.is_empty() {
+                    return Err(error(ObjErrorKind::MissingArgument));
+                }
+                let material = builder
+                    .mesh
+                    .materials
+                    .iter()
+                    .position(|m| m.name == name)
+                    .ok_or_else(|| error(ObjErrorKind::UnknownMaterial(name.into())))?;
+                builder.material = Some(material);
+            }
+            Some("mtllib") => {
+                for library in args {
+                    let name = cx.relative(library);
+                    let bytes = cx
+                        .fetch_relative(library)
+                        .ok_or_else(|| error(ObjErrorKind::MissingFile(name.clone())))?;
+                    let src = core::str::from_utf8(&bytes)
+                        .map_err(|_| ObjError::new(&name, 0, ObjErrorKind::InvalidUtf8))?;
+                    builder.mesh.materials.extend(parse_mtl(src, &name)?);
+                }
+            }
+            // objects, groups, smoothing groups, lines and points don't change the triangle list
+            _ => {}
+        }
+    }
+    Ok(builder.finish())
+}
+
+/// parses a material library. `name` is the library's own asset name, texture paths
+/// are resolved relative to it
+pub fn parse_mtl(src: &str, name: &str) -> Result<Vec<Material>, ObjError> {
+    let cx = DecodeContext::new(name, AssetKind::Other);
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (line, statement) in statements(src) {
+        let error = |kind| ObjError::new(name, line, kind);
+        let mut args = statement.split_whitespace();
+        let Some(keyword) = args.next() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            let material = statement.trim()["newmtl".len()..].trim();
+            if material.is_empty() {
+                return Err(error(ObjErrorKind::MissingArgument));
+            }
+            materials.push(Material::new(material.into()));
+            continue;
+        }
+        // anything before the first `newmtl` has nothing to apply to
+        let Some(material) = materials.last_mut() else {
+            continue;
+        };
+        match keyword {
+            "Ka" | "Kd" | "Ks" | "Ke" => {
+                let [r, g, b] = floats::<3>(&mut args, 1, f32::NAN).map_err(error)?;
+                // a single value is a grey
+                let color = if g.is_nan() { [r; 3] } else { [r, g, b] };
+                match keyword {
+                    "Ka" => material.ambient = color,
+                    "Kd" => material.diffuse = color,
+                    "Ks" => material.specular = color,
+                    _ => material.emissive = color,
+                }
+            }
+            "Ns" => material.shininess = floats::<1>(&mut args, 1, 0.0).map_err(error)?[0],
+            "d" => material.opacity = floats::<1>(&mut args, 1, 1.0).map_err(error)?[0],
+            "Tr" => material.opacity = 1.0 - floats::<1>(&mut args, 1, 0.0).map_err(error)?[0],
+            "illum" => {
+                material.illumination = args
+                    .next()
+                    .ok_or_else(|| error(ObjErrorKind::MissingArgument))?
+                    .parse()
+                    .map_err(|_| error(ObjErrorKind::InvalidNumber))?
+            }
+            "map_Kd" | "map_Ks" | "map_Bump" | "map_bump" | "bump" | "norm" | "map_d" => {
+                // options like `-s 1 1 1` come first, the file name is always last
+                let texture = args
+                    .last()
+                    .map(|file| cx.relative(file))
+                    .ok_or_else(|| error(ObjErrorKind::MissingArgument))?;
+                let slot = match keyword {
+                    "map_Kd" => &mut material.diffuse_map,
+                    "map_Ks" => &mut material.specular_map,
+                    "map_d" => &mut material.opacity_map,
+                    _ => &mut material.normal_map,
+                };
+                *slot = Some(texture);
+            }
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+/// ear-clips a planar polygon, falling back to a fan if it is too degenerate to clip.
+/// triangles keep the winding of the polygon
+fn triangulate(points: &[[f32; 3]], out: &mut Vec<[usize; 3]>) {
+    let n = points.len();
+    // newell's method, robust for non-planar and concave polygons
+    let mut normal = [0.0f32; 3];
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    // project onto the plane the polygon is most facing, picking axes that keep orientation
+    let dominant = (0..3)
+        .max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs()))
+        .unwrap_or(2);
+    let (u, v) = ((dominant + 1) % 3, (dominant + 2) % 3);
+    let flat: Vec<[f32; 2]> = points.iter().map(|p| [p[u], p[v]]).collect();
+    let sign = if normal[dominant] < 0.0 { -1.0 } else { 1.0 };
+
+    let cross = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| {
+        sign * ((b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0]))
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+            if cross(flat[a], flat[b], flat[c]) <= 0.0 {
+                return false;
+            }
+            !remaining.iter().any(|&other| {
+                other != a
+                    && other != b
+                    && other != c
+                    && cross(flat[a], flat[b], flat[other]) >= 0.0
+                    && cross(flat[b], flat[c], flat[other]) >= 0.0
+                    && cross(flat[c], flat[a], flat[other]) >= 0.0
+            })
+        });
+        match ear {
+            Some(i) => {
+                out.push([
+                    remaining[(i + m - 1) % m],
+                    remaining[i],
+                    remaining[(i + 1) % m],
+                ]);
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+    for i in 1..remaining.len() - 1 {
+        out.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{Bytes, Fetch};
+
+    struct Files(&'static [(&'static str, &'static str)]);
+
+    impl Fetch for Files {
+        fn fetch(&self, name: &str) -> Option<Bytes> {
+            self.0
+                .iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, contents)| Bytes::from(contents.as_bytes()))
+        }
+    }
+
+    const CUBE_FACE: &str = "
+mtllib ../materials/crate.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+usemtl wood
+f 1/1/1 2/2/1 3/3/1 4/4/1
+usemtl metal
+f -4/1/1 -2/3/1 -1/4/1
+";
+
+    const MATERIALS: &str = "
+newmtl wood
+Kd 0.5 0.25 0.1
+map_Kd -s 1 1 1 textures/wood.png
+newmtl metal
+Ks 1
+d 0.5
+";
+
+    fn decode(src: &str) -> Result<Mesh, ObjError> {
+        let files = Files(&[("materials/crate.mtl", MATERIALS)]);
+        let cx = DecodeContext::new("models/crate.obj", AssetKind::Mesh).with_fetch(&files);
+        ObjDecoder.decode(&cx, src.as_bytes())
+    }
+
+    #[test]
+    fn triangulates_and_dedups() {
+        let mesh = decode(CUBE_FACE).unwrap();
+        // the quad splits into two triangles, the last face reuses three of its corners
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 3);
+        assert!(mesh.has_uvs() && mesh.has_normals());
+        assert_eq!(&mesh.indices[6..], &[0, 2, 3]);
+        assert_eq!(
+            mesh.groups,
+            [
+                MaterialGroup {
+                    material: Some(0),
+                    start: 0,
+                    count: 6
+                },
+                MaterialGroup {
+                    material: Some(1),
+                    start: 6,
+                    count: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_materials() {
+        let mesh = decode(CUBE_FACE).unwrap();
+        let wood = mesh.material("wood").unwrap();
+        assert_eq!(wood.diffuse, [0.5, 0.25, 0.1]);
+        assert_eq!(
+            wood.diffuse_map.as_deref(),
+            Some("materials/textures/wood.png")
+        );
+        let metal = mesh.material("metal").unwrap();
+        assert_eq!(metal.specular, [1.0; 3]);
+        assert_eq!(metal.opacity, 0.5);
+
+        let cx = DecodeContext::new("models/crate.obj", AssetKind::Mesh);
+        let libraries = ObjDecoder.dependencies(&cx, CUBE_FACE.as_bytes());
+        assert_eq!(libraries, ["materials/crate.mtl"]);
+        let cx = DecodeContext::new(&libraries[0], AssetKind::Other);
+        let textures = MtlDecoder.dependencies(&cx, MATERIALS.as_bytes());
+        assert_eq!(textures, ["materials/textures/wood.png"]);
+    }
+
+    #[test]
+    fn clips_concave_polygons() {
+        // an L shape, which a fan from the first corner would get wrong
+        let src = "v 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nf 1 2 3 4 5 6\n";
+        let mesh = decode(src).unwrap();
+        assert_eq!(mesh.triangle_count(), 4);
+        let area: f32 = mesh
+            .triangles()
+            .map(|[a, b, c]| {
+                let (a, b, c) = (
+                    mesh.positions[a as usize],
+                    mesh.positions[b as usize],
+                    mesh.positions[c as usize],
+                );
+                ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])) / 2.0
+            })
+            .sum();
+        // every triangle keeps the counter-clockwise winding, and they cover the L exactly
+        assert_eq!(area, 3.0);
+    }
+
+    #[test]
+    fn reports_errors_with_lines() {
+        let error = decode("v 0 0 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!((error.line, error.kind), (2, ObjErrorKind::IndexOutOfRange));
+
+        let error = decode("mtllib nope.mtl\n").unwrap_err();
+        assert_eq!(
+            error.kind,
+            ObjErrorKind::MissingFile("models/nope.mtl".into())
+        );
+
+        let error = decode("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl stone\n").unwrap_err();
+        assert_eq!(error.kind, ObjErrorKind::UnknownMaterial("stone".into()));
+    }
+}
@@ -1,6 +1,41 @@
+use core::any::Any;
+use core::fmt;
 use core::future::Future;
+use core::marker::PhantomData;
 use core::num::NonZero;
 
+use alloc::sync::Arc;
+
+#[cfg(feature = "alloc")]
+pub mod bytes;
+pub mod checksum;
+#[cfg(feature = "alloc")]
+pub mod compress;
+pub mod decode;
+#[cfg(feature = "alloc")]
+pub mod deflate;
+pub mod hash;
+#[cfg(feature = "alloc")]
+pub mod font;
+#[cfg(feature = "alloc")]
+pub mod image;
+#[cfg(feature = "alloc")]
+pub mod json;
+#[cfg(feature = "alloc")]
+pub mod lz4;
+#[cfg(feature = "alloc")]
+pub mod mesh;
+#[cfg(feature = "alloc")]
+pub mod preload;
+#[cfg(feature = "alloc")]
+pub mod strings;
+#[cfg(feature = "alloc")]
+pub mod world;
+
+pub use bytes::{ByteSource, Bytes};
+pub use decode::{AssetDecoder, DecodeContext, DecodeError, DecoderRegistry, Fetch};
+pub use hash::ContentHash;
+
 pub trait Loader<I, N, C: Cache<NonZero<usize>>>: Sized {
     type Error;
 
@@ -11,7 +46,8 @@ pub trait Loader<I, N, C: Cache<NonZero<usize>>>: Sized {
 
     type InitFuture: Future<Output = Result<Self, Self::Error>>;
 
-    fn init(init: I) -> Self::InitFuture
+    /// `cache` is the one the app hands to the loader plugin, everything loaded goes in it
+    fn init(init: I, cache: Arc<C>) -> Self::InitFuture
     where
         Self: Sized;
 
@@ -40,32 +76,152 @@ macro_rules! bkey {
 }
 
 bkey! {
-  NonZero<usize> => Asset<alloc::boxed::Box<[u8]>>
+  NonZero<usize> => Asset<Bytes>
 }
 
 pub trait Cache<I: Key> {
-    fn lookup(&self, index: &I) -> Option<&I::Value>;
+    /// a shared copy of the entry, which stays valid if the entry is removed or reloaded
+    /// while it is held
+    fn lookup(&self, index: &I) -> Option<Arc<I::Value>>;
     fn insert(&self, index: &I, value: I::Value) -> bool;
     fn remove(&self, index: &I) -> Option<(I, I::Value)>;
 
+    /// whether the entry was swapped out by a hot reload this frame
+    fn changed(&self, index: &I) -> bool {
+        let _ = index;
+        false
+    }
+
     // clones an asset entry, should only be called by this crate (maybe?)
     fn clone_entry(&self, index: &I, token: crate::machine_cog::OnlyCalledByThisCrate) -> I;
+
+    /// like `clone_entry`, but `None` instead of a panic if the entry is gone. caches that can
+    /// lose entries from another thread should override this to check and clone in one go
+    fn try_clone_entry(
+        &self,
+        index: &I,
+        token: crate::machine_cog::OnlyCalledByThisCrate,
+    ) -> Option<I> {
+        self.lookup(index)?;
+        Some(self.clone_entry(index, token))
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum AssetKind {
     JsonBody,
     GameWorld,
     RawData,
     Mesh,
+    Image,
+    Font,
+    StringTable,
+    Preload,
     Other,
 }
 
+#[derive(Clone)]
 pub struct Asset<B> {
     pub kind: AssetKind,
-    pub(crate) gen: usize, // used so we can uniquely hash every single time
+    /// what is in `data`, so two assets with the same bytes can be told apart from two that
+    /// merely share a name. `None` if it was never worked out
+    pub(crate) hash: Option<ContentHash>,
     pub(crate) data: B,
+    // filled in by a `DecoderRegistry` when the asset is loaded
+    pub(crate) value: Option<decode::DecodedValue>,
+}
+
+impl<B: AsRef<[u8]>> Asset<B> {
+    /// hashes `data` as it goes, so expect it to cost a pass over the bytes
+    pub fn new(kind: AssetKind, data: B) -> Self {
+        Self {
+            kind,
+            hash: Some(ContentHash::of(data.as_ref())),
+            data,
+            value: None,
+        }
+    }
+
+    /// like `new`, but leaves the bytes alone. for data where a pass over it is too costly, like
+    /// a memory map the hash would page in whole
+    pub fn unhashed(kind: AssetKind, data: B) -> Self {
+        Self {
+            kind,
+            hash: None,
+            data,
+            value: None,
+        }
+    }
+}
+
+impl<B> Asset<B> {
+    pub fn hash(&self) -> Option<ContentHash> {
+        self.hash
+    }
+
+    pub fn data(&self) -> &B {
+        &self.data
+    }
+
+    /// sets the value handles hand out, for assets whose value doesn't come from a decoder
+    pub fn with_value<T: Any + Send + Sync>(mut self, value: T) -> Self {
+        self.value = Some(Arc::new(value));
+        self
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.value.is_some()
+    }
+
+    pub fn value(&self) -> Option<&(dyn Any + Send + Sync)> {
+        self.value.as_deref()
+    }
+
+    pub fn downcast<T: Any>(&self) -> Result<&T, AccessError> {
+        self.value
+            .as_deref()
+            .ok_or(AccessError::NotDecoded)?
+            .downcast_ref()
+            .ok_or(AccessError::TypeMismatch {
+                expected: core::any::type_name::<T>(),
+            })
+    }
+
+    /// like `downcast`, but the value can outlive this asset
+    pub fn downcast_arc<T: Any + Send + Sync>(&self) -> Result<Arc<T>, AccessError> {
+        let value = self.value.clone().ok_or(AccessError::NotDecoded)?;
+        value.downcast().map_err(|_| AccessError::TypeMismatch {
+            expected: core::any::type_name::<T>(),
+        })
+    }
+}
+
+/// why a handle could not hand out a typed value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessError {
+    /// the entry is no longer in the cache
+    Evicted,
+    /// no decoder ran for this asset, so only the raw bytes are available
+    NotDecoded,
+    TypeMismatch {
+        expected: &'static str,
+    },
 }
 
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Evicted => f.write_str("the asset has been evicted from the cache"),
+            Self::NotDecoded => f.write_str("the asset was never decoded"),
+            Self::TypeMismatch { expected } => {
+                write!(f, "the asset does not hold a value of type `{expected}`")
+            }
+        }
+    }
+}
+
+impl core::error::Error for AccessError {}
+
 pub struct OwnedHandle<L: Cache<NonZero<usize>>> {
     index: NonZero<usize>,
     #[cfg(feature = "alloc")]
@@ -82,6 +238,14 @@ pub struct BorrowedHandle<'a, L> {
     _capture: core::marker::PhantomData<&'a L>,
 }
 
+/// doesn't keep its entry, or the cache, alive, so it can sit in long-lived state without
+/// deciding when an asset goes away. `upgrade` to get at the asset
+#[cfg(feature = "alloc")]
+pub struct WeakHandle<L> {
+    index: NonZero<usize>,
+    cache: alloc::sync::Weak<L>,
+}
+
 impl<L> Copy for BorrowedHandle<'_, L> {}
 
 impl<L> Clone for BorrowedHandle<'_, L> {
@@ -91,10 +255,88 @@ impl<L> Clone for BorrowedHandle<'_, L> {
 }
 
 #[cfg(feature = "alloc")]
-impl<L> BorrowedHandle<'_, L>
+impl<L> OwnedHandle<L>
+where
+    L: Cache<NonZero<usize>>,
+{
+    /// takes ownership of the entry at `index`, which gets removed from `cache` when this is dropped
+    pub fn new(index: NonZero<usize>, cache: alloc::sync::Arc<L>) -> Self {
+        Self { index, cache }
+    }
+
+    pub fn index(&self) -> NonZero<usize> {
+        self.index
+    }
+
+    pub fn asset(&self) -> Option<Arc<Asset<Bytes>>> {
+        self.cache.lookup(&self.index)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Result<Arc<T>, AccessError> {
+        self.asset().ok_or(AccessError::Evicted)?.downcast_arc()
+    }
+
+    /// true for the frame after the file behind this asset was reloaded
+    pub fn changed(&self) -> bool {
+        self.cache.changed(&self.index)
+    }
+
+    pub fn borrow(&self) -> BorrowedHandle<'_, L> {
+        BorrowedHandle {
+            index: self.index,
+            cache: &self.cache,
+        }
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<L> {
+        self.borrow().downgrade()
+    }
+
+    /// checks the decoded value is a `T` once, so `Handle::get` only fails on eviction.
+    /// gives the handle back untouched if it is not
+    pub fn typed<T: Any + Send + Sync>(self) -> Result<Handle<T, L>, Self> {
+        match self.get::<T>() {
+            Ok(_) => Ok(Handle {
+                inner: self,
+                _marker: PhantomData,
+            }),
+            Err(_) => Err(self),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, L> BorrowedHandle<'a, L>
 where
     L: Cache<NonZero<usize>>,
 {
+    pub fn new(index: NonZero<usize>, cache: &'a alloc::sync::Arc<L>) -> Self {
+        Self { index, cache }
+    }
+
+    pub fn index(&self) -> NonZero<usize> {
+        self.index
+    }
+
+    pub fn asset(&self) -> Option<Arc<Asset<Bytes>>> {
+        self.cache.lookup(&self.index)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Result<Arc<T>, AccessError> {
+        self.asset().ok_or(AccessError::Evicted)?.downcast_arc()
+    }
+
+    pub fn changed(&self) -> bool {
+        self.cache.changed(&self.index)
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<L> {
+        WeakHandle {
+            index: self.index,
+            cache: alloc::sync::Arc::downgrade(self.cache),
+        }
+    }
+
     pub fn to_owned_handle(self) -> OwnedHandle<L> {
         let token = crate::token!();
         let new_index = self.cache.clone_entry(&self.index, token);
@@ -105,6 +347,41 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<L> WeakHandle<L>
+where
+    L: Cache<NonZero<usize>>,
+{
+    pub fn index(&self) -> NonZero<usize> {
+        self.index
+    }
+
+    /// an owned handle to a copy of the entry, the same way `CowHandle::into_owned` makes one.
+    /// `None` once the entry was evicted or the cache dropped
+    pub fn upgrade(&self) -> Option<OwnedHandle<L>> {
+        let cache = self.cache.upgrade()?;
+        let index = cache.try_clone_entry(&self.index, crate::token!())?;
+        Some(OwnedHandle { index, cache })
+    }
+
+    /// whether `upgrade` would find the entry right now
+    pub fn is_alive(&self) -> bool {
+        self.cache
+            .upgrade()
+            .is_some_and(|cache| cache.lookup(&self.index).is_some())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<L> Clone for WeakHandle<L> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
 #[cfg(feature = "alloc")]
 impl<L> Drop for OwnedHandle<L>
 where
@@ -122,3 +399,73 @@ pub enum CowHandle<'a, C: Cache<NonZero<usize>>> {
     Borrowed(BorrowedHandle<'a, C>),
     Owned(OwnedHandle<C>),
 }
+
+impl<C: Cache<NonZero<usize>>> CowHandle<'_, C> {
+    pub fn index(&self) -> NonZero<usize> {
+        match self {
+            Self::Borrowed(handle) => handle.index(),
+            Self::Owned(handle) => handle.index(),
+        }
+    }
+
+    pub fn asset(&self) -> Option<Arc<Asset<Bytes>>> {
+        match self {
+            Self::Borrowed(handle) => handle.asset(),
+            Self::Owned(handle) => handle.asset(),
+        }
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Result<Arc<T>, AccessError> {
+        self.asset().ok_or(AccessError::Evicted)?.downcast_arc()
+    }
+
+    pub fn changed(&self) -> bool {
+        match self {
+            Self::Borrowed(handle) => handle.changed(),
+            Self::Owned(handle) => handle.changed(),
+        }
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<C> {
+        match self {
+            Self::Borrowed(handle) => handle.downgrade(),
+            Self::Owned(handle) => handle.downgrade(),
+        }
+    }
+
+    pub fn into_owned(self) -> OwnedHandle<C> {
+        match self {
+            Self::Borrowed(handle) => handle.to_owned_handle(),
+            Self::Owned(handle) => handle,
+        }
+    }
+}
+
+/// an `OwnedHandle` that has already been checked to hold a `T`
+pub struct Handle<T, L: Cache<NonZero<usize>>> {
+    inner: OwnedHandle<L>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Any + Send + Sync, L: Cache<NonZero<usize>>> Handle<T, L> {
+    pub fn get(&self) -> Result<Arc<T>, AccessError> {
+        self.inner.get()
+    }
+
+    pub fn changed(&self) -> bool {
+        self.inner.changed()
+    }
+
+    /// the weak handle is untyped, check again after upgrading
+    pub fn downgrade(&self) -> WeakHandle<L> {
+        self.inner.downgrade()
+    }
+
+    pub fn untyped(&self) -> &OwnedHandle<L> {
+        &self.inner
+    }
+
+    pub fn into_untyped(self) -> OwnedHandle<L> {
+        self.inner
+    }
+}
@@ -0,0 +1,248 @@
+//! preload manifests for `AssetKind::Preload`: named groups of assets to load together, like
+//! everything one level needs. a `.preload` file looks like
+//!
+//! ```text
+//! # comments start with `#`
+//! [common]
+//! ui/font.png          priority=10 tags=ui
+//! text/ui.strings      tags=ui,text
+//!
+//! [forest]
+//! levels/forest.world  priority=5
+//! ```
+//!
+//! paths are relative to the manifest and can't have spaces in them. higher priorities load
+//! first, the default is 0
+
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreloadManifest {
+    pub groups: Vec<Group>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub entries: Vec<PreloadEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreloadEntry {
+    pub path: String,
+    pub priority: i32,
+    pub tags: Vec<String>,
+}
+
+impl PreloadManifest {
+    pub fn parse(src: &str, file: &str) -> Result<Self, PreloadError> {
+        let mut manifest = Self::default();
+        for (index, line) in src.lines().enumerate() {
+            let at = |kind| PreloadError::new(file, index + 1, kind);
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                continue;
+            };
+            if let Some(name) = first.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .filter(|name| !name.is_empty() && words.next().is_none())
+                    .ok_or_else(|| at(PreloadErrorKind::InvalidGroup))?;
+                if manifest.group(name).is_some() {
+                    return Err(at(PreloadErrorKind::DuplicateGroup(name.into())));
+                }
+                manifest.groups.push(Group {
+                    name: name.into(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            let group = manifest
+                .groups
+                .last_mut()
+                .ok_or_else(|| at(PreloadErrorKind::OutsideGroup))?;
+            let mut entry = PreloadEntry {
+                path: first.into(),
+                priority: 0,
+                tags: Vec::new(),
+            };
+            for option in words {
+                match option.split_once('=') {
+                    Some(("priority", priority)) => {
+                        entry.priority = priority
+                            .parse()
+                            .map_err(|_| at(PreloadErrorKind::InvalidPriority))?;
+                    }
+                    Some(("tags", tags)) => entry.tags.extend(
+                        tags.split(',')
+                            .filter(|tag| !tag.is_empty())
+                            .map(String::from),
+                    ),
+                    _ => return Err(at(PreloadErrorKind::UnknownOption(option.into()))),
+                }
+            }
+            group.entries.push(entry);
+        }
+        Ok(manifest)
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// rewrites every path, e.g. to resolve them against the manifest's directory
+    pub fn resolve(&mut self, mut resolve: impl FnMut(&str) -> String) {
+        let entries = self.groups.iter_mut().flat_map(|group| &mut group.entries);
+        for entry in entries {
+            entry.path = resolve(&entry.path);
+        }
+    }
+}
+
+impl Group {
+    /// highest priority first, entries with the same priority in the order they were written
+    pub fn by_priority(&self) -> Vec<&PreloadEntry> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| core::cmp::Reverse(entry.priority));
+        entries
+    }
+
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a PreloadEntry> {
+        self.entries.iter().filter(move |entry| entry.has_tag(tag))
+    }
+}
+
+impl PreloadEntry {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreloadErrorKind {
+    InvalidUtf8,
+    /// a `[group]` line that is empty or has something after it
+    InvalidGroup,
+    DuplicateGroup(String),
+    /// an asset before the first group
+    OutsideGroup,
+    InvalidPriority,
+    UnknownOption(String),
+}
+
+/// `line` is 1-based, 0 when the error is not about a particular line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreloadError {
+    pub file: String,
+    pub line: usize,
+    pub kind: PreloadErrorKind,
+}
+
+impl PreloadError {
+    fn new(file: &str, line: usize, kind: PreloadErrorKind) -> Self {
+        Self {
+            file: file.into(),
+            line,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for PreloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.line != 0 {
+            write!(f, ":{}", self.line)?;
+        }
+        match &self.kind {
+            PreloadErrorKind::InvalidUtf8 => f.write_str(": invalid utf-8"),
+            PreloadErrorKind::InvalidGroup => f.write_str(": expected `[group name]`"),
+            PreloadErrorKind::DuplicateGroup(name) => write!(f, ": group `{name}` twice"),
+            PreloadErrorKind::OutsideGroup => f.write_str(": asset outside of any group"),
+            PreloadErrorKind::InvalidPriority => f.write_str(": invalid priority"),
+            PreloadErrorKind::UnknownOption(option) => write!(f, ": unknown option `{option}`"),
+        }
+    }
+}
+
+impl core::error::Error for PreloadError {}
+
+/// decodes a manifest, resolving its paths relative to it
+pub struct PreloadDecoder;
+
+impl AssetDecoder for PreloadDecoder {
+    type Output = PreloadManifest;
+    type Error = PreloadError;
+
+    fn decode(
+        &self,
+        cx: &DecodeContext<'_>,
+        bytes: &[u8],
+    ) -> Result<PreloadManifest, PreloadError> {
+        let src = core::str::from_utf8(bytes)
+            .map_err(|_| PreloadError::new(cx.name, 0, PreloadErrorKind::InvalidUtf8))?;
+        let mut manifest = PreloadManifest::parse(src, cx.name)?;
+        manifest.resolve(|path| cx.relative(path));
+        Ok(manifest)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.preload` files into a `PreloadManifest`
+    pub fn with_preload(mut self) -> Self {
+        self.register(AssetKind::Preload, &["preload"], PreloadDecoder);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_in_priority_order() {
+        let src = "
+# loaded for every level
+[common]
+ui/font.png  priority=10 tags=ui
+text/ui.strings tags=ui,text
+../shared/click.wav priority=10
+
+[forest]
+levels/forest.world priority=-1
+";
+        let cx = DecodeContext::new("data/game.preload", AssetKind::Preload);
+        let manifest = PreloadDecoder.decode(&cx, src.as_bytes()).unwrap();
+        let common = manifest.group("common").unwrap();
+        let order: Vec<_> = common.by_priority().iter().map(|e| &*e.path).collect();
+        assert_eq!(
+            order,
+            [
+                "data/ui/font.png",
+                "shared/click.wav",
+                "data/text/ui.strings"
+            ]
+        );
+        assert_eq!(common.tagged("text").count(), 1);
+        assert_eq!(manifest.group("forest").unwrap().entries[0].priority, -1);
+
+        let error = |src: &str| PreloadManifest::parse(src, "x").unwrap_err().kind;
+        assert_eq!(error("a.png"), PreloadErrorKind::OutsideGroup);
+        assert_eq!(
+            error("[a]\n[a]"),
+            PreloadErrorKind::DuplicateGroup("a".into())
+        );
+        assert_eq!(
+            error("[a]\nb.png size=3"),
+            PreloadErrorKind::UnknownOption("size=3".into())
+        );
+    }
+}
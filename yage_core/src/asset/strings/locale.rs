@@ -0,0 +1,71 @@
+use alloc::string::String;
+use core::fmt;
+
+/// a BCP 47 style language tag: a language, then optionally a script and a region
+/// (`en`, `pt-BR`, `zh-Hant-TW`). tags are normalized when parsed, so `pt_br` and `pt-BR` are
+/// the same locale
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Locale {
+    tag: String,
+}
+
+impl Locale {
+    /// `None` unless every subtag is 1 to 8 ascii letters or digits, and the first is letters
+    pub fn parse(tag: &str) -> Option<Self> {
+        let mut normalized = String::with_capacity(tag.len());
+        for (i, subtag) in tag.split(['-', '_']).enumerate() {
+            let valid = (1..=8).contains(&subtag.len())
+                && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+                && (i > 0 || subtag.bytes().all(|b| b.is_ascii_alphabetic()));
+            if !valid {
+                return None;
+            }
+            if i > 0 {
+                normalized.push('-');
+            }
+            match (i, subtag.len()) {
+                (0, _) => normalized.extend(subtag.chars().map(|c| c.to_ascii_lowercase())),
+                // scripts are title case, regions upper case
+                (_, 4) => {
+                    let (first, rest) = subtag.split_at(1);
+                    normalized.push_str(&first.to_ascii_uppercase());
+                    normalized.push_str(&rest.to_ascii_lowercase());
+                }
+                (_, 2) => normalized.push_str(&subtag.to_ascii_uppercase()),
+                _ => normalized.push_str(&subtag.to_ascii_lowercase()),
+            }
+        }
+        Some(Self { tag: normalized })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn language(&self) -> &str {
+        self.tag.split('-').next().unwrap_or_default()
+    }
+
+    /// this tag, then shorter and shorter ones: `zh-Hant-TW`, `zh-Hant`, `zh`
+    pub fn fallbacks(&self) -> impl Iterator<Item = &str> {
+        let mut rest = Some(self.tag.as_str());
+        core::iter::from_fn(move || {
+            let tag = rest?;
+            rest = tag.rsplit_once('-').map(|(shorter, _)| shorter);
+            Some(tag)
+        })
+    }
+}
+
+/// english, which string tables without an `@locale` line are assumed to be in
+impl Default for Locale {
+    fn default() -> Self {
+        Self { tag: "en".into() }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
@@ -0,0 +1,421 @@
+//! localized text for `AssetKind::StringTable`.
+//!
+//! a `.strings` file is one `key = value` per line, `#` lines are comments. plural forms go in
+//! brackets after the key (`apples[one] = {count} apple`), `{name}` is replaced by the argument
+//! called `name`, `{{` and `}}` are literal braces and `\n`, `\t` and `\\` are escapes. an
+//! `@locale pt-BR` line says what language the file is in
+//!
+//! the file an asset is loaded from is the last fallback, translations of it live next to it in
+//! a directory per locale. loading `text/ui.strings` in `pt-BR` looks for `text/pt-BR/ui.strings`,
+//! then `text/pt/ui.strings`, and each key comes from the first of those (or `text/ui.strings`
+//! itself) that has it
+
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+mod locale;
+mod plural;
+
+pub use locale::Locale;
+pub use plural::{Operands, PluralCategory};
+
+/// every message of one string table, in the locale it was loaded for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringTable {
+    locale: Locale,
+    messages: BTreeMap<String, Message>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Message {
+    /// the locale of the file the message came from, which picks the plural rules
+    locale: Locale,
+    /// `None` is the form without a plural category
+    forms: BTreeMap<Option<PluralCategory>, String>,
+}
+
+/// an argument to interpolate, `{key}` is replaced by `value`
+pub type Arg<'a> = (&'a str, &'a dyn fmt::Display);
+
+impl StringTable {
+    /// parses one `.strings` file. `locale` is what it is in unless it says otherwise
+    pub fn parse(src: &str, file: &str, locale: Locale) -> Result<Self, StringsError> {
+        let mut table = Self {
+            locale,
+            messages: BTreeMap::new(),
+        };
+        let mut entries = Vec::new();
+        for (index, line) in src.lines().enumerate() {
+            let at = |kind| StringsError::new(file, index + 1, kind);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(directive) = line.strip_prefix('@') {
+                let (name, value) = directive.split_once(' ').unwrap_or((directive, ""));
+                match name {
+                    "locale" => {
+                        table.locale = Locale::parse(value.trim())
+                            .ok_or_else(|| at(StringsErrorKind::InvalidLocale(value.into())))?;
+                    }
+                    name => return Err(at(StringsErrorKind::UnknownDirective(name.into()))),
+                }
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| at(StringsErrorKind::MissingEquals))?;
+            let (key, category) = match key.trim().split_once('[') {
+                Some((key, category)) => {
+                    let category = category.strip_suffix(']').unwrap_or(category);
+                    let category = PluralCategory::from_name(category.trim())
+                        .ok_or_else(|| at(StringsErrorKind::UnknownCategory(category.into())))?;
+                    (key.trim(), Some(category))
+                }
+                None => (key.trim(), None),
+            };
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(at(StringsErrorKind::InvalidKey));
+            }
+            let value = unescape(value.trim()).map_err(at)?;
+            entries.push((index + 1, key, category, value));
+        }
+        // the locale line may come after the messages, so they get theirs at the end
+        for (line, key, category, value) in entries {
+            let message = table.messages.entry(key.into()).or_insert_with(|| Message {
+                locale: table.locale.clone(),
+                forms: BTreeMap::new(),
+            });
+            if message.forms.insert(category, value).is_some() {
+                let kind = StringsErrorKind::DuplicateKey(key.into());
+                return Err(StringsError::new(file, line, kind));
+            }
+        }
+        Ok(table)
+    }
+
+    /// the locale that was asked for, which some messages may have fallen back from
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.messages.contains_key(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.messages.keys().map(String::as_str)
+    }
+
+    /// the message as written, without a plural category or else its `other` form
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let forms = &self.messages.get(key)?.forms;
+        forms
+            .get(&None)
+            .or_else(|| forms.get(&Some(PluralCategory::Other)))
+            .map(String::as_str)
+    }
+
+    /// the message with `args` filled in. a missing key comes back as the key itself, so it
+    /// shows up on screen rather than as a blank
+    pub fn format(&self, key: &str, args: &[Arg<'_>]) -> String {
+        match self.get(key) {
+            Some(message) => interpolate(message, args),
+            None => key.into(),
+        }
+    }
+
+    /// the form of the message for `count` in the language it is written in, with `args` and
+    /// `{count}` filled in. falls back to `other`, then to the form without a category
+    pub fn plural<N>(&self, key: &str, count: N, args: &[Arg<'_>]) -> String
+    where
+        N: Into<Operands> + fmt::Display + Copy,
+    {
+        let Some(message) = self.messages.get(key) else {
+            return key.into();
+        };
+        let category = PluralCategory::of(&message.locale, count);
+        let form = [Some(category), Some(PluralCategory::Other), None]
+            .iter()
+            .find_map(|category| message.forms.get(category));
+        let Some(form) = form else {
+            return key.into();
+        };
+        let mut all = Vec::with_capacity(args.len() + 1);
+        all.extend_from_slice(args);
+        all.push(("count", &count as &dyn fmt::Display));
+        interpolate(form, &all)
+    }
+
+    /// adds every message of `fallback` this table doesn't have
+    pub fn fall_back_to(&mut self, fallback: StringTable) {
+        for (key, message) in fallback.messages {
+            self.messages.entry(key).or_insert(message);
+        }
+    }
+}
+
+fn unescape(value: &str) -> Result<String, StringsErrorKind> {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('\\') => '\\',
+            _ => return Err(StringsErrorKind::InvalidEscape),
+        });
+    }
+    Ok(out)
+}
+
+/// replaces `{name}` with the argument called `name`. unknown names and unclosed braces are
+/// left as they are
+pub fn interpolate(message: &str, args: &[Arg<'_>]) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(brace) = rest.find(['{', '}']) {
+        out.push_str(&rest[..brace]);
+        rest = &rest[brace..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let arg = rest
+            .find('}')
+            .filter(|_| rest.starts_with('{'))
+            .and_then(|end| {
+                let name = &rest[1..end];
+                let (_, value) = args.iter().find(|(key, _)| *key == name)?;
+                Some((end, value))
+            });
+        match arg {
+            Some((end, value)) => {
+                let _ = write!(out, "{value}");
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringsErrorKind {
+    InvalidUtf8,
+    MissingEquals,
+    /// keys can't be empty or have spaces in them
+    InvalidKey,
+    UnknownCategory(String),
+    InvalidEscape,
+    InvalidLocale(String),
+    UnknownDirective(String),
+    /// the same key, with the same plural category, twice in one file
+    DuplicateKey(String),
+}
+
+/// `line` is 1-based, 0 when the error is not about a particular line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringsError {
+    pub file: String,
+    pub line: usize,
+    pub kind: StringsErrorKind,
+}
+
+impl StringsError {
+    fn new(file: &str, line: usize, kind: StringsErrorKind) -> Self {
+        Self {
+            file: file.into(),
+            line,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for StringsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.line != 0 {
+            write!(f, ":{}", self.line)?;
+        }
+        match &self.kind {
+            StringsErrorKind::InvalidUtf8 => f.write_str(": invalid utf-8"),
+            StringsErrorKind::MissingEquals => f.write_str(": expected `key = value`"),
+            StringsErrorKind::InvalidKey => f.write_str(": keys can't be empty or have spaces"),
+            StringsErrorKind::UnknownCategory(name) => {
+                write!(f, ": unknown plural category `{name}`")
+            }
+            StringsErrorKind::InvalidEscape => f.write_str(": invalid escape"),
+            StringsErrorKind::InvalidLocale(tag) => write!(f, ": invalid locale `{tag}`"),
+            StringsErrorKind::UnknownDirective(name) => write!(f, ": unknown directive `@{name}`"),
+            StringsErrorKind::DuplicateKey(key) => write!(f, ": `{key}` is defined twice"),
+        }
+    }
+}
+
+impl core::error::Error for StringsError {}
+
+/// decodes a string table in whatever locale `locale` returns at the time, fetching the
+/// translations along its fallback chain. reloading a table is how it switches locale
+pub struct StringTableDecoder<F> {
+    locale: F,
+}
+
+impl<F: Fn() -> Locale + Send + Sync + 'static> StringTableDecoder<F> {
+    pub fn new(locale: F) -> Self {
+        Self { locale }
+    }
+}
+
+fn parse_file(bytes: &[u8], file: &str, locale: Locale) -> Result<StringTable, StringsError> {
+    let src = core::str::from_utf8(bytes)
+        .map_err(|_| StringsError::new(file, 0, StringsErrorKind::InvalidUtf8))?;
+    StringTable::parse(src, file, locale)
+}
+
+impl<F: Fn() -> Locale + Send + Sync + 'static> AssetDecoder for StringTableDecoder<F> {
+    type Output = StringTable;
+    type Error = StringsError;
+
+    fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Result<StringTable, StringsError> {
+        let locale = (self.locale)();
+        let file = cx.name.rsplit('/').next().unwrap_or(cx.name);
+        let mut table: Option<StringTable> = None;
+        for tag in locale.fallbacks() {
+            let path = format!("{tag}/{file}");
+            let Some(bytes) = cx.fetch_relative(&path) else {
+                continue;
+            };
+            // `fallbacks` only yields tags that parse
+            let tag = Locale::parse(tag).unwrap_or_default();
+            let found = parse_file(&bytes, &cx.relative(&path), tag)?;
+            match &mut table {
+                Some(table) => table.fall_back_to(found),
+                None => table = Some(found),
+            }
+        }
+        let base = parse_file(bytes, cx.name, Locale::default())?;
+        let mut table = match table {
+            Some(mut table) => {
+                table.fall_back_to(base);
+                table
+            }
+            None => base,
+        };
+        table.locale = locale;
+        Ok(table)
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.strings` files into a `StringTable` in `locale`, which never changes. loaders
+    /// that switch locales at runtime register a `StringTableDecoder` of their own
+    pub fn with_strings(mut self, locale: Locale) -> Self {
+        self.register(
+            AssetKind::StringTable,
+            &["strings"],
+            StringTableDecoder::new(move || locale.clone()),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{Bytes, Fetch};
+
+    struct Files(&'static [(&'static str, &'static str)]);
+
+    impl Fetch for Files {
+        fn fetch(&self, name: &str) -> Option<Bytes> {
+            let (_, src) = self.0.iter().find(|(file, _)| *file == name)?;
+            Some(Bytes::from(src.as_bytes()))
+        }
+    }
+
+    const BASE: &str = "
+# the fallback for everything
+title = Yage
+greeting = Hello, {name}!
+apples[one] = {count} apple
+apples[other] = {count} apples
+braces = {{not an arg}}
+";
+
+    #[test]
+    fn falls_back_along_the_chain() {
+        let files = Files(&[
+            ("text/pt/ui.strings", "greeting = Olá, {name}!\n"),
+            (
+                "text/pt-BR/ui.strings",
+                "@locale pt-BR\napples[one] = {count} maçã\napples[other] = {count} maçãs",
+            ),
+        ]);
+        let cx = DecodeContext::new("text/ui.strings", AssetKind::StringTable).with_fetch(&files);
+        let pt_br = Locale::parse("pt_br").unwrap();
+        let decoder = StringTableDecoder::new(move || pt_br.clone());
+        let table = decoder.decode(&cx, BASE.as_bytes()).unwrap();
+
+        assert_eq!(table.locale().as_str(), "pt-BR");
+        assert_eq!(table.get("title"), Some("Yage"));
+        assert_eq!(table.format("greeting", &[("name", &"Ana")]), "Olá, Ana!");
+        // portuguese counts zero as one, english wouldn't
+        assert_eq!(table.plural("apples", 0, &[]), "0 maçã");
+        assert_eq!(table.plural("apples", 2, &[]), "2 maçãs");
+        assert_eq!(table.format("braces", &[]), "{not an arg}");
+        assert_eq!(table.format("missing.key", &[]), "missing.key");
+        assert_eq!(
+            table.format("greeting", &[]),
+            "Olá, {name}!",
+            "unknown arguments are left alone"
+        );
+
+        let en = StringTableDecoder::new(Locale::default)
+            .decode(&cx, BASE.as_bytes())
+            .unwrap();
+        assert_eq!(en.plural("apples", 0, &[]), "0 apples");
+        assert_eq!(en.plural("apples", 1.5, &[]), "1.5 apples");
+        assert_eq!(en.plural("apples", 1, &[]), "1 apple");
+    }
+
+    #[test]
+    fn plural_rules() {
+        use PluralCategory::*;
+        let of = |tag: &str, n: i64| PluralCategory::of(&Locale::parse(tag).unwrap(), n);
+        let ru: Vec<_> = [1, 2, 5, 11, 21, 22, 25, 111].map(|n| of("ru", n)).into();
+        assert_eq!(ru, [One, Few, Many, Many, One, Few, Many, Many]);
+        let pl: Vec<_> = [1, 2, 5, 12, 21, 22].map(|n| of("pl", n)).into();
+        assert_eq!(pl, [One, Few, Many, Many, Many, Few]);
+        let ar: Vec<_> = [0, 1, 2, 3, 11, 100, 103].map(|n| of("ar", n)).into();
+        assert_eq!(ar, [Zero, One, Two, Few, Many, Other, Few]);
+        assert_eq!(
+            (of("fr", 0), of("pt-PT", 0), of("ja", 1)),
+            (One, Other, Other)
+        );
+        let en = Locale::default();
+        assert_eq!(PluralCategory::of(&en, 1.5), Other);
+        assert_eq!(PluralCategory::of(&Locale::parse("cs").unwrap(), 1.5), Many);
+
+        let zh = Locale::parse("ZH-hant-tw").unwrap();
+        assert_eq!(
+            zh.fallbacks().collect::<Vec<_>>(),
+            ["zh-Hant-TW", "zh-Hant", "zh"]
+        );
+        assert_eq!(Locale::parse("1x"), None);
+        assert_eq!(Locale::parse("en--US"), None);
+    }
+}
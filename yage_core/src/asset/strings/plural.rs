@@ -0,0 +1,171 @@
+//! CLDR cardinal plural rules for the languages games most often ship in. anything else only has
+//! `other`, which is right for languages without plurals and readable for the rest
+
+use super::Locale;
+use alloc::format;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+/// the parts of a number CLDR rules look at: its absolute value `n`, integer digits `i`, the
+/// number of visible fraction digits `v` and those digits `f`. `1` and `1.0` differ in `v`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Operands {
+    pub n: f64,
+    pub i: u64,
+    pub v: u32,
+    pub f: u64,
+}
+
+impl PluralCategory {
+    pub const ALL: [Self; 6] = [
+        Self::Zero,
+        Self::One,
+        Self::Two,
+        Self::Few,
+        Self::Many,
+        Self::Other,
+    ];
+
+    /// how the category is spelled in string tables
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::One => "one",
+            Self::Two => "two",
+            Self::Few => "few",
+            Self::Many => "many",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+
+    /// the category `number` falls into in `locale`
+    pub fn of(locale: &Locale, number: impl Into<Operands>) -> Self {
+        let Operands { n, i, v, .. } = number.into();
+        let i10 = i % 10;
+        let i100 = i % 100;
+        let whole = v == 0;
+        if locale.as_str() == "pt-PT" {
+            return match i == 1 && whole {
+                true => Self::One,
+                false => Self::Other,
+            };
+        }
+        match locale.language() {
+            "en" | "de" | "nl" | "sv" | "fi" | "et" | "it" | "ca" => match i == 1 && whole {
+                true => Self::One,
+                false => Self::Other,
+            },
+            "es" | "el" | "tr" | "hu" | "bg" | "nb" | "no" => match n == 1.0 {
+                true => Self::One,
+                false => Self::Other,
+            },
+            "fr" | "pt" => match i <= 1 {
+                true => Self::One,
+                false => Self::Other,
+            },
+            "ru" | "uk" if whole => match (i10, i100) {
+                (1, _) if i100 != 11 => Self::One,
+                (2..=4, _) if !(12..=14).contains(&i100) => Self::Few,
+                _ => Self::Many,
+            },
+            "pl" if whole => match (i, i10, i100) {
+                (1, _, _) => Self::One,
+                (_, 2..=4, _) if !(12..=14).contains(&i100) => Self::Few,
+                _ => Self::Many,
+            },
+            "cs" | "sk" => match (i, whole) {
+                (1, true) => Self::One,
+                (2..=4, true) => Self::Few,
+                (_, false) => Self::Many,
+                _ => Self::Other,
+            },
+            "he" => match (i, whole) {
+                (1, true) => Self::One,
+                (0, false) => Self::One,
+                (2, true) => Self::Two,
+                _ => Self::Other,
+            },
+            "ar" if n == i as f64 => match (i, i100) {
+                (0, _) => Self::Zero,
+                (1, _) => Self::One,
+                (2, _) => Self::Two,
+                (_, 3..=10) => Self::Few,
+                (_, 11..=99) => Self::Many,
+                _ => Self::Other,
+            },
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<u64> for Operands {
+    fn from(value: u64) -> Self {
+        Self {
+            n: value as f64,
+            i: value,
+            v: 0,
+            f: 0,
+        }
+    }
+}
+
+impl From<i64> for Operands {
+    fn from(value: i64) -> Self {
+        Self::from(value.unsigned_abs())
+    }
+}
+
+impl From<u32> for Operands {
+    fn from(value: u32) -> Self {
+        Self::from(value as u64)
+    }
+}
+
+impl From<i32> for Operands {
+    fn from(value: i32) -> Self {
+        Self::from(value as i64)
+    }
+}
+
+impl From<usize> for Operands {
+    fn from(value: usize) -> Self {
+        Self::from(value as u64)
+    }
+}
+
+/// the digits of the shortest form that reads back as the same float, so `1.5` has one
+/// visible fraction digit and `2.0` none
+impl From<f64> for Operands {
+    fn from(value: f64) -> Self {
+        let value = value.abs();
+        let text = format!("{value}");
+        let (int, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        Self {
+            n: value,
+            i: int.parse().unwrap_or(u64::MAX),
+            v: fraction.len() as u32,
+            f: fraction.parse().unwrap_or(0),
+        }
+    }
+}
+
+impl fmt::Display for PluralCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,328 @@
+//! the binary form, everything little-endian:
+//!
+//! ```text
+//! magic "YWLD", version u16
+//! width, height, tile width, tile height: u32
+//! assets: u32 count, then name and path strings
+//! properties
+//! layers: u32 count, then each one as
+//!   0 (tiles), name, properties, u8 has tileset [, tileset], width u32, height u32, u32 ids
+//!   1 (objects), name, properties,
+//!     u32 count of objects: name, u8 shape (0 point, 1 rect), x y [w h] f32, properties
+//!     u32 count of spawns: entity, x y f32, properties
+//! ```
+//!
+//! strings are a u32 length and utf-8, properties a u32 count of key, u8 type (0 bool, 1 int,
+//! 2 float, 3 string, 4 asset) and the value as u8, i64, f64 or a string
+
+use super::{
+    AssetRef, Layer, Object, ObjectLayer, Properties, Property, Shape, Spawn, TileLayer, World,
+    WorldError, WorldErrorKind,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const MAGIC: [u8; 4] = *b"YWLD";
+const VERSION: u16 = 1;
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(u32::try_from(len).expect("more than u32::MAX entries in a level"));
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn properties(&mut self, properties: &Properties) {
+        self.len(properties.len());
+        for (key, value) in properties.iter() {
+            self.str(key);
+            match value {
+                Property::Bool(value) => {
+                    self.u8(0);
+                    self.u8(*value as u8);
+                }
+                Property::Int(value) => {
+                    self.u8(1);
+                    self.0.extend_from_slice(&value.to_le_bytes());
+                }
+                Property::Float(value) => {
+                    self.u8(2);
+                    self.0.extend_from_slice(&value.to_le_bytes());
+                }
+                Property::String(value) => {
+                    self.u8(3);
+                    self.str(value);
+                }
+                Property::Asset(name) => {
+                    self.u8(4);
+                    self.str(name);
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+fn eof() -> WorldErrorKind {
+    WorldErrorKind::UnexpectedEof
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], WorldErrorKind> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>().ok_or_else(eof)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], WorldErrorKind> {
+        let bytes = self.0.get(..len).ok_or_else(eof)?;
+        self.0 = &self.0[len..];
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, WorldErrorKind> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, WorldErrorKind> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    /// a count of things at least `min_size` bytes each, checked against what is left so a
+    /// bad count can't allocate more than the data could hold
+    fn count(&mut self, min_size: usize) -> Result<usize, WorldErrorKind> {
+        let count = self.u32()? as usize;
+        match count.checked_mul(min_size) {
+            Some(size) if size <= self.0.len() => Ok(count),
+            _ => Err(eof()),
+        }
+    }
+
+    fn f32(&mut self) -> Result<f32, WorldErrorKind> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn string(&mut self) -> Result<String, WorldErrorKind> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        match core::str::from_utf8(bytes) {
+            Ok(string) => Ok(string.into()),
+            Err(_) => Err(WorldErrorKind::InvalidUtf8),
+        }
+    }
+
+    fn properties(&mut self) -> Result<Properties, WorldErrorKind> {
+        let mut properties = Properties::new();
+        // a key length and a type at least
+        for _ in 0..self.count(5)? {
+            let key = self.string()?;
+            let value = match self.u8()? {
+                0 => Property::Bool(self.u8()? != 0),
+                1 => Property::Int(i64::from_le_bytes(self.take()?)),
+                2 => Property::Float(f64::from_le_bytes(self.take()?)),
+                3 => Property::String(self.string()?),
+                4 => Property::Asset(self.string()?),
+                _ => return Err(WorldErrorKind::Invalid("unknown property type")),
+            };
+            properties.insert(key, value);
+        }
+        Ok(properties)
+    }
+
+    fn layer(&mut self) -> Result<Layer, WorldErrorKind> {
+        let tag = self.u8()?;
+        let name = self.string()?;
+        let properties = self.properties()?;
+        match tag {
+            0 => {
+                let tileset = match self.u8()? {
+                    0 => None,
+                    _ => Some(self.string()?),
+                };
+                let width = self.u32()?;
+                let height = self.u32()?;
+                let area = (width as usize)
+                    .checked_mul(height as usize)
+                    .ok_or_else(eof)?;
+                let ids = self.bytes(area.checked_mul(4).ok_or_else(eof)?)?;
+                Ok(Layer::Tiles(TileLayer {
+                    name,
+                    tileset,
+                    width,
+                    height,
+                    tiles: ids
+                        .chunks_exact(4)
+                        .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+                        .collect(),
+                    properties,
+                }))
+            }
+            1 => {
+                let mut layer = ObjectLayer {
+                    name,
+                    properties,
+                    ..ObjectLayer::default()
+                };
+                for _ in 0..self.count(4 + 1 + 8 + 4)? {
+                    let name = self.string()?;
+                    let shape = match self.u8()? {
+                        0 => Shape::Point {
+                            x: self.f32()?,
+                            y: self.f32()?,
+                        },
+                        1 => Shape::Rect {
+                            x: self.f32()?,
+                            y: self.f32()?,
+                            w: self.f32()?,
+                            h: self.f32()?,
+                        },
+                        _ => return Err(WorldErrorKind::Invalid("unknown object shape")),
+                    };
+                    let properties = self.properties()?;
+                    layer.objects.push(Object {
+                        name,
+                        shape,
+                        properties,
+                    });
+                }
+                for _ in 0..self.count(4 + 8 + 4)? {
+                    layer.spawns.push(Spawn {
+                        entity: self.string()?,
+                        x: self.f32()?,
+                        y: self.f32()?,
+                        properties: self.properties()?,
+                    });
+                }
+                Ok(Layer::Objects(layer))
+            }
+            _ => Err(WorldErrorKind::Invalid("unknown layer type")),
+        }
+    }
+
+    fn world(&mut self) -> Result<World, WorldErrorKind> {
+        if self.take::<4>()? != MAGIC {
+            return Err(WorldErrorKind::Invalid("not a binary level"));
+        }
+        let version = u16::from_le_bytes(self.take()?);
+        if version != VERSION {
+            return Err(WorldErrorKind::UnsupportedVersion(version));
+        }
+        let mut world = World {
+            width: self.u32()?,
+            height: self.u32()?,
+            tile_width: self.u32()?,
+            tile_height: self.u32()?,
+            ..World::default()
+        };
+        for _ in 0..self.count(8)? {
+            world.assets.push(AssetRef {
+                name: self.string()?,
+                path: self.string()?,
+            });
+        }
+        world.properties = self.properties()?;
+        // a tag, a name and a property count
+        for _ in 0..self.count(1 + 4 + 4)? {
+            world.layers.push(self.layer()?);
+        }
+        if !self.0.is_empty() {
+            return Err(WorldErrorKind::Invalid("trailing bytes"));
+        }
+        world.check()?;
+        Ok(world)
+    }
+}
+
+impl World {
+    /// reads the binary form, checking it the way `parse` checks text
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, WorldError> {
+        Reader(bytes)
+            .world()
+            .map_err(|kind| WorldError::new(0, kind))
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = Writer(Vec::from(MAGIC));
+        out.0.extend_from_slice(&VERSION.to_le_bytes());
+        for size in [self.width, self.height, self.tile_width, self.tile_height] {
+            out.u32(size);
+        }
+        out.len(self.assets.len());
+        for asset in &self.assets {
+            out.str(&asset.name);
+            out.str(&asset.path);
+        }
+        out.properties(&self.properties);
+        out.len(self.layers.len());
+        for layer in &self.layers {
+            match layer {
+                Layer::Tiles(tiles) => {
+                    out.u8(0);
+                    out.str(&tiles.name);
+                    out.properties(&tiles.properties);
+                    match &tiles.tileset {
+                        Some(tileset) => {
+                            out.u8(1);
+                            out.str(tileset);
+                        }
+                        None => out.u8(0),
+                    }
+                    out.u32(tiles.width);
+                    out.u32(tiles.height);
+                    for &tile in &tiles.tiles {
+                        out.u32(tile);
+                    }
+                }
+                Layer::Objects(objects) => {
+                    out.u8(1);
+                    out.str(&objects.name);
+                    out.properties(&objects.properties);
+                    out.len(objects.objects.len());
+                    for object in &objects.objects {
+                        out.str(&object.name);
+                        match object.shape {
+                            Shape::Point { x, y } => {
+                                out.u8(0);
+                                out.f32(x);
+                                out.f32(y);
+                            }
+                            Shape::Rect { x, y, w, h } => {
+                                out.u8(1);
+                                for value in [x, y, w, h] {
+                                    out.f32(value);
+                                }
+                            }
+                        }
+                        out.properties(&object.properties);
+                    }
+                    out.len(objects.spawns.len());
+                    for spawn in &objects.spawns {
+                        out.str(&spawn.entity);
+                        out.f32(spawn.x);
+                        out.f32(spawn.y);
+                        out.properties(&spawn.properties);
+                    }
+                }
+            }
+        }
+        out.0
+    }
+}
@@ -0,0 +1,536 @@
+//! levels for `AssetKind::GameWorld`: a grid size, tile layers, object layers with entity spawn
+//! points, and the other assets the level refers to.
+//!
+//! levels are written as text (`.world`, see `text`) so they review well in git, and shipped as
+//! binary (`.wld`, see `binary`). the decoder takes either, whatever the extension says
+
+use super::{AssetDecoder, AssetKind, DecodeContext, DecoderRegistry};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+mod binary;
+mod text;
+
+pub use binary::MAGIC;
+
+/// a tile id of zero is an empty cell
+pub const EMPTY: u32 = 0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct World {
+    /// in tiles
+    pub width: u32,
+    pub height: u32,
+    /// in pixels
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub assets: Vec<AssetRef>,
+    pub properties: Properties,
+    /// bottom to top
+    pub layers: Vec<Layer>,
+}
+
+/// another asset the level needs, under a short name the rest of the level uses for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetRef {
+    pub name: String,
+    /// as written in the level file. the decoder resolves it relative to the level, so in a
+    /// decoded `World` this is an asset name that can be loaded as is
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+/// a `width` by `height` grid of tile ids, row by row from the top left
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    /// the name of the asset the ids index into
+    pub tileset: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<u32>,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<Object>,
+    pub spawns: Vec<Spawn>,
+    pub properties: Properties,
+}
+
+/// a named shape: a door, a trigger, a camera bound...
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub name: String,
+    pub shape: Shape,
+    pub properties: Properties,
+}
+
+/// in pixels, from the top left of the level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Point { x: f32, y: f32 },
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+}
+
+/// where an entity of type `entity` appears when the level starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawn {
+    pub entity: String,
+    pub x: f32,
+    pub y: f32,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// the name of one of the level's `assets`
+    Asset(String),
+}
+
+/// properties in the order they were written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Properties {
+    entries: Vec<(String, Property)>,
+}
+
+impl World {
+    /// text or binary, told apart by the binary magic
+    pub fn decode(bytes: &[u8]) -> Result<Self, WorldError> {
+        if bytes.starts_with(&MAGIC) {
+            return Self::from_binary(bytes);
+        }
+        let src = core::str::from_utf8(bytes)
+            .map_err(|_| WorldError::new(0, WorldErrorKind::InvalidUtf8))?;
+        Self::parse(src)
+    }
+
+    pub fn asset(&self, name: &str) -> Option<&AssetRef> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Tiles(tiles) => Some(tiles),
+            Layer::Objects(_) => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Objects(objects) => Some(objects),
+            Layer::Tiles(_) => None,
+        })
+    }
+
+    /// every spawn point in every layer, bottom layer first
+    pub fn spawns(&self) -> impl Iterator<Item = &Spawn> {
+        self.object_layers().flat_map(|layer| &layer.spawns)
+    }
+
+    /// rewrites every asset path, e.g. to resolve them against the level's directory
+    pub fn resolve(&mut self, mut resolve: impl FnMut(&str) -> String) {
+        for asset in &mut self.assets {
+            asset.path = resolve(&asset.path);
+        }
+    }
+
+    /// checks that tile layers are the size of the level and every asset the level uses is
+    /// declared. text is checked as it is parsed, binary levels all at once
+    fn check(&self) -> Result<(), WorldErrorKind> {
+        for (i, asset) in self.assets.iter().enumerate() {
+            if self.assets[..i].iter().any(|a| a.name == asset.name) {
+                return Err(WorldErrorKind::DuplicateAsset(asset.name.clone()));
+            }
+        }
+        let known = |name: &str| match self.asset(name) {
+            Some(_) => Ok(()),
+            None => Err(WorldErrorKind::UnknownAsset(name.into())),
+        };
+        let mut properties = alloc::vec![&self.properties];
+        for layer in &self.layers {
+            match layer {
+                Layer::Tiles(tiles) => {
+                    if (tiles.width, tiles.height) != (self.width, self.height) {
+                        return Err(WorldErrorKind::WrongSize);
+                    }
+                    if let Some(tileset) = &tiles.tileset {
+                        known(tileset)?;
+                    }
+                    properties.push(&tiles.properties);
+                }
+                Layer::Objects(objects) => {
+                    properties.push(&objects.properties);
+                    properties.extend(objects.objects.iter().map(|o| &o.properties));
+                    properties.extend(objects.spawns.iter().map(|s| &s.properties));
+                }
+            }
+        }
+        for (_, property) in properties.into_iter().flat_map(Properties::iter) {
+            if let Property::Asset(name) = property {
+                known(name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Tiles(tiles) => &tiles.name,
+            Self::Objects(objects) => &objects.name,
+        }
+    }
+
+    pub fn properties(&self) -> &Properties {
+        match self {
+            Self::Tiles(tiles) => &tiles.properties,
+            Self::Objects(objects) => &objects.properties,
+        }
+    }
+}
+
+impl TileLayer {
+    /// an empty layer
+    pub fn new(name: impl Into<String>, width: u32, height: u32) -> Self {
+        Self {
+            name: name.into(),
+            tileset: None,
+            width,
+            height,
+            tiles: alloc::vec![EMPTY; width as usize * height as usize],
+            properties: Properties::new(),
+        }
+    }
+
+    /// `None` outside the layer
+    pub fn get(&self, x: u32, y: u32) -> Option<u32> {
+        (x < self.width && y < self.height)
+            .then(|| self.tiles[y as usize * self.width as usize + x as usize])
+    }
+
+    /// panics outside the layer
+    pub fn set(&mut self, x: u32, y: u32, tile: u32) {
+        assert!(
+            x < self.width && y < self.height,
+            "({x}, {y}) is outside the layer"
+        );
+        self.tiles[y as usize * self.width as usize + x as usize] = tile;
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u32]> {
+        self.tiles.chunks(self.width.max(1) as usize)
+    }
+}
+
+impl ObjectLayer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn object(&self, name: &str) -> Option<&Object> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+}
+
+impl Property {
+    /// how the type is spelled in the text form
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Asset(_) => "asset",
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// ints convert too
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// the name of the asset, look it up with `World::asset`
+    pub fn as_asset(&self) -> Option<&str> {
+        match self {
+            Self::Asset(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl Properties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Property> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// replaces (and returns) the old value if `key` was already set, keeping its position
+    pub fn insert(&mut self, key: impl Into<String>, value: Property) -> Option<Property> {
+        let key = key.into();
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, old)) => Some(core::mem::replace(old, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Property)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldErrorKind {
+    InvalidUtf8,
+    InvalidNumber,
+    /// a statement is missing one of its arguments, or has too many
+    WrongArguments,
+    /// the text doesn't start with a `world` statement
+    MissingHeader,
+    UnknownStatement(String),
+    UnknownType(String),
+    /// a quoted string without its closing quote, or with an unknown escape
+    InvalidString,
+    /// a tile layer row isn't as wide as the level, or there are too few or too many rows
+    WrongSize,
+    /// a layer is missing its `end`
+    UnterminatedLayer,
+    DuplicateAsset(String),
+    UnknownAsset(String),
+    /// binary levels only
+    UnexpectedEof,
+    UnsupportedVersion(u16),
+    Invalid(&'static str),
+}
+
+/// `line` is 1-based, 0 for binary levels and errors that aren't about a particular line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldError {
+    pub line: usize,
+    pub kind: WorldErrorKind,
+}
+
+impl WorldError {
+    fn new(line: usize, kind: WorldErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line != 0 {
+            write!(f, "line {}: ", self.line)?;
+        }
+        match &self.kind {
+            WorldErrorKind::InvalidUtf8 => f.write_str("invalid utf-8"),
+            WorldErrorKind::InvalidNumber => f.write_str("invalid number"),
+            WorldErrorKind::WrongArguments => f.write_str("wrong number of arguments"),
+            WorldErrorKind::MissingHeader => f.write_str("expected a `world` statement first"),
+            WorldErrorKind::UnknownStatement(name) => write!(f, "unknown statement `{name}`"),
+            WorldErrorKind::UnknownType(name) => write!(f, "unknown property type `{name}`"),
+            WorldErrorKind::InvalidString => f.write_str("invalid quoted string"),
+            WorldErrorKind::WrongSize => f.write_str("tile layer doesn't match the level size"),
+            WorldErrorKind::UnterminatedLayer => f.write_str("layer is missing its `end`"),
+            WorldErrorKind::DuplicateAsset(name) => write!(f, "asset `{name}` declared twice"),
+            WorldErrorKind::UnknownAsset(name) => write!(f, "unknown asset `{name}`"),
+            WorldErrorKind::UnexpectedEof => f.write_str("level data ended early"),
+            WorldErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported level version {version}")
+            }
+            WorldErrorKind::Invalid(what) => write!(f, "invalid level: {what}"),
+        }
+    }
+}
+
+impl core::error::Error for WorldError {}
+
+/// decodes text and binary levels, resolving their asset paths relative to the level
+pub struct WorldDecoder;
+
+impl AssetDecoder for WorldDecoder {
+    type Output = World;
+    type Error = WorldError;
+
+    fn decode(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Result<World, WorldError> {
+        let mut world = World::decode(bytes)?;
+        world.resolve(|path| cx.relative(path));
+        Ok(world)
+    }
+
+    /// every asset the level declares
+    fn dependencies(&self, cx: &DecodeContext<'_>, bytes: &[u8]) -> Vec<String> {
+        let Ok(world) = World::decode(bytes) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<String> = world
+            .assets
+            .iter()
+            .map(|asset| cx.relative(&asset.path))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+impl DecoderRegistry {
+    /// decodes `.world` and `.wld` files into a `World`
+    pub fn with_worlds(mut self) -> Self {
+        self.register(AssetKind::GameWorld, &["world", "wld"], WorldDecoder);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = r#"
+# the first level
+world 4 3 16 16
+asset tiles ../tilesets/forest.png
+asset "boss music" music/boss.ogg
+prop title string "Forest \"edge\""
+prop gravity float 9.5
+
+tiles ground tiles
+  prop solid bool true
+  1 1 1 1
+  .   2 .  3
+  4 4 4 4
+end
+
+objects things
+  rect exit 48 0 16 32
+    prop to string "cave.world"
+  point camera 32 24
+  spawn slime 16 32
+    prop hp int -12
+  spawn boss 40 8
+    prop music asset "boss music"
+end
+"#;
+
+    #[test]
+    fn text_and_binary_agree() {
+        let world = World::parse(LEVEL).unwrap();
+        assert_eq!((world.width, world.height, world.tile_width), (4, 3, 16));
+        assert_eq!(
+            world.properties.get("title").and_then(Property::as_str),
+            Some("Forest \"edge\"")
+        );
+        let Some(Layer::Tiles(ground)) = world.layer("ground") else {
+            panic!("no ground layer");
+        };
+        assert_eq!(ground.get(3, 1), Some(3));
+        assert_eq!(ground.get(0, 1), Some(EMPTY));
+        assert_eq!(ground.get(4, 0), None);
+        let spawns: Vec<_> = world.spawns().map(|s| s.entity.as_str()).collect();
+        assert_eq!(spawns, ["slime", "boss"]);
+        let boss = world.spawns().nth(1).unwrap();
+        let music = boss.properties.get("music").and_then(Property::as_asset);
+        assert_eq!(world.asset(music.unwrap()).unwrap().path, "music/boss.ogg");
+
+        // both forms round trip, and turn into each other
+        let text = world.to_text();
+        assert_eq!(World::parse(&text).unwrap(), world);
+        let binary = world.to_binary();
+        assert_eq!(World::decode(&binary).unwrap(), world);
+        assert_eq!(World::decode(text.as_bytes()).unwrap(), world);
+        for len in 0..binary.len() {
+            assert!(World::decode(&binary[..len]).is_err());
+        }
+
+        let cx = DecodeContext::new("levels/forest.world", AssetKind::GameWorld);
+        assert_eq!(
+            WorldDecoder.dependencies(&cx, LEVEL.as_bytes()),
+            ["levels/music/boss.ogg", "tilesets/forest.png"]
+        );
+        let decoded = WorldDecoder.decode(&cx, &binary).unwrap();
+        assert_eq!(decoded.asset("tiles").unwrap().path, "tilesets/forest.png");
+    }
+
+    #[test]
+    fn errors_point_at_lines() {
+        let error = |src: &str| World::parse(src).unwrap_err();
+        assert_eq!(
+            error("tiles ground\nend"),
+            WorldError::new(1, WorldErrorKind::MissingHeader)
+        );
+        assert_eq!(
+            error("world 2 1 8 8\ntiles ground\n1 2 3\nend"),
+            WorldError::new(3, WorldErrorKind::WrongSize)
+        );
+        assert_eq!(
+            error("world 2 1 8 8\ntiles ground nope\n1 2\nend"),
+            WorldError::new(2, WorldErrorKind::UnknownAsset("nope".into()))
+        );
+        assert_eq!(
+            error("world 2 1 8 8\nobjects things\nspawn slime 1 2"),
+            WorldError::new(2, WorldErrorKind::UnterminatedLayer)
+        );
+        assert_eq!(
+            error("world 2 1 8 8\nprop hp long 3"),
+            WorldError::new(2, WorldErrorKind::UnknownType("long".into()))
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! the text form, one statement per line, `#` starts a comment:
+//!
+//! ```text
+//! world <width> <height> <tile width> <tile height>
+//! asset <name> <path>
+//! prop <key> bool|int|float|string|asset <value>
+//!
+//! tiles <name> [tileset]
+//!   <one row of tile ids per line, `.` for empty>
+//! end
+//!
+//! objects <name>
+//!   rect <name> <x> <y> <w> <h>
+//!   point <name> <x> <y>
+//!   spawn <entity> <x> <y>
+//! end
+//! ```
+//!
+//! `world` comes first, assets have to be declared before anything uses them, and a `prop`
+//! belongs to whatever was opened last: the level, a layer, an object or a spawn. names and
+//! values with spaces in them are quoted, with `\"`, `\\`, `\n` and `\t` escapes
+
+use super::{
+    AssetRef, Layer, Object, ObjectLayer, Properties, Property, Shape, Spawn, TileLayer, World,
+    WorldError, WorldErrorKind, EMPTY,
+};
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::str::FromStr;
+
+/// splits a line into bare and quoted words, dropping any comment
+fn words(line: &str) -> Result<Vec<Cow<'_, str>>, WorldErrorKind> {
+    let mut words = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(first) = rest.chars().next() {
+        if first == '#' {
+            break;
+        }
+        if first != '"' {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '#' || c == '"')
+                .unwrap_or(rest.len());
+            words.push(Cow::Borrowed(&rest[..end]));
+            rest = rest[end..].trim_start();
+            continue;
+        }
+        let mut word = String::new();
+        let mut chars = rest[1..].char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i + 2,
+                Some((_, '\\')) => word.push(match chars.next() {
+                    Some((_, '"')) => '"',
+                    Some((_, '\\')) => '\\',
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    _ => return Err(WorldErrorKind::InvalidString),
+                }),
+                Some((_, c)) => word.push(c),
+                None => return Err(WorldErrorKind::InvalidString),
+            }
+        };
+        words.push(Cow::Owned(word));
+        rest = rest[end..].trim_start();
+    }
+    Ok(words)
+}
+
+fn number<T: FromStr>(word: &str) -> Result<T, WorldErrorKind> {
+    word.parse().map_err(|_| WorldErrorKind::InvalidNumber)
+}
+
+/// the arguments after the statement name, which have to be exactly `N`
+fn args<'a, const N: usize>(words: &'a [Cow<'_, str>]) -> Result<[&'a str; N], WorldErrorKind> {
+    let args: Vec<&str> = words[1..].iter().map(|word| &**word).collect();
+    args.try_into().map_err(|_| WorldErrorKind::WrongArguments)
+}
+
+/// what a `prop` inside an objects layer belongs to
+#[derive(Clone, Copy)]
+enum Last {
+    Layer,
+    Object,
+    Spawn,
+}
+
+/// the layer being filled in, and the line it started on
+enum Open {
+    Tiles(TileLayer, usize),
+    Objects(ObjectLayer, usize, Last),
+}
+
+impl World {
+    /// parses the text form. asset paths are left as written
+    pub fn parse(src: &str) -> Result<Self, WorldError> {
+        let mut world = None;
+        let mut open = None;
+        for (index, line) in src.lines().enumerate() {
+            let at = |kind| WorldError::new(index + 1, kind);
+            let words = words(line).map_err(at)?;
+            if words.is_empty() {
+                continue;
+            }
+            match &mut world {
+                Some(world) => statement(world, &mut open, index + 1, &words).map_err(at)?,
+                None => world = Some(header(&words).map_err(at)?),
+            }
+        }
+        if let Some(Open::Tiles(_, line) | Open::Objects(_, line, _)) = open {
+            return Err(WorldError::new(line, WorldErrorKind::UnterminatedLayer));
+        }
+        world.ok_or(WorldError::new(0, WorldErrorKind::MissingHeader))
+    }
+
+    /// the text form, which `parse` reads back into an equal `World`
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+fn header(words: &[Cow<'_, str>]) -> Result<World, WorldErrorKind> {
+    if words[0] != "world" {
+        return Err(WorldErrorKind::MissingHeader);
+    }
+    let [width, height, tile_width, tile_height] = args(words)?;
+    Ok(World {
+        width: number(width)?,
+        height: number(height)?,
+        tile_width: number(tile_width)?,
+        tile_height: number(tile_height)?,
+        ..World::default()
+    })
+}
+
+fn statement(
+    world: &mut World,
+    open: &mut Option<Open>,
+    line: usize,
+    words: &[Cow<'_, str>],
+) -> Result<(), WorldErrorKind> {
+    match (&mut *open, &*words[0]) {
+        (_, "prop") => {
+            let (key, value) = property(world, words)?;
+            let properties = match open {
+                None => &mut world.properties,
+                Some(Open::Tiles(layer, _)) => &mut layer.properties,
+                Some(Open::Objects(layer, _, last)) => match last {
+                    Last::Layer => &mut layer.properties,
+                    Last::Object => &mut layer.objects.last_mut().unwrap().properties,
+                    Last::Spawn => &mut layer.spawns.last_mut().unwrap().properties,
+                },
+            };
+            properties.insert(key, value);
+        }
+        (Some(_), "end") => {
+            args::<0>(words)?;
+            let layer = match open.take() {
+                Some(Open::Tiles(layer, _)) => {
+                    if layer.tiles.len() != layer.width as usize * layer.height as usize {
+                        return Err(WorldErrorKind::WrongSize);
+                    }
+                    Layer::Tiles(layer)
+                }
+                Some(Open::Objects(layer, _, _)) => Layer::Objects(layer),
+                None => unreachable!(),
+            };
+            world.layers.push(layer);
+        }
+        (Some(Open::Tiles(layer, _)), _) => {
+            let full = layer.tiles.len() == layer.width as usize * layer.height as usize;
+            if full || words.len() != layer.width as usize {
+                return Err(WorldErrorKind::WrongSize);
+            }
+            for word in words {
+                layer.tiles.push(match &**word {
+                    "." => EMPTY,
+                    word => number(word)?,
+                });
+            }
+        }
+        (Some(Open::Objects(layer, _, last)), "rect" | "point") => {
+            let shape = match words.len() {
+                4 => {
+                    let [_, x, y] = args(words)?;
+                    Shape::Point {
+                        x: number(x)?,
+                        y: number(y)?,
+                    }
+                }
+                _ => {
+                    let [_, x, y, w, h] = args(words)?;
+                    Shape::Rect {
+                        x: number(x)?,
+                        y: number(y)?,
+                        w: number(w)?,
+                        h: number(h)?,
+                    }
+                }
+            };
+            if matches!(shape, Shape::Point { .. }) != (words[0] == "point") {
+                return Err(WorldErrorKind::WrongArguments);
+            }
+            layer.objects.push(Object {
+                name: words[1].to_string(),
+                shape,
+                properties: Properties::new(),
+            });
+            *last = Last::Object;
+        }
+        (Some(Open::Objects(layer, _, last)), "spawn") => {
+            let [entity, x, y] = args(words)?;
+            layer.spawns.push(Spawn {
+                entity: entity.into(),
+                x: number(x)?,
+                y: number(y)?,
+                properties: Properties::new(),
+            });
+            *last = Last::Spawn;
+        }
+        (None, "asset") => {
+            let [name, path] = args(words)?;
+            if world.asset(name).is_some() {
+                return Err(WorldErrorKind::DuplicateAsset(name.into()));
+            }
+            world.assets.push(AssetRef {
+                name: name.into(),
+                path: path.into(),
+            });
+        }
+        (None, "tiles") => {
+            let (name, tileset) = match words {
+                [_, name] => (name, None),
+                [_, name, tileset] => {
+                    known(world, tileset)?;
+                    (name, Some(tileset.to_string()))
+                }
+                _ => return Err(WorldErrorKind::WrongArguments),
+            };
+            let layer = TileLayer {
+                name: name.to_string(),
+                tileset,
+                width: world.width,
+                height: world.height,
+                // filled in row by row, so a huge size can't allocate before there are rows
+                tiles: Vec::new(),
+                properties: Properties::new(),
+            };
+            *open = Some(Open::Tiles(layer, line));
+        }
+        (None, "objects") => {
+            let [name] = args(words)?;
+            *open = Some(Open::Objects(ObjectLayer::new(name), line, Last::Layer));
+        }
+        (_, "world") => return Err(WorldErrorKind::Invalid("more than one `world` statement")),
+        (_, name) => return Err(WorldErrorKind::UnknownStatement(name.into())),
+    }
+    Ok(())
+}
+
+fn known(world: &World, asset: &str) -> Result<(), WorldErrorKind> {
+    match world.asset(asset) {
+        Some(_) => Ok(()),
+        None => Err(WorldErrorKind::UnknownAsset(asset.into())),
+    }
+}
+
+fn property(world: &World, words: &[Cow<'_, str>]) -> Result<(String, Property), WorldErrorKind> {
+    let [key, ty, value] = args(words)?;
+    let value = match ty {
+        "bool" => match value {
+            "true" => Property::Bool(true),
+            "false" => Property::Bool(false),
+            _ => return Err(WorldErrorKind::Invalid("bools are `true` or `false`")),
+        },
+        "int" => Property::Int(number(value)?),
+        "float" => Property::Float(number(value)?),
+        "string" => Property::String(value.into()),
+        "asset" => {
+            known(world, value)?;
+            Property::Asset(value.into())
+        }
+        ty => return Err(WorldErrorKind::UnknownType(ty.into())),
+    };
+    Ok((key.into(), value))
+}
+
+/// writes a name or path, quoted only if it has to be
+struct Word<'a>(&'a str);
+
+/// writes a string, always quoted
+struct Quoted<'a>(&'a str);
+
+impl fmt::Display for Word<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bare = !self.0.is_empty()
+            && !self
+                .0
+                .contains(|c: char| c.is_whitespace() || c == '#' || c == '"' || c == '\\');
+        match bare {
+            true => f.write_str(self.0),
+            false => Quoted(self.0).fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+fn write_properties(
+    f: &mut fmt::Formatter<'_>,
+    indent: &str,
+    properties: &Properties,
+) -> fmt::Result {
+    for (key, value) in properties.iter() {
+        write!(f, "{indent}prop {} {} ", Word(key), value.type_name())?;
+        match value {
+            Property::Bool(value) => writeln!(f, "{value}")?,
+            Property::Int(value) => writeln!(f, "{value}")?,
+            Property::Float(value) => writeln!(f, "{value}")?,
+            Property::String(value) => writeln!(f, "{}", Quoted(value))?,
+            Property::Asset(name) => writeln!(f, "{}", Word(name))?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "world {} {} {} {}",
+            self.width, self.height, self.tile_width, self.tile_height
+        )?;
+        for asset in &self.assets {
+            writeln!(f, "asset {} {}", Word(&asset.name), Word(&asset.path))?;
+        }
+        write_properties(f, "", &self.properties)?;
+
+        for layer in &self.layers {
+            writeln!(f)?;
+            match layer {
+                Layer::Tiles(tiles) => {
+                    write!(f, "tiles {}", Word(&tiles.name))?;
+                    if let Some(tileset) = &tiles.tileset {
+                        write!(f, " {}", Word(tileset))?;
+                    }
+                    writeln!(f)?;
+                    write_properties(f, "  ", &tiles.properties)?;
+                    let cell = |tile: u32| match tile {
+                        EMPTY => String::from("."),
+                        tile => format!("{tile}"),
+                    };
+                    // columns line up, so a changed tile is easy to spot in a diff
+                    let width = tiles
+                        .tiles
+                        .iter()
+                        .map(|&t| cell(t).len())
+                        .max()
+                        .unwrap_or(1);
+                    for row in tiles.rows() {
+                        f.write_str(" ")?;
+                        for &tile in row {
+                            write!(f, " {:>width$}", cell(tile))?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+                Layer::Objects(objects) => {
+                    writeln!(f, "objects {}", Word(&objects.name))?;
+                    write_properties(f, "  ", &objects.properties)?;
+                    for object in &objects.objects {
+                        match object.shape {
+                            Shape::Point { x, y } => {
+                                writeln!(f, "  point {} {x} {y}", Word(&object.name))?
+                            }
+                            Shape::Rect { x, y, w, h } => {
+                                writeln!(f, "  rect {} {x} {y} {w} {h}", Word(&object.name))?
+                            }
+                        }
+                        write_properties(f, "    ", &object.properties)?;
+                    }
+                    for spawn in &objects.spawns {
+                        writeln!(f, "  spawn {} {} {}", Word(&spawn.entity), spawn.x, spawn.y)?;
+                        write_properties(f, "    ", &spawn.properties)?;
+                    }
+                }
+            }
+            writeln!(f, "end")?;
+        }
+        Ok(())
+    }
+}
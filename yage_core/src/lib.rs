@@ -19,7 +19,7 @@ pub mod prelude {
         BuildConfigs,
     };
 
-    pub use crate::asset::{BorrowedHandle, Cache, CowHandle, OwnedHandle};
+    pub use crate::asset::{BorrowedHandle, Cache, CowHandle, Handle, OwnedHandle, WeakHandle};
 }
 
 macro_rules! token_impl {
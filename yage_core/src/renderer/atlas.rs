@@ -0,0 +1,210 @@
+//! a runtime texture atlas: small images packed into a few large pages, so sprites from many
+//! images can be drawn a page at a time
+
+use super::Rgba;
+use crate::asset::image::Image;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// the part of `self` inside `0..width` and `0..height`
+    pub fn clamp_to(self, width: u32, height: u32) -> Self {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Self {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+}
+
+/// where an image ended up in an `Atlas`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub rect: Rect,
+}
+
+/// packs images into square pages of `page_size` with shelf packing: rows of images no
+/// taller than the first one put in the row. images bigger than a page get a page of their own
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    page_size: u32,
+    pages: Vec<Page>,
+}
+
+#[derive(Debug, Clone)]
+struct Page {
+    image: Image,
+    shelves: Vec<Shelf>,
+    /// where the next shelf would start
+    bottom: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// how much of the shelf is used, from the left
+    used: u32,
+}
+
+impl Atlas {
+    pub fn new(page_size: u32) -> Self {
+        Self {
+            page_size,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn page(&self, page: usize) -> Option<&Image> {
+        self.pages.get(page).map(|page| &page.image)
+    }
+
+    /// copies `image` into the first page with room for it
+    pub fn insert(&mut self, image: &Image) -> AtlasRegion {
+        let (width, height) = (image.width(), image.height());
+        let spot = self.pages.iter_mut().enumerate().find_map(|(index, page)| {
+            let (x, y) = page.allocate(width, height)?;
+            Some((index, x, y))
+        });
+        let (page, x, y) = match spot {
+            Some(spot) => spot,
+            None => {
+                let fits = width <= self.page_size && height <= self.page_size;
+                let mut page = match fits {
+                    true => Page::new(self.page_size, self.page_size),
+                    false => Page::new(width, height),
+                };
+                let (x, y) = page.allocate(width, height).unwrap_or_default();
+                self.pages.push(page);
+                (self.pages.len() - 1, x, y)
+            }
+        };
+
+        let target = &mut self.pages[page].image;
+        let stride = target.width() as usize;
+        let rows = image.pixels().chunks(width.max(1) as usize);
+        for (row, src) in rows.enumerate() {
+            let start = (y as usize + row) * stride + x as usize;
+            target.pixels_mut()[start..start + src.len()].copy_from_slice(src);
+        }
+        AtlasRegion {
+            page,
+            rect: Rect::new(x, y, width, height),
+        }
+    }
+
+    /// forgets every image but keeps the pages' memory around
+    pub fn clear(&mut self) {
+        for page in &mut self.pages {
+            page.shelves.clear();
+            page.bottom = 0;
+            page.image.pixels_mut().fill(Rgba::TRANSPARENT);
+        }
+    }
+}
+
+impl Page {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            image: Image::filled(width, height, Rgba::TRANSPARENT),
+            shelves: Vec::new(),
+            bottom: 0,
+        }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let page_width = self.image.width();
+        if width > page_width {
+            return None;
+        }
+        // the shelf that wastes the least height
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && page_width - shelf.used >= width)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = best {
+            let x = shelf.used;
+            shelf.used += width;
+            return Some((x, shelf.y));
+        }
+        if self.image.height() - self.bottom < height {
+            return None;
+        }
+        let y = self.bottom;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            used: width,
+        });
+        self.bottom += height;
+        Some((0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_into_pages() {
+        let mut atlas = Atlas::new(8);
+        let red = Image::filled(4, 4, Rgba::opaque(255, 0, 0));
+        let a = atlas.insert(&red);
+        let b = atlas.insert(&Image::filled(4, 2, Rgba::WHITE));
+        let c = atlas.insert(&Image::filled(3, 2, Rgba::WHITE));
+        assert_eq!(
+            a,
+            AtlasRegion {
+                page: 0,
+                rect: Rect::new(0, 0, 4, 4)
+            }
+        );
+        assert_eq!(b.rect, Rect::new(4, 0, 4, 2));
+        // the first shelf is full, so the next row starts below it
+        assert_eq!(c.rect, Rect::new(0, 4, 3, 2));
+        assert_eq!(atlas.page(0).unwrap().get(3, 3), Some(red.pixels()[0]));
+
+        let big = atlas.insert(&Image::filled(10, 3, Rgba::WHITE));
+        assert_eq!(big.page, 1);
+        assert_eq!(atlas.page(1).unwrap().width(), 10);
+        let d = atlas.insert(&Image::filled(8, 2, Rgba::WHITE));
+        assert_eq!(
+            d,
+            AtlasRegion {
+                page: 0,
+                rect: Rect::new(0, 6, 8, 2)
+            }
+        );
+        assert_eq!(atlas.insert(&Image::filled(1, 1, Rgba::WHITE)).page, 0);
+        assert_eq!(atlas.page_count(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+//! Porter-Duff compositing of straight RGBA, in sRGB or in linear light
+
+use super::format::PixelFormat;
+use super::srgb::{linear_to_srgb, srgb_to_linear};
+use super::{Renderer, Rgba};
+
+/// the twelve Porter-Duff operators, `src` being what is drawn and `dst` what is already there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    Clear,
+    Src,
+    Dst,
+    /// normal alpha blending
+    #[default]
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcAtop,
+    DstAtop,
+    Xor,
+}
+
+/// which space colours are mixed in. sRGB is what naive blending does and makes translucent
+/// edges look too dark, linear mixes light like it physically mixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    Srgb,
+    #[default]
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Blend {
+    pub mode: BlendMode,
+    pub space: ColorSpace,
+}
+
+impl BlendMode {
+    /// how much of the source and the destination end up in the result
+    fn factors(self, sa: f32, da: f32) -> (f32, f32) {
+        match self {
+            Self::Clear => (0.0, 0.0),
+            Self::Src => (1.0, 0.0),
+            Self::Dst => (0.0, 1.0),
+            Self::SrcOver => (1.0, 1.0 - sa),
+            Self::DstOver => (1.0 - da, 1.0),
+            Self::SrcIn => (da, 0.0),
+            Self::DstIn => (0.0, sa),
+            Self::SrcOut => (1.0 - da, 0.0),
+            Self::DstOut => (0.0, 1.0 - sa),
+            Self::SrcAtop => (da, 1.0 - sa),
+            Self::DstAtop => (1.0 - da, sa),
+            Self::Xor => (1.0 - da, 1.0 - sa),
+        }
+    }
+}
+
+impl Blend {
+    pub const fn new(mode: BlendMode, space: ColorSpace) -> Self {
+        Self { mode, space }
+    }
+
+    /// `src` over `dst` in linear light
+    pub const OVER: Self = Self::new(BlendMode::SrcOver, ColorSpace::Linear);
+
+    pub fn apply(self, src: Rgba, dst: Rgba) -> Rgba {
+        if self.mode == BlendMode::SrcOver {
+            match src.a {
+                255 => return src,
+                0 => return dst,
+                _ => {}
+            }
+        }
+        let sa = src.a as f32 / 255.0;
+        let da = dst.a as f32 / 255.0;
+        let (fa, fb) = self.mode.factors(sa, da);
+        let alpha = sa * fa + da * fb;
+        if alpha <= 0.0 {
+            return Rgba::TRANSPARENT;
+        }
+        // mixed premultiplied, then divided back out
+        let mix = |s: u8, d: u8| {
+            let (s, d) = (self.decode(s), self.decode(d));
+            self.encode((s * sa * fa + d * da * fb) / alpha)
+        };
+        Rgba::new(
+            mix(src.r, dst.r),
+            mix(src.g, dst.g),
+            mix(src.b, dst.b),
+            (alpha * 255.0 + 0.5) as u8,
+        )
+    }
+
+    /// `apply` for a pixel already in some format
+    pub fn apply_to<P: PixelFormat>(self, src: Rgba, dst: P) -> P {
+        P::from_rgba(self.apply(src, dst.to_rgba()))
+    }
+
+    fn decode(self, level: u8) -> f32 {
+        match self.space {
+            ColorSpace::Srgb => level as f32 / 255.0,
+            ColorSpace::Linear => srgb_to_linear(level) as f32 / 65535.0,
+        }
+    }
+
+    fn encode(self, value: f32) -> u8 {
+        match self.space {
+            ColorSpace::Srgb => (value * 255.0 + 0.5) as u8,
+            ColorSpace::Linear => linear_to_srgb((value * 65535.0 + 0.5) as u16),
+        }
+    }
+}
+
+/// blends a `width` by `height` block of straight RGBA onto `renderer` with its top left corner
+/// at `(x, y)`, clipped to the renderer. the block is row-major like `overwrite_with`'s buffer
+pub fn composite<R>(
+    renderer: &mut R,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    src: &[Rgba],
+    blend: Blend,
+) where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    let (target_width, target_height) = (renderer.width(), renderer.height());
+    let columns = width.min(target_width.saturating_sub(x)) as usize;
+    let rows = height.min(target_height.saturating_sub(y)) as usize;
+    if columns == 0 {
+        return;
+    }
+    let data = renderer.data_mut();
+    for (row, src) in src.chunks(width as usize).take(rows).enumerate() {
+        let start = (y as usize + row) * target_width as usize + x as usize;
+        for (dst, &src) in data[start..start + columns].iter_mut().zip(src) {
+            *dst = blend.apply_to(src, *dst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::format::Argb8888;
+
+    #[test]
+    fn porter_duff() {
+        let red = Rgba::new(255, 0, 0, 128);
+        let blue = Rgba::opaque(0, 0, 255);
+        let srgb = |mode| Blend::new(mode, ColorSpace::Srgb).apply(red, blue);
+
+        assert_eq!(srgb(BlendMode::SrcOver), Rgba::opaque(128, 0, 127));
+        assert_eq!(srgb(BlendMode::DstOver), blue);
+        assert_eq!(srgb(BlendMode::SrcIn), red);
+        assert_eq!(srgb(BlendMode::SrcOut), Rgba::TRANSPARENT);
+        assert_eq!(srgb(BlendMode::DstOut), Rgba::new(0, 0, 255, 127));
+        assert_eq!(srgb(BlendMode::Clear), Rgba::TRANSPARENT);
+
+        // half red over blue is brighter in linear light than the naive mix
+        let linear = Blend::OVER.apply(red, blue);
+        assert_eq!(linear, Rgba::opaque(188, 0, 187));
+        assert_eq!(
+            Blend::OVER.apply_to(red, Argb8888(0xff0000ff)),
+            Argb8888(0xffbc00bb)
+        );
+    }
+}
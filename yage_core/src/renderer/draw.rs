@@ -0,0 +1,664 @@
+//! shapes for any `Renderer`: thick lines, polylines, polygons, rounded rectangles and
+//! ellipses, with or without anti-aliasing.
+//!
+//! everything is turned into polygons and filled by one scanline rasterizer. coordinates are
+//! `f32` pixels with `(0, 0)` the top left corner of the top left pixel, so that pixel's centre
+//! is `(0.5, 0.5)`. everything is clipped to the renderer
+
+use super::math::{ceil, floor, sin_cos, sqrt};
+use super::{Blend, PixelFormat, Renderer, Rgba};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<(f32, f32)> for Point {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+/// which parts of overlapping or self-intersecting polygons are inside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FillRule {
+    /// inside if crossed an odd number of times, so overlaps become holes
+    EvenOdd,
+    /// inside unless the edges around a point cancel out
+    #[default]
+    NonZero,
+}
+
+/// how a `Paint` turns the coverage of a pixel into a pixel
+pub trait Paint<R: Renderer> {
+    /// sub-scanlines sampled per row of pixels, 1 for hard edges
+    fn samples(&self) -> u32;
+
+    /// `coverage` is in `0.0..=1.0`, and always 1 when `samples` is 1
+    fn put(&self, renderer: &mut R, x: u32, y: u32, coverage: f32) -> Result<(), R::Error>;
+}
+
+/// hard edges in any pixel type, written with `Renderer::pixel`. a pixel is drawn if its
+/// centre is inside the shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solid<P>(pub P);
+
+/// smooth edges, blended onto what is already there. needs a `PixelFormat` to read that back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AntiAliased {
+    pub color: Rgba,
+    pub blend: Blend,
+}
+
+impl AntiAliased {
+    /// `color` over what is there, in linear light
+    pub const fn new(color: Rgba) -> Self {
+        Self {
+            color,
+            blend: Blend::OVER,
+        }
+    }
+}
+
+impl<R: Renderer> Paint<R> for Solid<R::Pixel> {
+    fn samples(&self) -> u32 {
+        1
+    }
+
+    fn put(&self, renderer: &mut R, x: u32, y: u32, _: f32) -> Result<(), R::Error> {
+        renderer.pixel(x, y, self.0)
+    }
+}
+
+impl<R> Paint<R> for AntiAliased
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    fn samples(&self) -> u32 {
+        SMOOTH_SAMPLES
+    }
+
+    fn put(&self, renderer: &mut R, x: u32, y: u32, coverage: f32) -> Result<(), R::Error> {
+        let index = y as usize * renderer.width() as usize + x as usize;
+        let dst = renderer.data()[index];
+        let alpha = (self.color.a as f32 * coverage + 0.5) as u8;
+        let src = Rgba {
+            a: alpha,
+            ..self.color
+        };
+        renderer.pixel(x, y, self.blend.apply_to(src, dst))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineJoin {
+    /// corners cut off straight
+    Bevel,
+    #[default]
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineCap {
+    /// ends exactly at the end points
+    #[default]
+    Butt,
+    /// goes half the width past the end points
+    Square,
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stroke {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl Stroke {
+    pub const fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::Round,
+            cap: LineCap::Butt,
+        }
+    }
+}
+
+pub fn fill_polygon<R, P>(
+    renderer: &mut R,
+    points: &[Point],
+    rule: FillRule,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    fill_polygons(renderer, &[points], rule, paint)
+}
+
+/// fills several polygons as one shape, so e.g. a polygon inside another can be a hole
+pub fn fill_polygons<R, P>(
+    renderer: &mut R,
+    contours: &[&[Point]],
+    rule: FillRule,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let mut edges = Vec::new();
+    for contour in contours {
+        add_edges(&mut edges, contour);
+    }
+    paint_edges(renderer, &edges, rule, paint)
+}
+
+pub fn line<R, P>(
+    renderer: &mut R,
+    from: Point,
+    to: Point,
+    stroke: &Stroke,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    stroke_polyline(renderer, &[from, to], stroke, paint)
+}
+
+/// connected lines through `points`, with the stroke's cap at both ends
+pub fn stroke_polyline<R, P>(
+    renderer: &mut R,
+    points: &[Point],
+    stroke: &Stroke,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let edges = stroke_edges(points, false, stroke);
+    paint_edges(renderer, &edges, FillRule::NonZero, paint)
+}
+
+/// the outline of a polygon, joined all the way around
+pub fn stroke_polygon<R, P>(
+    renderer: &mut R,
+    points: &[Point],
+    stroke: &Stroke,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let edges = stroke_edges(points, true, stroke);
+    paint_edges(renderer, &edges, FillRule::NonZero, paint)
+}
+
+/// `radius` is clamped to half the shorter side, 0 is a plain rectangle
+pub fn fill_rounded_rect<R, P>(
+    renderer: &mut R,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    radius: f32,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let points = rounded_rect(x, y, width, height, radius);
+    fill_polygon(renderer, &points, FillRule::NonZero, paint)
+}
+
+/// the stroke is centred on the rectangle's edge
+#[allow(clippy::too_many_arguments)]
+pub fn stroke_rounded_rect<R, P>(
+    renderer: &mut R,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    radius: f32,
+    stroke: &Stroke,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let points = rounded_rect(x, y, width, height, radius);
+    stroke_polygon(renderer, &points, stroke, paint)
+}
+
+pub fn fill_ellipse<R, P>(
+    renderer: &mut R,
+    center: Point,
+    radius_x: f32,
+    radius_y: f32,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let points = ellipse(center, radius_x, radius_y);
+    fill_polygon(renderer, &points, FillRule::NonZero, paint)
+}
+
+pub fn stroke_ellipse<R, P>(
+    renderer: &mut R,
+    center: Point,
+    radius_x: f32,
+    radius_y: f32,
+    stroke: &Stroke,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let points = ellipse(center, radius_x, radius_y);
+    stroke_polygon(renderer, &points, stroke, paint)
+}
+
+/// a non-horizontal polygon edge, `top` above `bottom`. `winding` is 1 going down, -1 going up
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    top: Point,
+    bottom: Point,
+    winding: i32,
+}
+
+fn add_edges(edges: &mut Vec<Edge>, contour: &[Point]) {
+    let next = contour.iter().cycle().skip(1);
+    for (&from, &to) in contour.iter().zip(next) {
+        let finite = |p: Point| p.x.is_finite() && p.y.is_finite();
+        if from.y == to.y || !finite(from) || !finite(to) {
+            continue;
+        }
+        edges.push(match from.y < to.y {
+            true => Edge {
+                top: from,
+                bottom: to,
+                winding: 1,
+            },
+            false => Edge {
+                top: to,
+                bottom: from,
+                winding: -1,
+            },
+        });
+    }
+}
+
+/// fills `edges` onto `renderer` through `paint`
+fn paint_edges<R, P>(
+    renderer: &mut R,
+    edges: &[Edge],
+    rule: FillRule,
+    paint: &P,
+) -> Result<(), R::Error>
+where
+    R: Renderer,
+    P: Paint<R>,
+{
+    let (width, height) = (renderer.width(), renderer.height());
+    rasterize(
+        width,
+        height,
+        edges,
+        rule,
+        paint.samples(),
+        |x, y, coverage| paint.put(renderer, x, y, coverage),
+    )
+}
+
+/// an alpha mask of `contours`, `width * height` bytes row by row, anti-aliased like
+/// `AntiAliased`
+pub fn coverage_mask(width: u32, height: u32, contours: &[&[Point]], rule: FillRule) -> Vec<u8> {
+    let mut edges = Vec::new();
+    for contour in contours {
+        add_edges(&mut edges, contour);
+    }
+    let mut mask = vec![0; width as usize * height as usize];
+    let put = |x: u32, y: u32, coverage: f32| {
+        mask[y as usize * width as usize + x as usize] = (coverage * 255.0 + 0.5) as u8;
+        Ok::<_, ()>(())
+    };
+    let _ = rasterize(width, height, &edges, rule, SMOOTH_SAMPLES, put);
+    mask
+}
+
+/// sub-scanlines per row when anti-aliasing
+const SMOOTH_SAMPLES: u32 = 5;
+
+/// calls `put` with the coverage of every pixel of `width * height` the edges cover at all
+fn rasterize<E>(
+    width: u32,
+    height: u32,
+    edges: &[Edge],
+    rule: FillRule,
+    samples: u32,
+    mut put: impl FnMut(u32, u32, f32) -> Result<(), E>,
+) -> Result<(), E> {
+    if edges.is_empty() || width == 0 || height == 0 {
+        return Ok(());
+    }
+    let top = edges.iter().map(|e| e.top.y).fold(f32::INFINITY, f32::min);
+    let bottom = edges
+        .iter()
+        .map(|e| e.bottom.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let first_row = floor(top).clamp(0.0, height as f32) as u32;
+    let last_row = ceil(bottom).clamp(0.0, height as f32) as u32;
+
+    let samples = samples.max(1);
+    let weight = 1.0 / samples as f32;
+    let mut coverage = vec![0.0f32; width as usize];
+    let mut crossings = Vec::new();
+    for row in first_row..last_row {
+        let (mut left, mut right) = (width as usize, 0);
+        for sample in 0..samples {
+            let y = row as f32 + (sample as f32 + 0.5) * weight;
+            crossings.clear();
+            for edge in edges {
+                if edge.top.y <= y && y < edge.bottom.y {
+                    let t = (y - edge.top.y) / (edge.bottom.y - edge.top.y);
+                    let x = edge.top.x + t * (edge.bottom.x - edge.top.x);
+                    crossings.push((x, edge.winding));
+                }
+            }
+            crossings.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::EvenOdd => winding % 2 != 0,
+                    FillRule::NonZero => winding != 0,
+                };
+                if !inside {
+                    continue;
+                }
+                let (start, end) = (pair[0].0, pair[1].0);
+                let span = match samples {
+                    1 => cover_centres(&mut coverage, start, end),
+                    _ => cover_exact(&mut coverage, start, end, weight),
+                };
+                if let Some((from, to)) = span {
+                    left = left.min(from);
+                    right = right.max(to);
+                }
+            }
+        }
+        for (x, amount) in coverage.iter_mut().enumerate().take(right).skip(left) {
+            let amount = core::mem::take(amount).min(1.0);
+            if amount > 0.0 {
+                put(x as u32, row, amount)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// whole pixels whose centres are in `start..end`. returns the pixels it touched
+fn cover_centres(coverage: &mut [f32], start: f32, end: f32) -> Option<(usize, usize)> {
+    let len = coverage.len() as f32;
+    let from = ceil(start - 0.5).clamp(0.0, len) as usize;
+    let to = ceil(end - 0.5).clamp(0.0, len) as usize;
+    coverage.get_mut(from..to)?.fill(1.0);
+    (from < to).then_some((from, to))
+}
+
+/// `weight` times how much of each pixel `start..end` covers
+fn cover_exact(coverage: &mut [f32], start: f32, end: f32, weight: f32) -> Option<(usize, usize)> {
+    let len = coverage.len() as f32;
+    let (start, end) = (start.clamp(0.0, len), end.clamp(0.0, len));
+    if start >= end {
+        return None;
+    }
+    let (first, last) = (floor(start) as usize, floor(end) as usize);
+    if first == last {
+        coverage[first] += (end - start) * weight;
+        return Some((first, first + 1));
+    }
+    coverage[first] += (first as f32 + 1.0 - start) * weight;
+    for pixel in &mut coverage[first + 1..last] {
+        *pixel += weight;
+    }
+    if let Some(pixel) = coverage.get_mut(last) {
+        *pixel += (end - last as f32) * weight;
+    }
+    Some((first, (last + 1).min(coverage.len())))
+}
+
+fn stroke_edges(points: &[Point], closed: bool, stroke: &Stroke) -> Vec<Edge> {
+    let half = stroke.width / 2.0;
+    let mut edges = Vec::new();
+    if half.is_nan() || half <= 0.0 || points.is_empty() {
+        return edges;
+    }
+    // every piece goes the same way around, so that overlaps add up under `NonZero`
+    let mut add = |mut piece: Vec<Point>| {
+        if signed_area(&piece) < 0.0 {
+            piece.reverse();
+        }
+        add_edges(&mut edges, &piece);
+    };
+
+    let count = match closed {
+        true => points.len(),
+        false => points.len() - 1,
+    };
+    let segment = |i: usize| (points[i], points[(i + 1) % points.len()]);
+    let normal = |(from, to): (Point, Point)| {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let length = sqrt(dx * dx + dy * dy);
+        (length > 0.0).then(|| Point::new(-dy / length * half, dx / length * half))
+    };
+
+    for i in 0..count {
+        let (from, to) = segment(i);
+        let Some(n) = normal((from, to)) else {
+            continue;
+        };
+        // square caps stretch the first and last segments along themselves
+        let along = Point::new(n.y, -n.x);
+        let square = stroke.cap == LineCap::Square && !closed;
+        let (from, to) = (
+            match square && i == 0 {
+                true => Point::new(from.x - along.x, from.y - along.y),
+                false => from,
+            },
+            match square && i == count - 1 {
+                true => Point::new(to.x + along.x, to.y + along.y),
+                false => to,
+            },
+        );
+        add(vec![
+            Point::new(from.x + n.x, from.y + n.y),
+            Point::new(to.x + n.x, to.y + n.y),
+            Point::new(to.x - n.x, to.y - n.y),
+            Point::new(from.x - n.x, from.y - n.y),
+        ]);
+    }
+
+    let joins = match closed {
+        true => 0..points.len(),
+        false => 1..points.len().saturating_sub(1),
+    };
+    for i in joins {
+        let at = points[i];
+        match stroke.join {
+            LineJoin::Round => add(ellipse(at, half, half)),
+            LineJoin::Bevel => {
+                let before = segment((i + points.len() - 1) % points.len());
+                let (Some(a), Some(b)) = (normal(before), normal(segment(i))) else {
+                    continue;
+                };
+                for side in [1.0, -1.0] {
+                    add(vec![
+                        at,
+                        Point::new(at.x + a.x * side, at.y + a.y * side),
+                        Point::new(at.x + b.x * side, at.y + b.y * side),
+                    ]);
+                }
+            }
+        }
+    }
+    if stroke.cap == LineCap::Round && !closed {
+        add(ellipse(points[0], half, half));
+        add(ellipse(points[points.len() - 1], half, half));
+    }
+    edges
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let next = points.iter().cycle().skip(1);
+    points
+        .iter()
+        .zip(next)
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum()
+}
+
+fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Vec<Point> {
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    if radius.is_nan() || radius <= 0.0 {
+        return vec![
+            Point::new(x, y),
+            Point::new(x + width, y),
+            Point::new(x + width, y + height),
+            Point::new(x, y + height),
+        ];
+    }
+    let circle = unit_circle(radius);
+    let quarter = circle.len() / 4;
+    // the unit circle starts at the right and goes down first, so bottom right comes first
+    let corners = [
+        (x + width - radius, y + height - radius),
+        (x + radius, y + height - radius),
+        (x + radius, y + radius),
+        (x + width - radius, y + radius),
+    ];
+    let mut points = Vec::with_capacity(circle.len() + 4);
+    for (i, (cx, cy)) in corners.into_iter().enumerate() {
+        for k in i * quarter..=(i + 1) * quarter {
+            let p = circle[k % circle.len()];
+            points.push(Point::new(cx + p.x * radius, cy + p.y * radius));
+        }
+    }
+    points
+}
+
+fn ellipse(center: Point, radius_x: f32, radius_y: f32) -> Vec<Point> {
+    unit_circle(radius_x.abs().max(radius_y.abs()))
+        .into_iter()
+        .map(|p| Point::new(center.x + p.x * radius_x, center.y + p.y * radius_y))
+        .collect()
+}
+
+/// enough points around a circle of `radius` that it is off by at most a quarter pixel, a
+/// multiple of 4 so quarters line up
+fn unit_circle(radius: f32) -> Vec<Point> {
+    // a chord across `a` radians misses the arc by about `r * a^2 / 8`
+    let segments = (core::f32::consts::PI * sqrt(2.0 * radius.max(0.0))) as usize;
+    let segments = segments.clamp(8, 1024).div_ceil(4) * 4;
+    let (sin, cos) = sin_cos(TAU / segments as f64);
+    let (mut x, mut y) = (1.0f64, 0.0f64);
+    let mut points = Vec::with_capacity(segments);
+    for _ in 0..segments {
+        points.push(Point::new(x as f32, y as f32));
+        (x, y) = (x * cos - y * sin, x * sin + y * cos);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::testing::Grid;
+
+    fn square(x: f32, y: f32, size: f32) -> [Point; 4] {
+        [
+            Point::new(x, y),
+            Point::new(x + size, y),
+            Point::new(x + size, y + size),
+            Point::new(x, y + size),
+        ]
+    }
+
+    #[test]
+    fn fill_rules() {
+        let ink = Solid(Rgba::WHITE);
+        let (outer, inner) = (square(0.0, 0.0, 5.0), square(1.0, 1.0, 3.0));
+        let mut grid = Grid::new(5, 5);
+        fill_polygons(&mut grid, &[&outer, &inner], FillRule::EvenOdd, &ink).unwrap();
+        assert_eq!(grid.rows(), ["#####", "#...#", "#...#", "#...#", "#####"]);
+
+        let mut grid = Grid::new(5, 5);
+        fill_polygons(&mut grid, &[&outer, &inner], FillRule::NonZero, &ink).unwrap();
+        assert!(grid.rows().iter().all(|row| row == "#####"));
+    }
+
+    #[test]
+    fn non_finite_points_are_skipped() {
+        let ink = Solid(Rgba::WHITE);
+        let nan: &[Point] = &[Point::new(0.0, 3.0), Point::new(f32::NAN, 1.0)];
+        let inf: &[Point] = &[Point::new(1.0, 0.0), Point::new(1.0, f32::INFINITY)];
+        let mut grid = Grid::new(3, 3);
+        let shapes = [&square(0.0, 0.0, 2.0)[..], nan, inf];
+        fill_polygons(&mut grid, &shapes, FillRule::NonZero, &ink).unwrap();
+        assert_eq!(grid.rows(), ["##.", "##.", "..."]);
+    }
+
+    #[test]
+    fn strokes_and_shapes() {
+        let ink = Solid(Rgba::WHITE);
+        let mut grid = Grid::new(7, 5);
+        let stroke = Stroke::new(2.0);
+        let from = Point::new(-3.0, 2.0);
+        line(&mut grid, from, Point::new(5.0, 2.0), &stroke, &ink).unwrap();
+        assert_eq!(
+            grid.rows(),
+            [".......", "#####..", "#####..", ".......", "......."]
+        );
+
+        let mut grid = Grid::new(7, 7);
+        let center = Point::new(3.5, 3.5);
+        stroke_ellipse(&mut grid, center, 3.0, 2.0, &Stroke::new(1.0), &ink).unwrap();
+        fill_rounded_rect(&mut grid, 3.0, 3.0, 1.0, 1.0, 0.5, &ink).unwrap();
+        assert_eq!(
+            grid.rows(),
+            [".......", "..###..", ".#...#.", "#..#..#", ".#...#.", "..###..", "......."]
+        );
+    }
+
+    #[test]
+    fn anti_aliased_edges() {
+        let mut grid = Grid::new(3, 1);
+        let ink = AntiAliased::new(Rgba::WHITE);
+        fill_polygon(&mut grid, &square(0.0, 0.0, 1.5), FillRule::NonZero, &ink).unwrap();
+        let alphas: Vec<_> = grid.pixels.iter().map(|p| p.a).collect();
+        assert_eq!(alphas, [255, 128, 0]);
+    }
+}
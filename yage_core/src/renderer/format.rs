@@ -0,0 +1,138 @@
+//! pixel formats a `Renderer` can draw in, and conversions between them
+
+use super::{FromRgba, Rgba};
+
+/// a pixel with a known memory layout that can be read back as, and built from, straight
+/// RGBA. formats without alpha read as opaque and drop alpha when written
+pub trait PixelFormat: FromRgba + Copy + Default + PartialEq + 'static {
+    /// the name wayland and drm use for the format
+    const NAME: &'static str;
+    const HAS_ALPHA: bool;
+
+    fn to_rgba(self) -> Rgba;
+
+    fn convert<Q: PixelFormat>(self) -> Q {
+        Q::from_rgba(self.to_rgba())
+    }
+}
+
+/// `[r, g, b, a]` in memory order
+pub type Rgba8888 = Rgba;
+
+/// packed `0xAARRGGBB`, wayland's `argb8888` and the format compositors always support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct Argb8888(pub u32);
+
+/// packed `0xXXRRGGBB`, like `Argb8888` with the top byte ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct Xrgb8888(pub u32);
+
+/// packed 5 bits red, 6 green and 5 blue, red in the top bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct Rgb565(pub u16);
+
+impl PixelFormat for Rgba {
+    const NAME: &'static str = "abgr8888";
+    const HAS_ALPHA: bool = true;
+
+    fn to_rgba(self) -> Rgba {
+        self
+    }
+}
+
+impl FromRgba for Argb8888 {
+    fn from_rgba(color: Rgba) -> Self {
+        Self(u32::from_rgba(color))
+    }
+}
+
+impl PixelFormat for Argb8888 {
+    const NAME: &'static str = "argb8888";
+    const HAS_ALPHA: bool = true;
+
+    fn to_rgba(self) -> Rgba {
+        let [a, r, g, b] = self.0.to_be_bytes();
+        Rgba::new(r, g, b, a)
+    }
+}
+
+impl FromRgba for Xrgb8888 {
+    fn from_rgba(Rgba { r, g, b, .. }: Rgba) -> Self {
+        Self(u32::from_be_bytes([0xff, r, g, b]))
+    }
+}
+
+impl PixelFormat for Xrgb8888 {
+    const NAME: &'static str = "xrgb8888";
+    const HAS_ALPHA: bool = false;
+
+    fn to_rgba(self) -> Rgba {
+        let [_, r, g, b] = self.0.to_be_bytes();
+        Rgba::opaque(r, g, b)
+    }
+}
+
+impl FromRgba for Rgb565 {
+    fn from_rgba(Rgba { r, g, b, .. }: Rgba) -> Self {
+        // rounds to the nearest level instead of cutting off the low bits
+        let r = (r as u16 * 31 + 127) / 255;
+        let g = (g as u16 * 63 + 127) / 255;
+        let b = (b as u16 * 31 + 127) / 255;
+        Self(r << 11 | g << 5 | b)
+    }
+}
+
+impl PixelFormat for Rgb565 {
+    const NAME: &'static str = "rgb565";
+    const HAS_ALPHA: bool = false;
+
+    fn to_rgba(self) -> Rgba {
+        let r = (self.0 >> 11) & 0x1f;
+        let g = (self.0 >> 5) & 0x3f;
+        let b = self.0 & 0x1f;
+        Rgba::opaque(
+            ((r * 255 + 15) / 31) as u8,
+            ((g * 255 + 31) / 63) as u8,
+            ((b * 255 + 15) / 31) as u8,
+        )
+    }
+}
+
+/// converts `from` into `to` pixel by pixel, as far as the shorter one goes
+pub fn convert<P: PixelFormat, Q: PixelFormat>(from: &[P], to: &mut [Q]) {
+    for (to, &from) in to.iter_mut().zip(from) {
+        *to = from.convert();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<P: PixelFormat>(color: Rgba) -> Rgba {
+        P::from_rgba(color).to_rgba()
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let color = Rgba::new(0x12, 0x80, 0xfe, 0x40);
+        assert_eq!(round_trip::<Rgba8888>(color), color);
+        assert_eq!(round_trip::<Argb8888>(color), color);
+        assert_eq!(Argb8888::from_rgba(color), Argb8888(0x401280fe));
+        assert_eq!(round_trip::<Xrgb8888>(color), Rgba { a: 255, ..color });
+
+        // every 565 level survives a trip through 8 bits
+        for level in 0..64 {
+            let pixel = Rgb565(level << 5 | (level >> 1) << 11 | level >> 1);
+            assert_eq!(Rgb565::from_rgba(pixel.to_rgba()), pixel);
+        }
+        assert_eq!(Rgb565::from_rgba(Rgba::WHITE).to_rgba(), Rgba::WHITE);
+
+        let mut packed = [Rgb565::default(); 2];
+        convert(&[Argb8888(0xffff0000), Argb8888(0xff0000ff)], &mut packed);
+        assert_eq!(packed, [Rgb565(0xf800), Rgb565(0x001f)]);
+    }
+}
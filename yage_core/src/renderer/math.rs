@@ -0,0 +1,37 @@
+//! float functions `no_std` doesn't have
+
+/// taylor series, plenty for angles under 45 degrees
+pub(crate) fn sin_cos(angle: f64) -> (f64, f64) {
+    let a2 = angle * angle;
+    let sin = angle * (1.0 - a2 / 6.0 * (1.0 - a2 / 20.0 * (1.0 - a2 / 42.0)));
+    let cos = 1.0 - a2 / 2.0 * (1.0 - a2 / 12.0 * (1.0 - a2 / 30.0 * (1.0 - a2 / 56.0)));
+    (sin, cos)
+}
+
+pub(crate) fn sqrt(value: f32) -> f32 {
+    if value.is_nan() || value <= 0.0 || value == f32::INFINITY {
+        return value.max(0.0);
+    }
+    // start from halving the exponent, then newton's method
+    let mut guess = f32::from_bits((value.to_bits() >> 1) + (127 << 22));
+    for _ in 0..4 {
+        guess = 0.5 * (guess + value / guess);
+    }
+    guess
+}
+
+pub(crate) fn floor(value: f32) -> f32 {
+    if value.is_nan() || value.abs() >= 8_388_608.0 {
+        // too big to have a fraction, or not a number
+        return value;
+    }
+    let truncated = value as i32 as f32;
+    match truncated > value {
+        true => truncated - 1.0,
+        false => truncated,
+    }
+}
+
+pub(crate) fn ceil(value: f32) -> f32 {
+    -floor(-value)
+}
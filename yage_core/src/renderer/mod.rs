@@ -0,0 +1,102 @@
+#[cfg(feature = "alloc")]
+pub mod atlas;
+pub mod blend;
+#[cfg(feature = "alloc")]
+pub mod draw;
+pub mod format;
+#[cfg(feature = "alloc")]
+pub(crate) mod math;
+#[cfg(feature = "alloc")]
+pub mod sprite;
+pub mod srgb;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "alloc")]
+pub mod text;
+
+pub use blend::{Blend, BlendMode, ColorSpace};
+pub use format::PixelFormat;
+
+pub trait Renderer {
+    type Pixel: Copy + Clone;
+    type Error;
+
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+
+    fn data(&self) -> &[Self::Pixel];
+    fn data_mut(&mut self) -> &mut [Self::Pixel];
+
+    fn sync(&mut self) -> Result<bool, Self::Error>;
+
+    fn pixel(&mut self, x: u32, y: u32, pix: Self::Pixel) -> Result<(), Self::Error>;
+
+    fn arc(&mut self, x: u32, y: u32, parts: u8, pix: Self::Pixel) -> Result<(), Self::Error>;
+
+    fn circle(&mut self, x: u32, y: u32, radius: i32, pix: Self::Pixel) -> Result<(), Self::Error>;
+
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), Self::Error>;
+
+    fn overwrite_with(
+        &mut self,
+        x: u32,
+        y: u32,
+        buffer_width: u32,
+        buffer_height: u32,
+        buffer: &[Self::Pixel],
+    ) -> Result<(), Self::Error>;
+}
+
+pub trait MakeRenderer: Renderer {
+    fn new(width: u32, height: u32) -> Result<Self, Self::Error>
+    where
+        Self: Sized;
+}
+
+/// straight (not premultiplied) 8-bit RGBA, what every image decoder produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const TRANSPARENT: Self = Self::new(0, 0, 0, 0);
+    pub const BLACK: Self = Self::new(0, 0, 0, 255);
+    pub const WHITE: Self = Self::new(255, 255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn opaque(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 255)
+    }
+}
+
+/// how a renderer's `Pixel` is built from decoded image data
+pub trait FromRgba: Copy {
+    fn from_rgba(color: Rgba) -> Self;
+}
+
+impl FromRgba for Rgba {
+    fn from_rgba(color: Rgba) -> Self {
+        color
+    }
+}
+
+/// `[r, g, b, a]` in memory order
+impl FromRgba for [u8; 4] {
+    fn from_rgba(Rgba { r, g, b, a }: Rgba) -> Self {
+        [r, g, b, a]
+    }
+}
+
+/// packed `0xAARRGGBB`, the layout of wayland's `argb8888`
+impl FromRgba for u32 {
+    fn from_rgba(Rgba { r, g, b, a }: Rgba) -> Self {
+        u32::from_be_bytes([a, r, g, b])
+    }
+}
@@ -0,0 +1,366 @@
+//! sprites: parts of images in an `Atlas`, flipped, rotated, scaled and tinted, and drawn
+//! in batches a page at a time
+
+use super::atlas::{Atlas, AtlasRegion, Rect};
+use super::{Blend, PixelFormat, Renderer, Rgba};
+use crate::asset::image::Image;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scale {
+    /// every source pixel becomes an `n` by `n` block, 0 draws nothing
+    Integer(u32),
+    /// stretched to exactly this size, filtered bilinearly
+    Bilinear { width: u32, height: u32 },
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::Integer(1)
+    }
+}
+
+/// what to draw of an atlas region and how. flips happen after the rotation, i.e. on the
+/// sprite as it appears on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sprite {
+    pub region: AtlasRegion,
+    /// the part of the region to draw, relative to it
+    pub source: Rect,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// a quarter turn clockwise
+    pub rotate_90: bool,
+    pub scale: Scale,
+    /// multiplies every channel, white changes nothing
+    pub tint: Rgba,
+    /// lower layers are drawn first, see `SpriteBatch`
+    pub layer: i32,
+}
+
+impl Sprite {
+    /// all of `region`, as it is
+    pub fn new(region: AtlasRegion) -> Self {
+        Self {
+            region,
+            source: Rect::new(0, 0, region.rect.width, region.rect.height),
+            flip_x: false,
+            flip_y: false,
+            rotate_90: false,
+            scale: Scale::default(),
+            tint: Rgba::WHITE,
+            layer: 0,
+        }
+    }
+
+    pub fn with_source(mut self, source: Rect) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_flip(mut self, x: bool, y: bool) -> Self {
+        (self.flip_x, self.flip_y) = (x, y);
+        self
+    }
+
+    pub fn with_rotate_90(mut self, rotate: bool) -> Self {
+        self.rotate_90 = rotate;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Rgba) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    fn source(&self) -> Rect {
+        let Rect { width, height, .. } = self.region.rect;
+        self.source.clamp_to(width, height)
+    }
+
+    /// the size after rotating, before scaling
+    fn oriented(&self) -> (u32, u32) {
+        let Rect { width, height, .. } = self.source();
+        match self.rotate_90 {
+            true => (height, width),
+            false => (width, height),
+        }
+    }
+
+    /// how big the sprite is on screen
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = self.oriented();
+        match self.scale {
+            Scale::Integer(n) => (width.saturating_mul(n), height.saturating_mul(n)),
+            Scale::Bilinear { width, height } => (width, height),
+        }
+    }
+}
+
+/// draws `sprite` with its top left corner at `(x, y)`, clipped to the renderer
+pub fn draw_sprite<R>(
+    renderer: &mut R,
+    atlas: &Atlas,
+    sprite: &Sprite,
+    x: i32,
+    y: i32,
+    blend: Blend,
+) where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    let Some(page) = atlas.page(sprite.region.page) else {
+        return;
+    };
+    // regions are plain data, one made up by hand or taken from another atlas may reach past
+    // the page
+    let mut sprite = *sprite;
+    sprite.region.rect = sprite.region.rect.clamp_to(page.width(), page.height());
+    let source = sprite.source();
+    let (width, height) = sprite.size();
+    if source.width == 0 || source.height == 0 || width == 0 || height == 0 {
+        return;
+    }
+    let origin = (
+        sprite.region.rect.x + source.x,
+        sprite.region.rect.y + source.y,
+    );
+    let (oriented_width, oriented_height) = sprite.oriented();
+    // from where on screen to where in the source, both as fractions of pixels
+    let to_source = |u: f32, v: f32| {
+        let u = match sprite.flip_x {
+            true => oriented_width as f32 - u,
+            false => u,
+        };
+        let v = match sprite.flip_y {
+            true => oriented_height as f32 - v,
+            false => v,
+        };
+        match sprite.rotate_90 {
+            true => (v, source.height as f32 - u),
+            false => (u, v),
+        }
+    };
+
+    let (target_width, target_height) = (renderer.width() as i64, renderer.height() as i64);
+    let columns = (x as i64).max(0)..(x as i64 + width as i64).min(target_width);
+    let rows = (y as i64).max(0)..(y as i64 + height as i64).min(target_height);
+    let data = renderer.data_mut();
+    for screen_y in rows {
+        for screen_x in columns.clone() {
+            // the centre of the screen pixel, in oriented source pixels
+            let u = (screen_x - x as i64) as f32 + 0.5;
+            let v = (screen_y - y as i64) as f32 + 0.5;
+            let color = match sprite.scale {
+                Scale::Integer(n) => {
+                    let (sx, sy) = to_source(u / n as f32, v / n as f32);
+                    let sx = (sx as u32).min(source.width - 1);
+                    let sy = (sy as u32).min(source.height - 1);
+                    page.pixels()[((origin.1 + sy) * page.width() + origin.0 + sx) as usize]
+                }
+                Scale::Bilinear { .. } => {
+                    let u = u * oriented_width as f32 / width as f32;
+                    let v = v * oriented_height as f32 / height as f32;
+                    let (sx, sy) = to_source(u, v);
+                    bilinear(page, origin, (source.width, source.height), sx, sy)
+                }
+            };
+            let index = (screen_y * target_width + screen_x) as usize;
+            data[index] = blend.apply_to(tint(color, sprite.tint), data[index]);
+        }
+    }
+}
+
+fn tint(color: Rgba, tint: Rgba) -> Rgba {
+    let mul = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+    Rgba::new(
+        mul(color.r, tint.r),
+        mul(color.g, tint.g),
+        mul(color.b, tint.b),
+        mul(color.a, tint.a),
+    )
+}
+
+/// samples the `size` block of `page` at `origin` at `(x, y)`, never reading outside of it.
+/// mixed with premultiplied alpha, so transparent pixels don't darken their neighbours
+fn bilinear(page: &Image, origin: (u32, u32), size: (u32, u32), x: f32, y: f32) -> Rgba {
+    let x = (x - 0.5).clamp(0.0, (size.0 - 1) as f32);
+    let y = (y - 0.5).clamp(0.0, (size.1 - 1) as f32);
+    let (x0, y0) = (x as u32, y as u32);
+    let (x1, y1) = ((x0 + 1).min(size.0 - 1), (y0 + 1).min(size.1 - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let at =
+        |px: u32, py: u32| page.pixels()[((origin.1 + py) * page.width() + origin.0 + px) as usize];
+    let mut sum = [0.0f32; 4];
+    for (px, py, weight) in [
+        (x0, y0, (1.0 - fx) * (1.0 - fy)),
+        (x1, y0, fx * (1.0 - fy)),
+        (x0, y1, (1.0 - fx) * fy),
+        (x1, y1, fx * fy),
+    ] {
+        let Rgba { r, g, b, a } = at(px, py);
+        let alpha = a as f32 * weight;
+        sum[0] += r as f32 * alpha;
+        sum[1] += g as f32 * alpha;
+        sum[2] += b as f32 * alpha;
+        sum[3] += alpha;
+    }
+    if sum[3] <= 0.0 {
+        return Rgba::TRANSPARENT;
+    }
+    let channel = |value: f32| (value / sum[3] + 0.5) as u8;
+    Rgba::new(
+        channel(sum[0]),
+        channel(sum[1]),
+        channel(sum[2]),
+        (sum[3] + 0.5) as u8,
+    )
+}
+
+/// sprites to draw together. drawing goes layer by layer, and within a layer a page at a
+/// time, so sprites on the same layer but different pages may overlap the other way around
+/// than they were pushed. within a page the order is kept
+#[derive(Debug, Clone, Default)]
+pub struct SpriteBatch {
+    draws: Vec<(Sprite, i32, i32)>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sprite: Sprite, x: i32, y: i32) {
+        self.draws.push((sprite, x, y));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// draws and forgets every sprite. returns how many batches that took, i.e. how often it
+    /// had to move on to another layer or page
+    pub fn flush<R>(&mut self, renderer: &mut R, atlas: &Atlas, blend: Blend) -> usize
+    where
+        R: Renderer,
+        R::Pixel: PixelFormat,
+    {
+        let key = |(sprite, ..): &(Sprite, i32, i32)| (sprite.layer, sprite.region.page);
+        self.draws.sort_by_key(key);
+        let batches = self.draws.chunk_by(|a, b| key(a) == key(b)).count();
+        for (sprite, x, y) in self.draws.drain(..) {
+            draw_sprite(renderer, atlas, &sprite, x, y, blend);
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::testing::Grid;
+    use crate::renderer::BlendMode;
+    use alloc::vec;
+
+    const RED: Rgba = Rgba::opaque(255, 0, 0);
+    const BLUE: Rgba = Rgba::opaque(0, 0, 255);
+
+    fn drawn(width: u32, height: u32, sprite: Sprite, atlas: &Atlas) -> Vec<Rgba> {
+        let mut grid = Grid::new(width, height);
+        draw_sprite(&mut grid, atlas, &sprite, 0, 0, Blend::OVER);
+        grid.pixels
+    }
+
+    #[test]
+    fn transforms() {
+        let mut atlas = Atlas::new(16);
+        atlas.insert(&Image::filled(3, 3, Rgba::WHITE));
+        let image = Image::from_pixels(2, 1, vec![RED, BLUE]).unwrap();
+        let sprite = Sprite::new(atlas.insert(&image));
+
+        assert_eq!(drawn(2, 1, sprite, &atlas), [RED, BLUE]);
+        assert_eq!(
+            drawn(2, 1, sprite.with_flip(true, false), &atlas),
+            [BLUE, RED]
+        );
+        assert_eq!(
+            drawn(1, 2, sprite.with_rotate_90(true), &atlas),
+            [RED, BLUE]
+        );
+        let flipped = sprite.with_rotate_90(true).with_flip(false, true);
+        assert_eq!(drawn(1, 2, flipped, &atlas), [BLUE, RED]);
+        let source = sprite.with_source(Rect::new(1, 0, 5, 5));
+        assert_eq!(drawn(2, 1, source, &atlas), [BLUE, Rgba::TRANSPARENT]);
+
+        let big = sprite.with_scale(Scale::Integer(2));
+        assert_eq!(big.size(), (4, 2));
+        assert_eq!(drawn(4, 1, big, &atlas), [RED, RED, BLUE, BLUE]);
+
+        let stretched = sprite.with_scale(Scale::Bilinear {
+            width: 4,
+            height: 1,
+        });
+        let pixels = drawn(4, 1, stretched, &atlas);
+        assert_eq!([pixels[0], pixels[3]], [RED, BLUE]);
+        assert_eq!(pixels[1], Rgba::opaque(191, 0, 64));
+
+        let tinted = sprite.with_tint(Rgba::new(255, 255, 255, 0));
+        assert_eq!(drawn(2, 1, tinted, &atlas), [Rgba::TRANSPARENT; 2]);
+    }
+
+    #[test]
+    fn batches_by_layer_and_page() {
+        let mut atlas = Atlas::new(4);
+        let small = Sprite::new(atlas.insert(&Image::filled(1, 1, RED)));
+        let large = Sprite::new(atlas.insert(&Image::filled(5, 1, BLUE)));
+        let mut batch = SpriteBatch::new();
+        batch.push(large, 0, 0);
+        batch.push(small, 0, 0);
+        batch.push(large, 1, 0);
+        batch.push(small.with_layer(1), 4, 0);
+
+        let mut grid = Grid::new(6, 1);
+        let src = Blend::new(BlendMode::Src, Default::default());
+        assert_eq!(batch.flush(&mut grid, &atlas, src), 3);
+        assert!(batch.is_empty());
+        // page 0 went first, so the large sprites ended up on top of the small one
+        assert_eq!(grid.pixels, [BLUE, BLUE, BLUE, BLUE, RED, BLUE]);
+    }
+
+    #[test]
+    fn regions_past_the_page() {
+        let mut atlas = Atlas::new(4);
+        let region = atlas.insert(&Image::filled(4, 4, RED));
+        let past = |rect| Sprite::new(AtlasRegion { rect, ..region });
+
+        let wide = past(Rect::new(2, 3, 10, 10));
+        assert_eq!(drawn(3, 1, wide, &atlas), [RED, RED, Rgba::TRANSPARENT]);
+        let stretched = wide.with_scale(Scale::Bilinear {
+            width: 3,
+            height: 1,
+        });
+        assert_eq!(drawn(3, 1, stretched, &atlas), [RED; 3]);
+        let outside = past(Rect::new(9, 0, 2, 2));
+        assert_eq!(drawn(2, 1, outside, &atlas), [Rgba::TRANSPARENT; 2]);
+        let other_page = Sprite::new(AtlasRegion { page: 5, ..region });
+        assert_eq!(drawn(1, 1, other_page, &atlas), [Rgba::TRANSPARENT]);
+    }
+}
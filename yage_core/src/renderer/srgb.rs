@@ -0,0 +1,111 @@
+//! sRGB transfer functions as lookup tables, built at compile time since `no_std` has no
+//! `powf`. linear values are 16 bits, so dark colours keep their precision
+
+/// the linear light of every 8-bit sRGB level, `0..=u16::MAX`
+pub static SRGB_TO_LINEAR: [u16; 256] = srgb_to_linear_table();
+
+/// the sRGB level of linear light, indexed by its top 12 bits
+pub static LINEAR_TO_SRGB: [u8; 4096] = linear_to_srgb_table();
+
+pub fn srgb_to_linear(level: u8) -> u16 {
+    SRGB_TO_LINEAR[level as usize]
+}
+
+pub fn linear_to_srgb(light: u16) -> u8 {
+    LINEAR_TO_SRGB[(light >> 4) as usize]
+}
+
+const fn srgb_to_linear_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let c = i as f64 / 255.0;
+        let light = match c <= 0.04045 {
+            true => c / 12.92,
+            false => pow((c + 0.055) / 1.055, 2.4),
+        };
+        table[i] = (light * 65535.0 + 0.5) as u16;
+        i += 1;
+    }
+    table
+}
+
+const fn linear_to_srgb_table() -> [u8; 4096] {
+    let mut table = [0; 4096];
+    let mut i = 0;
+    while i < 4096 {
+        // the middle of the 16 linear values that share this entry
+        let light = (i * 16 + 8) as f64 / 65535.0;
+        let c = match light <= 0.0031308 {
+            true => light * 12.92,
+            false => 1.055 * pow(light, 1.0 / 2.4) - 0.055,
+        };
+        table[i] = (c * 255.0 + 0.5) as u8;
+        i += 1;
+    }
+    table
+}
+
+/// `x^y` for `x` in `0..=1` and positive `y`, good to far more digits than the tables keep
+const fn pow(x: f64, y: f64) -> f64 {
+    match x <= 0.0 {
+        true => 0.0,
+        false => exp(y * ln(x)),
+    }
+}
+
+const fn ln(x: f64) -> f64 {
+    // x = m * 2^e with m in 0.5..=1, then the atanh series, which converges fast that close to 1
+    let (mut m, mut e) = (x, 0);
+    while m < 0.5 {
+        m *= 2.0;
+        e -= 1;
+    }
+    while m > 1.0 {
+        m /= 2.0;
+        e += 1;
+    }
+    let z = (m - 1.0) / (m + 1.0);
+    let (mut term, mut sum, mut k) = (z, 0.0, 1);
+    while k < 40 {
+        sum += term / k as f64;
+        term *= z * z;
+        k += 2;
+    }
+    2.0 * sum + e as f64 * core::f64::consts::LN_2
+}
+
+const fn exp(x: f64) -> f64 {
+    // halve until the taylor series is quick, then square back up
+    let (mut r, mut halvings) = (x, 0);
+    while r < -0.5 || r > 0.5 {
+        r /= 2.0;
+        halvings += 1;
+    }
+    let (mut term, mut sum, mut n) = (1.0, 1.0, 1);
+    while n < 20 {
+        term *= r / n as f64;
+        sum += term;
+        n += 1;
+    }
+    while halvings > 0 {
+        sum *= sum;
+        halvings -= 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_round_trip() {
+        for level in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(level)), level);
+        }
+        assert_eq!(srgb_to_linear(255), u16::MAX);
+        // sRGB 188 is about half the light of white
+        assert!(srgb_to_linear(188).abs_diff(u16::MAX / 2) < 200);
+    }
+}
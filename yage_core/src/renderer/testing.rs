@@ -0,0 +1,72 @@
+//! a bare `Renderer` over straight RGBA for tests
+
+use super::{Renderer, Rgba};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// `rows` shows transparent pixels as `.` and everything else as `#`
+pub(crate) struct Grid {
+    pub width: u32,
+    pub pixels: Vec<Rgba>,
+}
+
+impl Grid {
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixels = vec![Rgba::TRANSPARENT; (width * height) as usize];
+        Self { width, pixels }
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        let glyph = |p: &Rgba| if p.a == 0 { '.' } else { '#' };
+        let rows = self.pixels.chunks(self.width as usize);
+        rows.map(|row| row.iter().map(glyph).collect()).collect()
+    }
+}
+
+impl Renderer for Grid {
+    type Pixel = Rgba;
+    type Error = ();
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.pixels.len() as u32 / self.width
+    }
+
+    fn data(&self) -> &[Rgba] {
+        &self.pixels
+    }
+
+    fn data_mut(&mut self) -> &mut [Rgba] {
+        &mut self.pixels
+    }
+
+    fn sync(&mut self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn pixel(&mut self, x: u32, y: u32, pix: Rgba) -> Result<(), ()> {
+        let index = (y * self.width + x) as usize;
+        self.pixels[index] = pix;
+        Ok(())
+    }
+
+    fn arc(&mut self, _: u32, _: u32, _: u8, _: Rgba) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn circle(&mut self, _: u32, _: u32, _: i32, _: Rgba) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn rect(&mut self, _: u32, _: u32, _: u32, _: u32) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn overwrite_with(&mut self, _: u32, _: u32, _: u32, _: u32, _: &[Rgba]) -> Result<(), ()> {
+        Err(())
+    }
+}
@@ -0,0 +1,335 @@
+//! text on any `Renderer`: laying out strings in lines, wrapping and aligning them, and
+//! drawing them glyph by glyph with either kind of `Font`
+
+use super::math::floor;
+use super::{Blend, PixelFormat, Renderer, Rgba};
+use crate::asset::font::{BitmapFont, GlyphBitmap, TrueType};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// what the text layer needs of a font, in pixels. `&mut self` so glyphs can be made as
+/// they are needed
+pub trait Glyphs {
+    /// from the top of a line down to its baseline
+    fn ascent(&self) -> f32;
+
+    /// from one baseline to the next
+    fn line_height(&self) -> f32;
+
+    fn advance(&mut self, c: char) -> f32;
+
+    fn glyph(&mut self, c: char) -> Option<&GlyphBitmap>;
+}
+
+/// chars the font doesn't have are drawn as `?`, if it has that
+impl Glyphs for BitmapFont {
+    fn ascent(&self) -> f32 {
+        BitmapFont::ascent(self) as f32
+    }
+
+    fn line_height(&self) -> f32 {
+        BitmapFont::line_height(self) as f32
+    }
+
+    fn advance(&mut self, c: char) -> f32 {
+        let glyph = BitmapFont::glyph(self, c).or_else(|| BitmapFont::glyph(self, '?'));
+        glyph.map_or(0.0, |glyph| glyph.advance as f32)
+    }
+
+    fn glyph(&mut self, c: char) -> Option<&GlyphBitmap> {
+        let glyph = BitmapFont::glyph(self, c).or_else(|| BitmapFont::glyph(self, '?'));
+        glyph.map(|glyph| &glyph.bitmap)
+    }
+}
+
+/// a `TrueType` font at one size, keeping every glyph it rasterized
+#[derive(Debug, Clone)]
+pub struct ScaledFont<'f> {
+    font: &'f TrueType,
+    px: f32,
+    cache: BTreeMap<u16, GlyphBitmap>,
+}
+
+impl<'f> ScaledFont<'f> {
+    /// `px` pixels per em
+    pub fn new(font: &'f TrueType, px: f32) -> Self {
+        Self {
+            font,
+            px,
+            cache: BTreeMap::new(),
+        }
+    }
+
+    pub fn font(&self) -> &'f TrueType {
+        self.font
+    }
+
+    pub fn px(&self) -> f32 {
+        self.px
+    }
+
+    /// how many glyphs are cached
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl Glyphs for ScaledFont<'_> {
+    fn ascent(&self) -> f32 {
+        self.font.ascender() as f32 * self.font.scale(self.px)
+    }
+
+    fn line_height(&self) -> f32 {
+        let font = self.font;
+        let units = font.ascender() as i32 - font.descender() as i32 + font.line_gap() as i32;
+        units as f32 * font.scale(self.px)
+    }
+
+    fn advance(&mut self, c: char) -> f32 {
+        let glyph = self.font.glyph_index(c);
+        self.font.advance(glyph) as f32 * self.font.scale(self.px)
+    }
+
+    fn glyph(&mut self, c: char) -> Option<&GlyphBitmap> {
+        let glyph = self.font.glyph_index(c);
+        let (font, px) = (self.font, self.px);
+        // a broken glyph is drawn as nothing rather than failing the whole string
+        let bitmap = self
+            .cache
+            .entry(glyph)
+            .or_insert_with(|| font.rasterize(glyph, px).unwrap_or_default());
+        Some(bitmap)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// one laid out line, without the spaces it was broken at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line<'t> {
+    pub text: &'t str,
+    pub width: f32,
+}
+
+/// splits `text` into lines at `\n`, and wraps them to `max_width` if there is one. lines
+/// are broken at spaces where possible and inside words where not
+pub fn layout<'t, G: Glyphs>(font: &mut G, text: &'t str, max_width: Option<f32>) -> Vec<Line<'t>> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.trim_end_matches('\r');
+        wrap(font, paragraph, max_width, &mut lines);
+    }
+    lines
+}
+
+fn wrap<'t, G: Glyphs>(
+    font: &mut G,
+    paragraph: &'t str,
+    max_width: Option<f32>,
+    lines: &mut Vec<Line<'t>>,
+) {
+    let mut start = 0;
+    let mut width = 0.0;
+    // the end of the last word on this line and the width up to it
+    let mut last_break = None;
+    let mut previous = ' ';
+    for (i, c) in paragraph.char_indices() {
+        let advance = font.advance(c);
+        if c == ' ' {
+            if previous != ' ' && i > start {
+                last_break = Some((i, width));
+            }
+        } else if max_width.is_some_and(|max| width + advance > max) && i > start {
+            // a word at a time if we can, or whatever still fits of one that is too long
+            let (end, end_width) = last_break.take().unwrap_or((i, width));
+            lines.push(Line {
+                text: &paragraph[start..end],
+                width: end_width,
+            });
+            start = end + (paragraph[end..].len() - paragraph[end..].trim_start_matches(' ').len());
+            width = width_of(font, &paragraph[start..i]);
+        }
+        width += advance;
+        previous = c;
+    }
+    let text = paragraph[start..].trim_end_matches(' ');
+    let width = width_of(font, text);
+    lines.push(Line { text, width });
+}
+
+fn width_of<G: Glyphs>(font: &mut G, text: &str) -> f32 {
+    text.chars().map(|c| font.advance(c)).sum()
+}
+
+/// the width of the widest line and the height of all of them, for laying out UI around
+pub fn measure<G: Glyphs>(font: &mut G, text: &str, max_width: Option<f32>) -> (f32, f32) {
+    let lines = layout(font, text, max_width);
+    let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+    (width, lines.len() as f32 * font.line_height())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub color: Rgba,
+    /// within `max_width`, or the widest line without one
+    pub align: Align,
+    /// wraps lines longer than this
+    pub max_width: Option<f32>,
+    pub blend: Blend,
+}
+
+impl TextStyle {
+    /// left aligned and unwrapped, over what is there
+    pub const fn new(color: Rgba) -> Self {
+        Self {
+            color,
+            align: Align::Left,
+            max_width: None,
+            blend: Blend::OVER,
+        }
+    }
+}
+
+/// draws `text` with the top left corner of its box at `(x, y)`, clipped to the renderer.
+/// glyphs go on whole pixels
+pub fn draw_text<R, G>(
+    renderer: &mut R,
+    font: &mut G,
+    text: &str,
+    x: i32,
+    y: i32,
+    style: &TextStyle,
+) where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+    G: Glyphs,
+{
+    let lines = layout(font, text, style.max_width);
+    let widest = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+    let width = style.max_width.unwrap_or(widest);
+    let (ascent, line_height) = (font.ascent(), font.line_height());
+
+    for (index, line) in lines.iter().enumerate() {
+        let offset = match style.align {
+            Align::Left => 0.0,
+            Align::Center => (width - line.width) / 2.0,
+            Align::Right => width - line.width,
+        };
+        let mut pen = x as f32 + offset;
+        let baseline = floor(y as f32 + ascent + index as f32 * line_height + 0.5) as i32;
+        for c in line.text.chars() {
+            let advance = font.advance(c);
+            if let Some(glyph) = font.glyph(c) {
+                let left = floor(pen + 0.5) as i32 + glyph.left;
+                draw_glyph(renderer, glyph, left, baseline + glyph.top, style);
+            }
+            pen += advance;
+        }
+    }
+}
+
+fn draw_glyph<R>(renderer: &mut R, glyph: &GlyphBitmap, x: i32, y: i32, style: &TextStyle)
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    let (target_width, target_height) = (renderer.width() as i64, renderer.height() as i64);
+    let columns = (x as i64).max(0)..(x as i64 + glyph.width as i64).min(target_width);
+    let rows = (y as i64).max(0)..(y as i64 + glyph.height as i64).min(target_height);
+    let data = renderer.data_mut();
+    for screen_y in rows {
+        for screen_x in columns.clone() {
+            let gx = (screen_x - x as i64) as usize;
+            let gy = (screen_y - y as i64) as usize;
+            let coverage = glyph.coverage[gy * glyph.width as usize + gx];
+            if coverage == 0 {
+                continue;
+            }
+            let alpha = (style.color.a as u16 * coverage as u16 + 127) / 255;
+            let src = Rgba {
+                a: alpha as u8,
+                ..style.color
+            };
+            let index = (screen_y * target_width + screen_x) as usize;
+            data[index] = style.blend.apply_to(src, data[index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::font::Font;
+    use crate::renderer::testing::Grid;
+
+    /// 'a' is a 2x2 corner moving the pen 3, ' ' moves it 2
+    fn tiny_font() -> BitmapFont {
+        let bdf = "STARTFONT 2.1
+FONTBOUNDINGBOX 2 3 0 -1
+STARTCHAR a
+ENCODING 97
+DWIDTH 3 0
+BBX 2 2 0 0
+BITMAP
+C0
+80
+ENDCHAR
+STARTCHAR space
+ENCODING 32
+DWIDTH 2 0
+BBX 0 0 0 0
+BITMAP
+ENDCHAR
+ENDFONT
+";
+        match Font::decode(bdf.as_bytes()).unwrap() {
+            Font::Bitmap(font) => font,
+            Font::TrueType(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn wraps_and_measures() {
+        let mut font = tiny_font();
+        fn texts(lines: Vec<Line<'_>>) -> Vec<&str> {
+            lines.iter().map(|line| line.text).collect()
+        }
+        assert_eq!(
+            texts(layout(&mut font, "aa aa  aa", Some(14.0))),
+            ["aa aa", "aa"]
+        );
+        assert_eq!(
+            texts(layout(&mut font, "aaaaa", Some(7.0))),
+            ["aa", "aa", "a"]
+        );
+        assert_eq!(texts(layout(&mut font, "a \n\na", None)), ["a", "", "a"]);
+        assert_eq!(measure(&mut font, "aa aa aa", Some(14.0)), (14.0, 6.0));
+        assert_eq!(measure(&mut font, "", None), (0.0, 3.0));
+    }
+
+    #[test]
+    fn draws_aligned() {
+        let mut font = tiny_font();
+        let mut grid = Grid::new(8, 6);
+        let style = TextStyle {
+            align: Align::Right,
+            max_width: Some(8.0),
+            ..TextStyle::new(Rgba::WHITE)
+        };
+        draw_text(&mut grid, &mut font, "a\naa", 0, 0, &style);
+        assert_eq!(
+            grid.rows(),
+            [".....##.", ".....#..", "........", "..##.##.", "..#..#..", "........",]
+        );
+    }
+}
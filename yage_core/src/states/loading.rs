@@ -18,6 +18,30 @@ pub struct LoaderContext<Fs, Net, C> {
     pub(super) asset_cache: alloc::sync::Arc<C>,
 }
 
+impl<Fs, Net, C> LoaderContext<Fs, Net, C> {
+    pub fn filesystem(&self) -> &Fs {
+        &self.filesystem
+    }
+
+    /// mostly useful for registering decoders before anything gets loaded
+    pub fn filesystem_mut(&mut self) -> &mut Fs {
+        &mut self.filesystem
+    }
+
+    pub fn networking(&self) -> &Net {
+        &self.networking
+    }
+
+    pub fn networking_mut(&mut self) -> &mut Net {
+        &mut self.networking
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn cache(&self) -> &alloc::sync::Arc<C> {
+        &self.asset_cache
+    }
+}
+
 pub struct Loading<L, Fs, Net, C> {
     pub(super) loader_plugin: TrackedPlugin<L, LoaderContext<Fs, Net, C>>,
     pub(super) loader_context: LoaderContext<Fs, Net, C>,
@@ -3,6 +3,7 @@ use super::TrackedPlugin;
 use crate::asset::Cache;
 use crate::asset::Loader;
 use crate::machine_cog::{Cog, TupleHelper};
+use alloc::sync::Arc;
 use core::future::Future;
 use core::net::SocketAddr;
 use core::num::NonZero;
@@ -20,9 +21,10 @@ enum State<F, N, E, C, FFut, NFut> {
         network_fut: Option<NFut>,
         file: Option<F>,
         net: Option<N>,
+        cache: Arc<C>,
     },
     Done {
-        loader: Result<(F, N, C), E>,
+        loader: Result<(F, N, Arc<C>), E>,
     },
     Panic,
 }
@@ -76,13 +78,16 @@ where
         let search_paths = SearchPaths { paths };
         match state {
             State::None => {
-                let file_fut = F::init(search_paths.clone());
-                let net_fut = N::init(input.input.0.addr);
+                // made first so both loaders fill the cache the loader plugin sees
+                let cache = Arc::new(C::from(search_paths.clone()));
+                let file_fut = F::init(search_paths, cache.clone());
+                let net_fut = N::init(input.input.0.addr, cache.clone());
                 *state = State::Polling {
                     file_fut: Some(file_fut),
                     network_fut: Some(net_fut),
                     file: None,
                     net: None,
+                    cache,
                 };
             }
             State::Polling {
@@ -90,13 +95,14 @@ where
                 network_fut,
                 file,
                 net,
+                cache,
             } => {
                 match (file.is_some(), net.is_some()) {
                     (true, true) => {
                         let file = file.take().unwrap();
                         let net = net.take().unwrap();
                         *state = State::Done {
-                            loader: Ok((file, net, C::from(search_paths))),
+                            loader: Ok((file, net, cache.clone())),
                         };
                         // we have to wake right here and return pending to satisfy borrowcheck
                         cx.waker().wake_by_ref();
@@ -145,7 +151,7 @@ where
                     loader_context: LoaderContext {
                         filesystem,
                         networking,
-                        asset_cache: alloc::sync::Arc::new(cache),
+                        asset_cache: cache,
                     },
                     loader_plugin: TrackedPlugin::new(input.input.1.take().expect("lmao")),
                     cfgs: core::mem::take(&mut input.input.0),
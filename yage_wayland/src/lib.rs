@@ -136,6 +136,8 @@ pub mod wl {
 
 mod xdg;
 
+pub mod shm;
+
 pub mod errors;
 
 pub use errors::{Error, ErrorKind, Result};
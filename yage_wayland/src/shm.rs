@@ -0,0 +1,572 @@
+//! software presentation: a swapchain of `wl_shm` buffers sharing one memfd, and a `Renderer`
+//! that puts whatever another renderer drew on a `wl_surface` at every `sync`
+
+use std::ffi::{c_char, c_int, c_uint, c_void};
+use std::io;
+use std::mem;
+use std::ops::Range;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use yage_core::renderer::{PixelFormat, Renderer, Rgba};
+
+use crate::wayland::protocol_structs::{
+    buffer, shm, shm_pool, surface, WlBuffer, WlShm, WlShmPool, WlSurface,
+};
+use crate::wl::{DummyObjectData, Event, ObjectData, ObjectId, Protocol, QueuedWayland};
+
+/// fewer and drawing waits on the compositor, more and it only adds latency
+pub const MIN_BUFFERS: usize = 2;
+pub const MAX_BUFFERS: usize = 3;
+
+const MFD_CLOEXEC: c_uint = 1;
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const MAP_SHARED: c_int = 1;
+const BUFFER_RELEASE_EVENT: u16 = 0;
+/// every format we hand out is 32 bits per pixel
+const BYTES_PER_PIXEL: usize = 4;
+
+extern "C" {
+    fn memfd_create(name: *const c_char, flags: c_uint) -> c_int;
+    fn ftruncate(fd: c_int, len: i64) -> c_int;
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// an anonymous file in memory, mapped read-write, that the compositor maps too
+struct ShmFile {
+    fd: OwnedFd,
+    /// dangling while `len` is 0, since empty maps aren't allowed
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// the mapping is only touched through `&mut self`
+unsafe impl Send for ShmFile {}
+
+impl ShmFile {
+    fn new(len: usize) -> io::Result<Self> {
+        let fd = unsafe { memfd_create(c"yage-shm".as_ptr(), MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut file = Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            ptr: NonNull::dangling(),
+            len: 0,
+        };
+        file.resize(len)?;
+        Ok(file)
+    }
+
+    /// keeps what the file held up to `len`. if this fails the old mapping stays, which is
+    /// only sound when growing, the compositor's mapping of it isn't touched either way
+    fn resize(&mut self, len: usize) -> io::Result<()> {
+        let size = i64::try_from(len).map_err(|_| io::ErrorKind::InvalidInput)?;
+        if unsafe { ftruncate(self.fd.as_raw_fd(), size) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if len == 0 {
+            self.unmap();
+            return Ok(());
+        }
+        let fd = self.fd.as_raw_fd();
+        let flags = PROT_READ | PROT_WRITE;
+        let ptr = unsafe { mmap(ptr::null_mut(), len, flags, MAP_SHARED, fd, 0) };
+        if ptr as usize == usize::MAX {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = NonNull::new(ptr.cast()).ok_or(io::ErrorKind::Other)?;
+        self.unmap();
+        (self.ptr, self.len) = (ptr, len);
+        Ok(())
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn unmap(&mut self) {
+        if self.len != 0 {
+            unsafe { munmap(self.ptr.as_ptr().cast(), self.len) };
+            (self.ptr, self.len) = (NonNull::dangling(), 0);
+        }
+    }
+}
+
+impl Drop for ShmFile {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
+
+/// whether the compositor may still read a buffer. set when it is committed, cleared by
+/// `wl_buffer.release`
+#[derive(Debug, Default)]
+struct BufferState {
+    busy: AtomicBool,
+}
+
+impl ObjectData for BufferState {
+    fn on_event(
+        self: Arc<Self>,
+        _handle: &QueuedWayland,
+        message: Event<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        if message.opcode == BUFFER_RELEASE_EVENT {
+            self.busy.store(false, Ordering::Release);
+        }
+        Some(self)
+    }
+
+    fn on_destruction(&self, _id: ObjectId) {}
+}
+
+struct Slot {
+    buffer: WlBuffer,
+    state: Arc<BufferState>,
+}
+
+impl Slot {
+    fn busy(&self) -> bool {
+        self.state.busy.load(Ordering::Acquire)
+    }
+
+    fn destroy(self) {
+        // a dead connection takes its buffers with it
+        let _ = self.buffer.send_request(buffer::Request::Destroy);
+    }
+}
+
+/// `wl_shm` buffers of one size and format, back to back in one pool. draw into one the
+/// compositor is done with, `present` it, and it is busy again until the compositor
+/// releases it
+pub struct Swapchain {
+    pool: WlShmPool,
+    file: ShmFile,
+    slots: Vec<Slot>,
+    /// buffers from before a resize the compositor hadn't released, and the bytes they use.
+    /// nothing is drawn over those bytes until they are released
+    retired: Vec<(Slot, Range<usize>)>,
+    /// where the first of `slots` starts in the pool
+    base: usize,
+    count: usize,
+    width: u32,
+    height: u32,
+    /// a `wl_shm` format code
+    format: u32,
+}
+
+impl Swapchain {
+    /// `count` is clamped to double or triple buffering. `format` is a `wl_shm` format code
+    /// with 32 bits per pixel, like `shm::FORMAT_XRGB8888`
+    pub fn new(
+        shm: &WlShm,
+        width: u32,
+        height: u32,
+        format: u32,
+        count: usize,
+    ) -> crate::Result<Self> {
+        let count = count.clamp(MIN_BUFFERS, MAX_BUFFERS);
+        let size = pool_size(width, height, count)?;
+        let file = ShmFile::new(size).map_err(shm_error)?;
+        let request = shm::Request::CreatePool {
+            fd: file.fd.as_fd(),
+            size: size as i32,
+        };
+        let pool = shm.cons(request, Arc::new(DummyObjectData))?;
+        let mut chain = Self {
+            pool,
+            file,
+            slots: Vec::with_capacity(count),
+            retired: Vec::new(),
+            base: 0,
+            count,
+            width,
+            height,
+            format,
+        };
+        chain.create_buffers()?;
+        Ok(chain)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// how many buffers the compositor hasn't released yet
+    pub fn busy(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.busy()).count()
+    }
+
+    /// a buffer free to draw into, if the compositor has released any
+    pub fn acquire(&self) -> Option<usize> {
+        self.slots.iter().position(|slot| !slot.busy())
+    }
+
+    /// the bytes of buffer `index`, row by row, little-endian pixels
+    pub fn bytes_mut(&mut self, index: usize) -> &mut [u8] {
+        let range = self.frame(index);
+        &mut self.file.bytes_mut()[range]
+    }
+
+    /// attaches buffer `index` to `surface`, damages all of it and commits. the buffer is
+    /// busy until the compositor releases it
+    pub fn present(&mut self, index: usize, surface: &WlSurface) -> crate::Result<()> {
+        self.destroy_released();
+        let slot = &self.slots[index];
+        slot.state.busy.store(true, Ordering::Release);
+        let (width, height) = (self.width as i32, self.height as i32);
+        surface.send_request(surface::Request::Attach {
+            buffer: slot.buffer.id(),
+            x_pos: 0,
+            y_pos: 0,
+        })?;
+        // surface coordinates only match buffer pixels while the buffer scale is 1
+        let damage = match surface.version() >= 4 {
+            true => surface::Request::DamageBuffer {
+                x_pos: 0,
+                y_pos: 0,
+                width,
+                height,
+            },
+            false => surface::Request::Damage {
+                x_pos: 0,
+                y_pos: 0,
+                width,
+                height,
+            },
+        };
+        surface.send_request(damage)?;
+        surface.send_request(surface::Request::Commit)
+    }
+
+    /// makes every buffer the new size. buffers the compositor still holds stay alive until
+    /// it releases them, and the new ones go where they don't overlap. nothing changes if
+    /// this fails
+    pub fn resize(&mut self, width: u32, height: u32) -> crate::Result<()> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        self.destroy_released();
+        let size = pool_size(width, height, self.count)?;
+        let base = first_fit(self.in_use(), size);
+        let end = base
+            .checked_add(size)
+            .filter(|&end| i32::try_from(end).is_ok())
+            .ok_or(crate::ErrorKind::AllocError("wl_shm_pool").into_error())?;
+        // pools can't shrink, so a smaller size just leaves the end unused
+        if end > self.file.len {
+            self.file.resize(end).map_err(shm_error)?;
+            let size = end as i32;
+            self.pool.send_request(shm_pool::Request::Resize { size })?;
+        }
+        for (index, slot) in mem::take(&mut self.slots).into_iter().enumerate() {
+            let range = self.frame(index);
+            self.retired.push((slot, range));
+        }
+        self.destroy_released();
+        (self.width, self.height, self.base) = (width, height, base);
+        self.create_buffers()
+    }
+
+    fn frame_size(&self) -> usize {
+        self.width as usize * self.height as usize * BYTES_PER_PIXEL
+    }
+
+    /// the bytes of buffer `index` in the pool
+    fn frame(&self, index: usize) -> Range<usize> {
+        let frame = self.frame_size();
+        let start = self.base + index * frame;
+        start..start + frame
+    }
+
+    /// the bytes of the pool the compositor may still read
+    fn in_use(&self) -> Vec<Range<usize>> {
+        let retired = self.retired.iter().map(|(_, range)| range.clone());
+        let busy = (0..self.slots.len()).filter(|&index| self.slots[index].busy());
+        retired.chain(busy.map(|index| self.frame(index))).collect()
+    }
+
+    fn create_buffers(&mut self) -> crate::Result<()> {
+        for index in 0..self.count {
+            let state = Arc::new(BufferState::default());
+            let request = shm_pool::Request::CreateBuffer {
+                offset: self.frame(index).start as i32,
+                width: self.width as i32,
+                height: self.height as i32,
+                stride: (self.width as usize * BYTES_PER_PIXEL) as i32,
+                format: self.format,
+            };
+            let buffer = self.pool.cons(request, state.clone())?;
+            self.slots.push(Slot { buffer, state });
+        }
+        Ok(())
+    }
+
+    /// destroys the retired buffers the compositor has released
+    fn destroy_released(&mut self) {
+        for (slot, _) in self.retired.extract_if(.., |(slot, _)| !slot.busy()) {
+            slot.destroy();
+        }
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        // the compositor keeps its mapping of buffers it still shows, so busy ones can go too
+        self.slots.drain(..).for_each(Slot::destroy);
+        self.retired.drain(..).for_each(|(slot, _)| slot.destroy());
+        let _ = self.pool.send_request(shm_pool::Request::Destroy);
+    }
+}
+
+/// all `count` frames of a pool, which `wl_shm` sizes with an `i32`. buffers can't be empty
+fn pool_size(width: u32, height: u32, count: usize) -> crate::Result<usize> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(BYTES_PER_PIXEL * count))
+        .filter(|&size| size != 0 && i32::try_from(size).is_ok())
+        .ok_or(crate::ErrorKind::AllocError("wl_shm_pool").into_error())
+}
+
+/// the lowest offset with `len` bytes that don't overlap any of `used`
+fn first_fit(mut used: Vec<Range<usize>>, len: usize) -> usize {
+    used.sort_by_key(|range| range.start);
+    let mut offset = 0;
+    for range in used {
+        if range.start >= offset + len {
+            break;
+        }
+        offset = offset.max(range.end);
+    }
+    offset
+}
+
+fn shm_error(error: io::Error) -> crate::Error {
+    crate::ErrorKind::AllocError("wl_shm_pool")
+        .into_error()
+        .with_payload(error)
+}
+
+#[derive(Debug)]
+pub enum PresentError<E> {
+    /// the renderer being presented failed
+    Renderer(E),
+    Wayland(crate::Error),
+}
+
+/// shows what `R` draws on a surface. every `sync` with something new copies the frame into a
+/// buffer the compositor is done with and commits it. if it holds all of them, the frame waits
+/// for a later `sync`.
+///
+/// pixel formats with alpha go out as premultiplied `argb8888`, the rest as `xrgb8888`
+pub struct ShmRenderer<R> {
+    inner: R,
+    swapchain: Swapchain,
+    surface: WlSurface,
+    /// drawn on but not shown yet
+    pending: bool,
+}
+
+impl<R> ShmRenderer<R>
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    /// `buffers` is clamped to double or triple buffering
+    pub fn new(inner: R, shm: &WlShm, surface: WlSurface, buffers: usize) -> crate::Result<Self> {
+        let format = match <R::Pixel as PixelFormat>::HAS_ALPHA {
+            true => shm::FORMAT_ARGB8888,
+            false => shm::FORMAT_XRGB8888,
+        };
+        let (width, height) = (inner.width(), inner.height());
+        Ok(Self {
+            swapchain: Swapchain::new(shm, width, height, format, buffers)?,
+            inner,
+            surface,
+            pending: true,
+        })
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// drawing through this doesn't count as drawing for `sync` unless `R` tracks it itself
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn swapchain(&self) -> &Swapchain {
+        &self.swapchain
+    }
+
+    pub fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    /// false when every buffer is still with the compositor
+    fn present(&mut self) -> crate::Result<bool> {
+        let (width, height) = (self.inner.width(), self.inner.height());
+        // there are no empty buffers, so an empty frame waits until there is something to show
+        if width == 0 || height == 0 {
+            return Ok(false);
+        }
+        self.swapchain.resize(width, height)?;
+        let Some(index) = self.swapchain.acquire() else {
+            return Ok(false);
+        };
+        let bytes = self.swapchain.bytes_mut(index);
+        for (out, &pixel) in bytes
+            .chunks_exact_mut(BYTES_PER_PIXEL)
+            .zip(self.inner.data())
+        {
+            out.copy_from_slice(&premultiplied(pixel).to_le_bytes());
+        }
+        self.swapchain.present(index, &self.surface)?;
+        Ok(true)
+    }
+}
+
+/// `0xAARRGGBB` with the colour multiplied by alpha, as `wl_shm` expects
+fn premultiplied<P: PixelFormat>(pixel: P) -> u32 {
+    let Rgba { r, g, b, a } = pixel.to_rgba();
+    let a = if P::HAS_ALPHA { a as u32 } else { 255 };
+    let mul = |channel: u8| (channel as u32 * a + 127) / 255;
+    a << 24 | mul(r) << 16 | mul(g) << 8 | mul(b)
+}
+
+impl<R> Renderer for ShmRenderer<R>
+where
+    R: Renderer,
+    R::Pixel: PixelFormat,
+{
+    type Pixel = R::Pixel;
+    type Error = PresentError<R::Error>;
+
+    fn width(&self) -> u32 {
+        self.inner.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.inner.height()
+    }
+
+    fn data(&self) -> &[R::Pixel] {
+        self.inner.data()
+    }
+
+    fn data_mut(&mut self) -> &mut [R::Pixel] {
+        self.pending = true;
+        self.inner.data_mut()
+    }
+
+    /// true if a frame went to the compositor
+    fn sync(&mut self) -> Result<bool, Self::Error> {
+        let drawn = self.inner.sync().map_err(PresentError::Renderer)?;
+        self.pending |= drawn;
+        if !self.pending {
+            return Ok(false);
+        }
+        let shown = self.present().map_err(PresentError::Wayland)?;
+        self.pending = !shown;
+        Ok(shown)
+    }
+
+    fn pixel(&mut self, x: u32, y: u32, pix: R::Pixel) -> Result<(), Self::Error> {
+        self.pending = true;
+        self.inner.pixel(x, y, pix).map_err(PresentError::Renderer)
+    }
+
+    fn arc(&mut self, x: u32, y: u32, parts: u8, pix: R::Pixel) -> Result<(), Self::Error> {
+        self.pending = true;
+        self.inner
+            .arc(x, y, parts, pix)
+            .map_err(PresentError::Renderer)
+    }
+
+    fn circle(&mut self, x: u32, y: u32, radius: i32, pix: R::Pixel) -> Result<(), Self::Error> {
+        self.pending = true;
+        self.inner
+            .circle(x, y, radius, pix)
+            .map_err(PresentError::Renderer)
+    }
+
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), Self::Error> {
+        self.pending = true;
+        self.inner
+            .rect(x, y, width, height)
+            .map_err(PresentError::Renderer)
+    }
+
+    fn overwrite_with(
+        &mut self,
+        x: u32,
+        y: u32,
+        buffer_width: u32,
+        buffer_height: u32,
+        buffer: &[R::Pixel],
+    ) -> Result<(), Self::Error> {
+        self.pending = true;
+        self.inner
+            .overwrite_with(x, y, buffer_width, buffer_height, buffer)
+            .map_err(PresentError::Renderer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yage_core::renderer::format::{Argb8888, Xrgb8888};
+
+    #[test]
+    fn premultiplies() {
+        assert_eq!(premultiplied(Argb8888(0x80ff_4000)), 0x8080_2000);
+        assert_eq!(premultiplied(Xrgb8888(0x00ff_4000)), 0xffff_4000);
+        assert_eq!(premultiplied(Rgba::TRANSPARENT), 0);
+    }
+
+    #[test]
+    fn shared_memory() {
+        let mut file = ShmFile::new(16).unwrap();
+        file.bytes_mut().fill(7);
+        file.resize(64).unwrap();
+        assert_eq!(file.bytes_mut().len(), 64);
+        assert_eq!(file.bytes_mut()[15], 7);
+        file.bytes_mut()[63] = 1;
+        assert!(pool_size(1 << 16, 1 << 16, 2).is_err());
+        assert!(pool_size(0, 8, 2).is_err());
+        assert_eq!(pool_size(4, 2, 3).unwrap(), 96);
+    }
+
+    #[test]
+    fn busy_buffers_keep_their_bytes() {
+        assert_eq!(first_fit(vec![], 8), 0);
+        assert_eq!(first_fit(vec![16..24, 0..8], 8), 8);
+        assert_eq!(first_fit(vec![0..8, 4..12, 12..16], 4), 16);
+        assert_eq!(first_fit(vec![0..8, 20..24], 16), 24);
+    }
+}
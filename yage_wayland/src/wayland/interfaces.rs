@@ -45,7 +45,7 @@ pub static WL_SURFACE_IFACE: Interface = Interface::new("wl_surface", 6)
         Message::with_sig("commit", &[]),
         Message::with_sig("set_buffer_transform", &[ArgKind::Int]).since(2),
         Message::with_sig("set_buffer_scale", &[ArgKind::Int]).since(3),
-        Message::with_sig("damage_buffer", &[ArgKind::Int, ArgKind::Int]).since(4),
+        Message::with_sig("damage_buffer", &[ArgKind::Int; 4]).since(4),
         Message::with_sig("offset", &[ArgKind::Int, ArgKind::Int]).since(5),
     ])
     .events(&[
@@ -96,6 +96,34 @@ pub static WL_BUFFER_IFACE: Interface = Interface::new("wl_buffer", 1)
     .events(&[Message::new("release")])
     .interface(unsafe { &bindings::wl_buffer_interface });
 
+pub static WL_SHM_IFACE: Interface = Interface::new("wl_shm", 2)
+    .requests(&[
+        Message::with_sig("create_pool", &[ArgKind::New, ArgKind::Fd, ArgKind::Int])
+            .child(&WL_SHM_POOL_IFACE),
+        Message::new("release").destructor(true).since(2),
+    ])
+    .events(&[Message::with_sig("format", &[ArgKind::Uint])])
+    .interface(unsafe { &bindings::wl_shm_interface });
+
+pub static WL_SHM_POOL_IFACE: Interface = Interface::new("wl_shm_pool", 2)
+    .requests(&[
+        Message::with_sig(
+            "create_buffer",
+            &[
+                ArgKind::New,
+                ArgKind::Int,
+                ArgKind::Int,
+                ArgKind::Int,
+                ArgKind::Int,
+                ArgKind::Uint,
+            ],
+        )
+        .child(&WL_BUFFER_IFACE),
+        Message::DESTROY,
+        Message::with_sig("resize", &[ArgKind::Int]),
+    ])
+    .interface(unsafe { &bindings::wl_shm_pool_interface });
+
 pub static WL_SEAT_IFACE: Interface = Interface::new("wl_seat", 10)
     .requests(&[
         Message::with_sig("get_pointer", &[ArgKind::New]).child(&WL_POINTER_IFACE),
@@ -310,7 +310,7 @@ pub mod registry {
 
 protocol_struct! {
     pub WlCallback {
-        iface: WL_CALLBACK_IFACE,
+        iface: WL_CALLBACK_INTERFACE,
         request: callback::Request<'request>,
         event: callback::Event
     }
@@ -387,6 +387,9 @@ pub mod surface {
     pub const SURFACE_SET_INPUT_REGION_OPCODE: u16 = 5;
     pub const SURFACE_COMMIT_OPCODE: u16 = 6;
     pub const SURFACE_SET_BUFFER_TRANSFORM_OPCODE: u16 = 7;
+    pub const SURFACE_SET_BUFFER_SCALE_OPCODE: u16 = 8;
+    pub const SURFACE_DAMAGE_BUFFER_OPCODE: u16 = 9;
+    pub const SURFACE_OFFSET_OPCODE: u16 = 10;
 
     pub enum Request<'req> {
         Destroy,
@@ -415,9 +418,12 @@ pub mod surface {
         SetBufferScale {
             scale: i32,
         },
+        /// like `Damage`, but in buffer pixels rather than surface coordinates. since version 4
         DamageBuffer {
             x_pos: i32,
             y_pos: i32,
+            width: i32,
+            height: i32,
         },
         Offset {
             off_x: i32,
@@ -481,6 +487,167 @@ pub mod surface {
     impl<'req> ProtocolRequest<'req> for Request<'req> {
         type Protocol = super::WlSurface;
 
+        fn write(
+            self,
+            protocol: &Self::Protocol,
+            conn: &crate::wayland::StrongHandle,
+        ) -> crate::Result<(
+            Evt<ObjectId, std::os::unix::prelude::BorrowedFd<'req>>,
+            Option<(&'static crate::wl::Interface, u32)>,
+        )> {
+            use super::helpers;
+            let sender = protocol.id();
+            let request = |opcode: u16, (child_spec, args)| {
+                Ok((
+                    Evt {
+                        sender: sender.clone(),
+                        opcode,
+                        args,
+                    },
+                    child_spec,
+                ))
+            };
+            match self {
+                Self::Destroy => {
+                    let made = helpers::make(conn, &WL_SURFACE_IFACE, protocol.id(), [])?;
+                    request(SURFACE_DESTROY_OPCODE, made)
+                }
+                Self::Attach {
+                    buffer,
+                    x_pos,
+                    y_pos,
+                } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_BUFFER_IFACE,
+                        protocol.id(),
+                        [Arg::Object(buffer), Arg::Int(x_pos), Arg::Int(y_pos)],
+                    )?;
+                    request(SURFACE_ATTACH_OPCODE, made)
+                }
+                Self::Damage {
+                    x_pos,
+                    y_pos,
+                    width,
+                    height,
+                } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_SURFACE_IFACE,
+                        protocol.id(),
+                        [
+                            Arg::Int(x_pos),
+                            Arg::Int(y_pos),
+                            Arg::Int(width),
+                            Arg::Int(height),
+                        ],
+                    )?;
+                    request(SURFACE_DAMAGE_OPCODE, made)
+                }
+                Self::Frame => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_CALLBACK_INTERFACE,
+                        protocol.id(),
+                        [Arg::New(ObjectId::NIL_OBJECT_ID)],
+                    )?;
+                    request(SURFACE_FRAME_OPCODE, made)
+                }
+                Self::SetOpaqueRegion { region } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_REGION_IFACE,
+                        protocol.id(),
+                        [Arg::Object(region)],
+                    )?;
+                    request(SURFACE_SET_OPAQUE_REGION_OPCODE, made)
+                }
+                Self::SetInputRegion { region } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_REGION_IFACE,
+                        protocol.id(),
+                        [Arg::Object(region)],
+                    )?;
+                    request(SURFACE_SET_INPUT_REGION_OPCODE, made)
+                }
+                Self::Commit => {
+                    let made = helpers::make(conn, &WL_SURFACE_IFACE, protocol.id(), [])?;
+                    request(SURFACE_COMMIT_OPCODE, made)
+                }
+                Self::SetBufferTransform { transform } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_SURFACE_IFACE,
+                        protocol.id(),
+                        [Arg::Int(transform)],
+                    )?;
+                    request(SURFACE_SET_BUFFER_TRANSFORM_OPCODE, made)
+                }
+                Self::SetBufferScale { scale } => {
+                    let made =
+                        helpers::make(conn, &WL_SURFACE_IFACE, protocol.id(), [Arg::Int(scale)])?;
+                    request(SURFACE_SET_BUFFER_SCALE_OPCODE, made)
+                }
+                Self::DamageBuffer {
+                    x_pos,
+                    y_pos,
+                    width,
+                    height,
+                } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_SURFACE_IFACE,
+                        protocol.id(),
+                        [
+                            Arg::Int(x_pos),
+                            Arg::Int(y_pos),
+                            Arg::Int(width),
+                            Arg::Int(height),
+                        ],
+                    )?;
+                    request(SURFACE_DAMAGE_BUFFER_OPCODE, made)
+                }
+                Self::Offset { off_x, off_y } => {
+                    let made = helpers::make(
+                        conn,
+                        &WL_SURFACE_IFACE,
+                        protocol.id(),
+                        [Arg::Int(off_x), Arg::Int(off_y)],
+                    )?;
+                    request(SURFACE_OFFSET_OPCODE, made)
+                }
+                Self::__Phantom { never, .. } => match never {},
+            }
+        }
+    }
+}
+
+protocol_struct! {
+    pub WlBuffer {
+        iface: WL_BUFFER_IFACE,
+        request: buffer::Request<'request>,
+        event: buffer::Event
+    }
+}
+
+pub mod buffer {
+    use super::__prelude::*;
+
+    pub const BUFFER_DESTROY_OPCODE: u16 = 0;
+
+    pub enum Request<'req> {
+        Destroy,
+        #[doc(hidden)]
+        __Phantom {
+            _capture: PhantomData<&'req ()>,
+            never: Infallible,
+        },
+    }
+
+    impl<'req> ProtocolRequest<'req> for Request<'req> {
+        type Protocol = super::WlBuffer;
+
         fn write(
             self,
             protocol: &Self::Protocol,
@@ -492,67 +659,260 @@ pub mod surface {
             use super::helpers;
             match self {
                 Self::Destroy => {
-                    let (child_spec, args): (
-                        Option<_>,
-                        Vec<Arg<ObjectId, std::os::unix::prelude::BorrowedFd<'req>>>,
-                    ) = helpers::make(conn, &WL_SURFACE_IFACE, protocol.id(), [])?;
+                    let (child_spec, args) =
+                        helpers::make(conn, &WL_BUFFER_IFACE, protocol.id(), [])?;
                     Ok((
                         Evt {
                             sender: protocol.id(),
-                            opcode: 0u16,
+                            opcode: BUFFER_DESTROY_OPCODE,
                             args,
                         },
                         child_spec,
                     ))
                 }
-                Self::Attach {
-                    buffer,
-                    x_pos,
-                    y_pos,
-                } => {
+                Self::__Phantom { never, .. } => match never {},
+            }
+        }
+    }
+
+    pub enum Event {
+        /// the compositor is done reading the buffer, so it can be drawn into again
+        Release,
+    }
+
+    impl ProtocolEvent for Event {
+        type Protocol = super::WlBuffer;
+
+        fn parse<I>(_protocol: &Self::Protocol, opcode: u16, _iter: I) -> crate::Result<Self>
+        where
+            I: IntoIterator<Item = Arg<ObjectId, std::os::unix::prelude::OwnedFd>>,
+        {
+            match opcode {
+                0u16 => Ok(Event::Release),
+                _ => submit_err!(),
+            }
+        }
+    }
+}
+
+protocol_struct! {
+    pub WlShm {
+        iface: WL_SHM_IFACE,
+        request: shm::Request<'request>,
+        event: shm::Event
+    }
+}
+
+pub mod shm {
+    use super::__prelude::*;
+    use std::os::fd::BorrowedFd;
+
+    pub const SHM_CREATE_POOL_OPCODE: u16 = 0;
+    pub const SHM_RELEASE_OPCODE: u16 = 1;
+
+    /// `wl_shm.format` values. everything but these two is a drm fourcc code
+    pub const FORMAT_ARGB8888: u32 = 0;
+    pub const FORMAT_XRGB8888: u32 = 1;
+
+    pub enum Request<'req> {
+        /// the compositor maps `size` bytes of `fd` too, and keeps its own copy of the fd
+        CreatePool { fd: BorrowedFd<'req>, size: i32 },
+        /// since version 2
+        Release,
+    }
+
+    impl<'req> ProtocolRequest<'req> for Request<'req> {
+        type Protocol = super::WlShm;
+
+        fn write(
+            self,
+            protocol: &Self::Protocol,
+            conn: &crate::wayland::StrongHandle,
+        ) -> crate::Result<(
+            Evt<ObjectId, BorrowedFd<'req>>,
+            Option<(&'static crate::wl::Interface, u32)>,
+        )> {
+            use super::helpers;
+            match self {
+                Self::CreatePool { fd, size } => {
                     let (child_spec, args) = helpers::make(
                         conn,
-                        &WL_BUFFER_IFACE,
+                        &WL_SHM_POOL_IFACE,
                         protocol.id(),
-                        [Arg::Object(buffer), Arg::Int(x_pos), Arg::Int(y_pos)],
+                        [
+                            Arg::New(ObjectId::NIL_OBJECT_ID),
+                            Arg::Fd(fd),
+                            Arg::Int(size),
+                        ],
                     )?;
                     Ok((
                         Evt {
                             sender: protocol.id(),
-                            opcode: 1u16,
+                            opcode: SHM_CREATE_POOL_OPCODE,
                             args,
                         },
                         child_spec,
                     ))
                 }
-                Self::Damage {
-                    x_pos,
-                    y_pos,
+                Self::Release => {
+                    let (child_spec, args) = helpers::make(conn, &WL_SHM_IFACE, protocol.id(), [])?;
+                    Ok((
+                        Evt {
+                            sender: protocol.id(),
+                            opcode: SHM_RELEASE_OPCODE,
+                            args,
+                        },
+                        child_spec,
+                    ))
+                }
+            }
+        }
+    }
+
+    pub enum Event {
+        /// sent once per supported format right after binding
+        Format { format: u32 },
+    }
+
+    impl ProtocolEvent for Event {
+        type Protocol = super::WlShm;
+
+        fn parse<I>(_protocol: &Self::Protocol, opcode: u16, iter: I) -> crate::Result<Self>
+        where
+            I: IntoIterator<Item = Arg<ObjectId, std::os::unix::prelude::OwnedFd>>,
+        {
+            let mut iter = iter.into_iter();
+            match opcode {
+                0u16 => {
+                    expect! {
+                        Arg::Uint(format) = iter => {
+                            return Ok(Event::Format { format });
+                        }
+                    }
+                }
+                _ => submit_err!(),
+            }
+        }
+    }
+}
+
+protocol_struct! {
+    pub WlShmPool {
+        iface: WL_SHM_POOL_IFACE,
+        request: shm_pool::Request<'request>,
+        event: shm_pool::Event
+    }
+}
+
+pub mod shm_pool {
+    use super::__prelude::*;
+
+    pub const SHM_POOL_CREATE_BUFFER_OPCODE: u16 = 0;
+    pub const SHM_POOL_DESTROY_OPCODE: u16 = 1;
+    pub const SHM_POOL_RESIZE_OPCODE: u16 = 2;
+
+    pub enum Request<'req> {
+        /// a `width` by `height` buffer `stride` bytes per row, `offset` bytes into the pool
+        CreateBuffer {
+            offset: i32,
+            width: i32,
+            height: i32,
+            stride: i32,
+            format: u32,
+        },
+        /// buffers made from the pool keep working until they are destroyed themselves
+        Destroy,
+        /// pools can only grow
+        Resize { size: i32 },
+        #[doc(hidden)]
+        __Phantom {
+            _capture: PhantomData<&'req ()>,
+            never: Infallible,
+        },
+    }
+
+    impl<'req> ProtocolRequest<'req> for Request<'req> {
+        type Protocol = super::WlShmPool;
+
+        fn write(
+            self,
+            protocol: &Self::Protocol,
+            conn: &crate::wayland::StrongHandle,
+        ) -> crate::Result<(
+            Evt<ObjectId, std::os::unix::prelude::BorrowedFd<'req>>,
+            Option<(&'static crate::wl::Interface, u32)>,
+        )> {
+            use super::helpers;
+            match self {
+                Self::CreateBuffer {
+                    offset,
                     width,
                     height,
+                    stride,
+                    format,
                 } => {
                     let (child_spec, args) = helpers::make(
                         conn,
-                        &WL_SURFACE_IFACE,
+                        &WL_BUFFER_IFACE,
                         protocol.id(),
                         [
-                            Arg::Int(x_pos),
-                            Arg::Int(y_pos),
+                            Arg::New(ObjectId::NIL_OBJECT_ID),
+                            Arg::Int(offset),
                             Arg::Int(width),
                             Arg::Int(height),
+                            Arg::Int(stride),
+                            Arg::Uint(format),
                         ],
                     )?;
+                    Ok((
+                        Evt {
+                            sender: protocol.id(),
+                            opcode: SHM_POOL_CREATE_BUFFER_OPCODE,
+                            args,
+                        },
+                        child_spec,
+                    ))
+                }
+                Self::Destroy => {
+                    let (child_spec, args) =
+                        helpers::make(conn, &WL_SHM_POOL_IFACE, protocol.id(), [])?;
+                    Ok((
+                        Evt {
+                            sender: protocol.id(),
+                            opcode: SHM_POOL_DESTROY_OPCODE,
+                            args,
+                        },
+                        child_spec,
+                    ))
+                }
+                Self::Resize { size } => {
+                    let (child_spec, args) =
+                        helpers::make(conn, &WL_SHM_POOL_IFACE, protocol.id(), [Arg::Int(size)])?;
+                    Ok((
+                        Evt {
+                            sender: protocol.id(),
+                            opcode: SHM_POOL_RESIZE_OPCODE,
+                            args,
+                        },
+                        child_spec,
+                    ))
                 }
-                Self::Frame => {}
-                Self::SetOpaqueRegion { region } => {}
-                Self::SetInputRegion { region } => {}
-                Self::Commit => {}
-                Self::SetBufferTransform { transform } => {}
-                Self::SetBufferScale { scale } => {}
-                Self::DamageBuffer { x_pos, y_pos } => {}
-                Self::Offset { off_x, off_y } => {}
                 Self::__Phantom { never, .. } => match never {},
             }
         }
     }
+
+    /// `wl_shm_pool` has no events
+    pub enum Event {}
+
+    impl ProtocolEvent for Event {
+        type Protocol = super::WlShmPool;
+
+        fn parse<I>(_protocol: &Self::Protocol, _opcode: u16, _iter: I) -> crate::Result<Self>
+        where
+            I: IntoIterator<Item = Arg<ObjectId, std::os::unix::prelude::OwnedFd>>,
+        {
+            submit_err!()
+        }
+    }
 }